@@ -74,76 +74,118 @@ fn parse_key_string(key_str: &str) -> Result<(Vec<Key>, Option<char>), String> {
     Ok((modifiers, main_key))
 }
 
+/// 解析后的单个物理按键，对应后端实际的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NativeKey {
+    Raw(u16), // 扫描码（Windows）或虚拟键码（macOS）
+    Key(Key),
+}
+
+/// 预先解析好的组合键，播放时不再解析字符串
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub modifiers: Vec<NativeKey>,
+    pub main: NativeKey,
+}
+
+fn modifier_native(modifier: Key) -> NativeKey {
+    #[cfg(target_os = "windows")]
+    if let Some(scancode) = modifier_to_windows_scancode(modifier) {
+        return NativeKey::Raw(scancode);
+    }
+    NativeKey::Key(modifier)
+}
+
+fn char_native(ch: char) -> NativeKey {
+    #[cfg(target_os = "macos")]
+    if let Some(code) = char_to_macos_keycode(ch) {
+        return NativeKey::Raw(code);
+    }
+    #[cfg(target_os = "windows")]
+    if let Some(code) = char_to_windows_scancode(ch) {
+        return NativeKey::Raw(code);
+    }
+    NativeKey::Key(Key::Unicode(ch))
+}
+
+/// 解析组合键字符串，如 "shift+a"
+pub fn resolve_key_combo(key_str: &str) -> Result<KeyCombo, String> {
+    let (modifiers, main_key) = parse_key_string(key_str)?;
+    let main = main_key.ok_or_else(|| format!("Missing main key: {}", key_str))?;
+    Ok(KeyCombo {
+        modifiers: modifiers.into_iter().map(modifier_native).collect(),
+        main: char_native(main),
+    })
+}
+
+/// 解析单个物理按键（"shift"、"a" 等）
+pub fn resolve_single_key(part: &str) -> Result<NativeKey, String> {
+    if part.chars().count() == 1 {
+        return Ok(char_native(part.chars().next().unwrap()));
+    }
+    // 复用组合键解析得到修饰键
+    let (modifiers, _) = parse_key_string(&format!("{}+x", part))?;
+    Ok(modifier_native(modifiers[0]))
+}
+
 pub trait SmartKeyboard {
     fn simulate_keypress_smart(&mut self, key_str: &str) -> Result<(), String>;
+    /// 按下单个物理按键（"shift"、"a" 等），用于按住音符时值的播放
+    fn key_down_smart(&mut self, key: &str) -> Result<(), String>;
+    /// 松开单个物理按键
+    fn key_up_smart(&mut self, key: &str) -> Result<(), String>;
+    /// 发送已解析的单个按键
+    fn native_key(&mut self, key: NativeKey, direction: Direction) -> Result<(), String>;
+    /// 按下并松开已解析的组合键
+    fn tap_combo(&mut self, combo: &KeyCombo) -> Result<(), String>;
 }
 
 impl SmartKeyboard for Enigo {
     fn simulate_keypress_smart(&mut self, key_str: &str) -> Result<(), String> {
-        let (modifiers, main_key) = parse_key_string(key_str)?;
+        let combo = resolve_key_combo(key_str)?;
+        self.tap_combo(&combo)
+    }
+
+    fn key_down_smart(&mut self, key: &str) -> Result<(), String> {
+        let key = resolve_single_key(key)?;
+        self.native_key(key, Direction::Press)
+    }
+
+    fn key_up_smart(&mut self, key: &str) -> Result<(), String> {
+        let key = resolve_single_key(key)?;
+        self.native_key(key, Direction::Release)
+    }
 
+    fn native_key(&mut self, key: NativeKey, direction: Direction) -> Result<(), String> {
+        match key {
+            NativeKey::Raw(code) => self.raw(code, direction).map_err(|e| format!("{:?}",e)),
+            NativeKey::Key(key) => self.key(key, direction).map_err(|e| format!("{:?}",e)),
+        }
+    }
+
+    fn tap_combo(&mut self, combo: &KeyCombo) -> Result<(), String> {
         // Press modifiers
-        for modifier in &modifiers {
-             #[cfg(target_os = "windows")]
-             {
-                 if let Some(scancode) = modifier_to_windows_scancode(*modifier) {
-                     self.raw(scancode, Direction::Press).map_err(|e| format!("{:?}",e))?;
-                 } else {
-                     self.key(*modifier, Direction::Press).map_err(|e| format!("{:?}",e))?;
-                 }
-             }
-
-             #[cfg(not(target_os = "windows"))]
-             {
-                 self.key(*modifier, Direction::Press).map_err(|e| format!("{:?}",e))?;
-             }
-             
+        for modifier in &combo.modifiers {
+             self.native_key(*modifier, Direction::Press)?;
              thread::sleep(Duration::from_millis(5));
         }
 
-        if !modifiers.is_empty() { thread::sleep(Duration::from_millis(10)); }
+        if !combo.modifiers.is_empty() { thread::sleep(Duration::from_millis(10)); }
 
-        if let Some(ch) = main_key {
-            #[cfg(target_os = "macos")]
-            if let Some(code) = char_to_macos_keycode(ch) {
-                self.raw(code, Direction::Press).map_err(|e| format!("{:?}",e))?;
+        match combo.main {
+            NativeKey::Raw(_) => {
+                self.native_key(combo.main, Direction::Press)?;
                 thread::sleep(Duration::from_millis(20)); // Short hold
-                self.raw(code, Direction::Release).map_err(|e| format!("{:?}",e))?;
-            } else {
-                 self.key(Key::Unicode(ch), Direction::Click).map_err(|e| format!("{:?}",e))?;
+                self.native_key(combo.main, Direction::Release)?;
             }
-
-            #[cfg(target_os = "windows")]
-            if let Some(code) = char_to_windows_scancode(ch) {
-                self.raw(code, Direction::Press).map_err(|e| format!("{:?}",e))?;
-                thread::sleep(Duration::from_millis(20));
-                self.raw(code, Direction::Release).map_err(|e| format!("{:?}",e))?;
-            } else {
-                 self.key(Key::Unicode(ch), Direction::Click).map_err(|e| format!("{:?}",e))?;
-            }
-            
-            #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-            self.key(Key::Unicode(ch), Direction::Click).map_err(|e| format!("{:?}",e))?;
+            NativeKey::Key(_) => self.native_key(combo.main, Direction::Click)?,
         }
 
-        if !modifiers.is_empty() { thread::sleep(Duration::from_millis(10)); }
+        if !combo.modifiers.is_empty() { thread::sleep(Duration::from_millis(10)); }
 
         // Release modifiers
-        for modifier in modifiers.iter().rev() {
-             #[cfg(target_os = "windows")]
-             {
-                 if let Some(scancode) = modifier_to_windows_scancode(*modifier) {
-                     self.raw(scancode, Direction::Release).map_err(|e| format!("{:?}",e))?;
-                 } else {
-                     self.key(*modifier, Direction::Release).map_err(|e| format!("{:?}",e))?;
-                 }
-             }
-
-             #[cfg(not(target_os = "windows"))]
-             {
-                 self.key(*modifier, Direction::Release).map_err(|e| format!("{:?}",e))?;
-             }
-
+        for modifier in combo.modifiers.iter().rev() {
+             self.native_key(*modifier, Direction::Release)?;
              thread::sleep(Duration::from_millis(30));
         }
 
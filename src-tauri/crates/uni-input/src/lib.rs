@@ -11,8 +11,8 @@ use uni_window::WindowInfo;
 pub mod mouse;
 pub mod keyboard;
 
-pub use mouse::SmoothMouse;
-pub use keyboard::SmartKeyboard;
+pub use mouse::{ClickTiming, DragWaypoint, SmoothMouse, TracePoint};
+pub use keyboard::{KeyCombo, NativeKey, SmartKeyboard};
 
 pub struct InputController {
     pub enigo: Enigo,
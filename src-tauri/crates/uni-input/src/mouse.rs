@@ -1,7 +1,7 @@
 use enigo::{Button, Coordinate, Direction, Enigo, Mouse};
 use rand::Rng;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// 生成贝塞尔曲线路径
 /// 使用二次贝塞尔曲线在起点和终点之间生成平滑路径
@@ -46,17 +46,199 @@ fn generate_bezier_path(
     path
 }
 
-/// 为坐标添加随机偏移（±5像素）
-fn add_coordinate_offset(x: i32, y: i32) -> (i32, i32) {
-    let mut rng = rand::thread_rng();
-    let offset_x = rng.gen_range(-5..=5);
-    let offset_y = rng.gen_range(-5..=5);
-    (x + offset_x, y + offset_y)
+/// 录制的真人鼠标轨迹上的一个点，t_ms 从轨迹开始算起
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TracePoint {
+    pub x: f64,
+    pub y: f64,
+    pub t_ms: f64,
+}
+
+/// 把录制的轨迹旋转、缩放到 start 和 end 之间，返回 (x, y, 毫秒)
+/// 按复数乘法做相似变换，轨迹的弯曲、过冲和修正按比例保留；
+/// 移动距离变化时用时按距离比例的平方根变化（距离越远平均速度越快）
+pub fn fit_trace(
+    trace: &[TracePoint],
+    start: (i32, i32),
+    end: (i32, i32),
+) -> Option<Vec<(i32, i32, f64)>> {
+    let (first, last) = (trace.first()?, trace.last()?);
+    let (vx, vy) = (last.x - first.x, last.y - first.y);
+    let (wx, wy) = ((end.0 - start.0) as f64, (end.1 - start.1) as f64);
+    let source_length = vx.hypot(vy);
+    let target_length = wx.hypot(wy);
+    if source_length < 1.0 || target_length < 1.0 {
+        return None;
+    }
+    // w / v
+    let norm = vx * vx + vy * vy;
+    let (a, b) = ((wx * vx + wy * vy) / norm, (wy * vx - wx * vy) / norm);
+    let time_scale = (target_length / source_length).sqrt();
+
+    let mut path: Vec<(i32, i32, f64)> = trace
+        .iter()
+        .map(|p| {
+            let (dx, dy) = (p.x - first.x, p.y - first.y);
+            let x = start.0 as f64 + dx * a - dy * b;
+            let y = start.1 as f64 + dx * b + dy * a;
+            (
+                x.round() as i32,
+                y.round() as i32,
+                (p.t_ms - first.t_ms) * time_scale,
+            )
+        })
+        .collect();
+    // 终点取整误差
+    if let Some(last) = path.last_mut() {
+        (last.0, last.1) = end;
+    }
+    Some(path)
+}
+
+/// 拖动经过的点，duration_ms 为从上一个点移动到这里所用的时间
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DragWaypoint {
+    pub x: i32,
+    pub y: i32,
+    pub duration_ms: f64,
+}
+
+// 拖动时每隔这么久移动一次
+const DRAG_STEP_MS: f64 = 10.0;
+
+/// 生成经过所有点的拖动路径，返回 (x, y, 毫秒)
+/// 用 Catmull-Rom 样条连接各段，经过途经点时速度方向连续，不会在每个点上折返停顿；
+/// 每段按自己的用时均匀取点
+pub fn drag_path(start: (i32, i32), waypoints: &[DragWaypoint]) -> Vec<(i32, i32, f64)> {
+    let points: Vec<(f64, f64)> = std::iter::once(start)
+        .chain(waypoints.iter().map(|w| (w.x, w.y)))
+        .map(|(x, y)| (x as f64, y as f64))
+        .collect();
+    let mut path = vec![(start.0, start.1, 0.0)];
+    let mut elapsed = 0.0;
+    for (i, waypoint) in waypoints.iter().enumerate() {
+        // 段的两端 p1 -> p2，首尾缺少的相邻点用端点本身代替
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        let duration = waypoint.duration_ms.max(0.0);
+        let steps = ((duration / DRAG_STEP_MS).ceil() as usize).max(1);
+        for step in 1..=steps {
+            let t = step as f64 / steps as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            let spline = |a: f64, b: f64, c: f64, d: f64| {
+                0.5 * (2.0 * b
+                    + (c - a) * t
+                    + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+                    + (3.0 * b - a - 3.0 * c + d) * t3)
+            };
+            let x = spline(p0.0, p1.0, p2.0, p3.0);
+            let y = spline(p0.1, p1.1, p2.1, p3.1);
+            path.push((x.round() as i32, y.round() as i32, elapsed + duration * t));
+        }
+        elapsed += duration;
+        // 途经点取整误差
+        if let Some(last) = path.last_mut() {
+            (last.0, last.1) = (waypoint.x, waypoint.y);
+        }
+    }
+    path
+}
+
+/// 点击的落点和节奏：落点按二维正态分布偏离目标中心，
+/// 移动到位后的停顿和按住时长按对数正态分布（有下限、右侧长尾，和真人一致）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClickTiming {
+    pub offset_sigma_px: f64, // 落点偏移的标准差（像素），0 表示不偏移
+    pub offset_limit_px: f64, // 落点最多偏离这么远，保证落在目标内
+    pub settle_median_ms: f64, // 移动到位到按下的停顿的中位数
+    pub settle_sigma: f64,     // 对数正态分布的形状参数，0 表示固定时长
+    pub dwell_median_ms: f64,  // 按住时长的中位数，0 表示按下立即松开
+    pub dwell_sigma: f64,
+}
+
+impl Default for ClickTiming {
+    fn default() -> Self {
+        Self {
+            offset_sigma_px: 3.0,
+            offset_limit_px: 6.0,
+            settle_median_ms: 20.0,
+            settle_sigma: 0.3,
+            dwell_median_ms: 50.0,
+            dwell_sigma: 0.25,
+        }
+    }
+}
+
+// Box-Muller 变换得到一对独立的标准正态分布随机数
+fn standard_normal_pair(rng: &mut impl Rng) -> (f64, f64) {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    let radius = (-2.0 * u1.ln()).sqrt();
+    let angle = 2.0 * std::f64::consts::PI * u2;
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+fn log_normal_ms(rng: &mut impl Rng, median_ms: f64, sigma: f64) -> Duration {
+    if median_ms <= 0.0 {
+        return Duration::ZERO;
+    }
+    let ms = median_ms * (sigma.max(0.0) * standard_normal_pair(rng).0).exp();
+    Duration::from_secs_f64(ms / 1000.0)
+}
+
+impl ClickTiming {
+    /// 在目标周围取一个落点，超出限制时按比例收回到限制圆上
+    pub fn aim(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.offset_sigma_px <= 0.0 {
+            return (x, y);
+        }
+        let (gx, gy) = standard_normal_pair(&mut rand::thread_rng());
+        let (mut dx, mut dy) = (gx * self.offset_sigma_px, gy * self.offset_sigma_px);
+        let distance = dx.hypot(dy);
+        if self.offset_limit_px > 0.0 && distance > self.offset_limit_px {
+            dx *= self.offset_limit_px / distance;
+            dy *= self.offset_limit_px / distance;
+        }
+        (x + dx.round() as i32, y + dy.round() as i32)
+    }
+
+    /// 目标半径已知时按比例缩放偏移，落点不超出目标
+    pub fn within_radius(&self, radius: f64) -> Self {
+        let radius = radius.max(0.0);
+        let scale = if self.offset_limit_px > 0.0 {
+            radius / self.offset_limit_px
+        } else {
+            0.0
+        };
+        Self {
+            offset_sigma_px: self.offset_sigma_px * scale,
+            offset_limit_px: radius,
+            ..*self
+        }
+    }
+
+    pub fn settle(&self) -> Duration {
+        log_normal_ms(&mut rand::thread_rng(), self.settle_median_ms, self.settle_sigma)
+    }
+
+    pub fn dwell(&self) -> Duration {
+        log_normal_ms(&mut rand::thread_rng(), self.dwell_median_ms, self.dwell_sigma)
+    }
 }
 
 pub trait SmoothMouse {
     fn mouse_move_smooth(&mut self, target_x: i32, target_y: i32, total_duration_ms: u64) -> Result<(), String>;
-    fn mouse_click_smooth(&mut self, target_x: i32, target_y: i32) -> Result<(), String>;
+    fn mouse_click_smooth(&mut self, target_x: i32, target_y: i32, timing: &ClickTiming) -> Result<(), String>;
+    /// 沿录制的真人轨迹移动，轨迹无法使用（距离过近）时退回贝塞尔曲线
+    fn mouse_move_trace(&mut self, target_x: i32, target_y: i32, trace: &[TracePoint]) -> Result<(), String>;
+    fn mouse_click_trace(&mut self, target_x: i32, target_y: i32, trace: &[TracePoint], timing: &ClickTiming) -> Result<(), String>;
+    /// 移动到目标附近但不点击（悬停），trace 为 None 时用贝塞尔曲线
+    fn mouse_hover(&mut self, target_x: i32, target_y: i32, trace: Option<&[TracePoint]>, timing: &ClickTiming) -> Result<(), String>;
+    /// 停顿后按下，按住一段时间再松开
+    fn mouse_press_humanized(&mut self, timing: &ClickTiming) -> Result<(), String>;
+    /// 移动到起点按下，依次经过途经点后松开。起点的随机偏移同样加到所有途经点上，保持手势形状
+    fn mouse_drag(&mut self, start_x: i32, start_y: i32, waypoints: &[DragWaypoint], timing: &ClickTiming) -> Result<(), String>;
 }
 
 impl SmoothMouse for Enigo {
@@ -66,11 +248,8 @@ impl SmoothMouse for Enigo {
             .location()
             .map_err(|e| format!("Failed to get mouse location: {:?}", e))?;
 
-        // 应用坐标偏移 (Ref Logic)
-        // Note: Reference `simulate_mouse_click` combined move + click + duration logic.
-        // Here we extract move logic.
-        
-        let (target_x, target_y) = add_coordinate_offset(x, y);
+        // 落点偏移由调用方按 ClickTiming 决定
+        let (target_x, target_y) = (x, y);
 
         let dx = target_x - current_x;
         let dy = target_y - current_y;
@@ -95,15 +274,93 @@ impl SmoothMouse for Enigo {
         Ok(())
     }
 
-    fn mouse_click_smooth(&mut self, target_x: i32, target_y: i32) -> Result<(), String> {
-        self.mouse_move_smooth(target_x, target_y, 200)?;
-        
-        // Small delay before click like reference
-        thread::sleep(Duration::from_millis(20));
-        
-        self.button(Button::Left, Direction::Click)
-            .map_err(|e| format!("Failed to click mouse: {:?}", e))?;
-            
+    fn mouse_click_smooth(&mut self, target_x: i32, target_y: i32, timing: &ClickTiming) -> Result<(), String> {
+        let (x, y) = timing.aim(target_x, target_y);
+        self.mouse_move_smooth(x, y, 200)?;
+        self.mouse_press_humanized(timing)
+    }
+
+    fn mouse_move_trace(&mut self, x: i32, y: i32, trace: &[TracePoint]) -> Result<(), String> {
+        let start = self
+            .location()
+            .map_err(|e| format!("Failed to get mouse location: {:?}", e))?;
+        let Some(path) = fit_trace(trace, start, (x, y)) else {
+            return self.mouse_move_smooth(x, y, 200);
+        };
+
+        // 按录制时的节奏移动
+        let begin = Instant::now();
+        for (px, py, t_ms) in path {
+            let due = Duration::from_secs_f64(t_ms.max(0.0) / 1000.0);
+            if let Some(wait) = due.checked_sub(begin.elapsed()) {
+                thread::sleep(wait);
+            }
+            self.move_mouse(px, py, Coordinate::Abs)
+                .map_err(|e| format!("Failed to move mouse: {:?}", e))?;
+        }
         Ok(())
     }
+
+    fn mouse_click_trace(&mut self, target_x: i32, target_y: i32, trace: &[TracePoint], timing: &ClickTiming) -> Result<(), String> {
+        let (x, y) = timing.aim(target_x, target_y);
+        self.mouse_move_trace(x, y, trace)?;
+        self.mouse_press_humanized(timing)
+    }
+
+    fn mouse_hover(&mut self, target_x: i32, target_y: i32, trace: Option<&[TracePoint]>, timing: &ClickTiming) -> Result<(), String> {
+        let (x, y) = timing.aim(target_x, target_y);
+        match trace {
+            Some(trace) => self.mouse_move_trace(x, y, trace),
+            None => self.mouse_move_smooth(x, y, 200),
+        }
+    }
+
+    fn mouse_drag(&mut self, start_x: i32, start_y: i32, waypoints: &[DragWaypoint], timing: &ClickTiming) -> Result<(), String> {
+        let (x, y) = timing.aim(start_x, start_y);
+        let (dx, dy) = (x - start_x, y - start_y);
+        let waypoints: Vec<DragWaypoint> = waypoints
+            .iter()
+            .map(|w| DragWaypoint { x: w.x + dx, y: w.y + dy, ..*w })
+            .collect();
+        self.mouse_move_smooth(x, y, 200)?;
+
+        thread::sleep(timing.settle());
+        self.button(Button::Left, Direction::Press)
+            .map_err(|e| format!("Failed to press mouse: {:?}", e))?;
+        let begin = Instant::now();
+        let mut moved = Ok(());
+        for (px, py, t_ms) in drag_path((x, y), &waypoints) {
+            let due = Duration::from_secs_f64(t_ms / 1000.0);
+            if let Some(wait) = due.checked_sub(begin.elapsed()) {
+                thread::sleep(wait);
+            }
+            moved = self
+                .move_mouse(px, py, Coordinate::Abs)
+                .map_err(|e| format!("Failed to move mouse: {:?}", e));
+            if moved.is_err() {
+                break;
+            }
+        }
+        // 移动失败时也要松开，避免按键卡住
+        thread::sleep(timing.settle());
+        let released = self
+            .button(Button::Left, Direction::Release)
+            .map_err(|e| format!("Failed to release mouse: {:?}", e));
+        moved.and(released)
+    }
+
+    fn mouse_press_humanized(&mut self, timing: &ClickTiming) -> Result<(), String> {
+        thread::sleep(timing.settle());
+        let dwell = timing.dwell();
+        if dwell.is_zero() {
+            return self
+                .button(Button::Left, Direction::Click)
+                .map_err(|e| format!("Failed to click mouse: {:?}", e));
+        }
+        self.button(Button::Left, Direction::Press)
+            .map_err(|e| format!("Failed to press mouse: {:?}", e))?;
+        thread::sleep(dwell);
+        self.button(Button::Left, Direction::Release)
+            .map_err(|e| format!("Failed to release mouse: {:?}", e))
+    }
 }
@@ -9,12 +9,35 @@ pub struct WindowInfo {
     pub pid: u32,
     pub title: String,
     pub app_name: String,
+    #[serde(default)]
+    pub x: i32,        // 窗口左上角屏幕坐标
+    #[serde(default)]
+    pub y: i32,
     pub width: u32,
     pub height: u32,
     pub is_minimized: bool,
     pub is_maximized: bool,
 }
 
+/// 矩形区域（屏幕坐标或相对窗口的坐标）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    /// 是否完全位于 width x height 的区域内
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        self.x >= 0
+            && self.y >= 0
+            && self.x as i64 + self.width as i64 <= width as i64
+            && self.y as i64 + self.height as i64 <= height as i64
+    }
+}
+
 pub fn enumerate_windows() -> Result<Vec<WindowInfo>, Box<dyn Error>> {
     let windows = Window::all()?;
     let infos = windows.into_iter().map(|w| WindowInfo {
@@ -22,6 +45,8 @@ pub fn enumerate_windows() -> Result<Vec<WindowInfo>, Box<dyn Error>> {
         pid: w.pid().unwrap_or(0),
         title: w.title().unwrap_or_default(),
         app_name: w.app_name().unwrap_or_default(),
+        x: w.x().unwrap_or(0),
+        y: w.y().unwrap_or(0),
         width: w.width().unwrap_or(0),
         height: w.height().unwrap_or(0),
         is_minimized: w.is_minimized().unwrap_or(false),
@@ -30,6 +55,83 @@ pub fn enumerate_windows() -> Result<Vec<WindowInfo>, Box<dyn Error>> {
     Ok(infos)
 }
 
+/// 显示器信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    pub id: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32, // 系统缩放比例，如 1.25 表示 125%
+    pub is_primary: bool,
+}
+
+pub fn enumerate_monitors() -> Result<Vec<MonitorInfo>, Box<dyn Error>> {
+    let monitors = xcap::Monitor::all()?;
+    let infos = monitors.into_iter().map(|m| MonitorInfo {
+        id: m.id().unwrap_or(0),
+        name: m.name().unwrap_or_default(),
+        x: m.x().unwrap_or(0),
+        y: m.y().unwrap_or(0),
+        width: m.width().unwrap_or(0),
+        height: m.height().unwrap_or(0),
+        scale_factor: m.scale_factor().unwrap_or(1.0),
+        is_primary: m.is_primary().unwrap_or(false),
+    }).collect();
+    Ok(infos)
+}
+
+/// 显示器上的物理像素坐标，相对显示器左上角。
+/// 混合缩放的多显示器上全局坐标的换算因显示器而异，按显示器记录后回放时再换算回全局坐标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorPoint {
+    pub monitor_id: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+// 全局坐标的一个单位对应多少物理像素。
+// macOS 的全局坐标是逻辑点；Windows 上本程序按每显示器 DPI 感知运行，Linux (X11) 上
+// 全局坐标本身就是物理像素
+fn physical_ratio(monitor: &xcap::Monitor) -> f64 {
+    if cfg!(target_os = "macos") {
+        monitor.scale_factor().map(f64::from).unwrap_or(1.0)
+    } else {
+        1.0
+    }
+}
+
+/// 把全局坐标换算为所在显示器上的物理像素坐标
+pub fn to_monitor_point(x: i32, y: i32) -> Result<MonitorPoint, Box<dyn Error>> {
+    let monitor = xcap::Monitor::from_point(x, y)?;
+    let ratio = physical_ratio(&monitor);
+    Ok(MonitorPoint {
+        monitor_id: monitor.id()?,
+        x: ((x - monitor.x()?) as f64 * ratio).round() as i32,
+        y: ((y - monitor.y()?) as f64 * ratio).round() as i32,
+    })
+}
+
+/// 把显示器上的物理像素坐标换算回全局坐标，显示器已断开时返回错误
+pub fn from_monitor_point(point: &MonitorPoint) -> Result<(i32, i32), Box<dyn Error>> {
+    let monitor = xcap::Monitor::all()?
+        .into_iter()
+        .find(|m| m.id().ok() == Some(point.monitor_id))
+        .ok_or_else(|| format!("Monitor {} is not connected", point.monitor_id))?;
+    let ratio = physical_ratio(&monitor);
+    Ok((
+        monitor.x()? + (point.x as f64 / ratio).round() as i32,
+        monitor.y()? + (point.y as f64 / ratio).round() as i32,
+    ))
+}
+
+/// 按 ID 重新获取窗口的最新信息，窗口已不存在时返回 None
+pub fn find_window(id: u32) -> Result<Option<WindowInfo>, Box<dyn Error>> {
+    Ok(enumerate_windows()?.into_iter().find(|w| w.id == id))
+}
+
 #[cfg(target_os = "windows")]
 pub fn activate_window(id: u32) -> Result<(), Box<dyn Error>> {
     use windows::Win32::Foundation::HWND;
@@ -45,6 +147,374 @@ pub fn activate_window(id: u32) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// 获取屏幕坐标处最上层的顶级窗口
+#[cfg(target_os = "windows")]
+pub fn window_at_point(x: i32, y: i32) -> Result<Option<WindowInfo>, Box<dyn Error>> {
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetAncestor, WindowFromPoint, GA_ROOT};
+
+    // WindowFromPoint 可能返回子控件，取其顶级窗口
+    let hwnd = unsafe { GetAncestor(WindowFromPoint(POINT { x, y }), GA_ROOT) };
+    if hwnd.0.is_null() {
+        return Ok(None);
+    }
+    find_window(hwnd.0 as usize as u32)
+}
+
+/// 获取屏幕坐标处最上层的顶级窗口
+/// xcap 按从前到后的顺序返回窗口，取第一个包含该点的窗口
+#[cfg(not(target_os = "windows"))]
+pub fn window_at_point(x: i32, y: i32) -> Result<Option<WindowInfo>, Box<dyn Error>> {
+    Ok(enumerate_windows()?.into_iter().find(|w| {
+        !w.is_minimized
+            && x >= w.x
+            && y >= w.y
+            && ((x - w.x) as i64) < w.width as i64
+            && ((y - w.y) as i64) < w.height as i64
+    }))
+}
+
+/// 子窗口信息（部分启动器把游戏画面放在子窗口中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildWindowInfo {
+    pub id: u32,
+    pub parent_id: u32,
+    pub class_name: String,
+    pub title: String,
+    pub rect: Rect, // 相对父窗口左上角
+    pub is_visible: bool,
+}
+
+/// 枚举窗口的所有子窗口（包括嵌套的子窗口）
+#[cfg(target_os = "windows")]
+pub fn enumerate_child_windows(parent_id: u32) -> Result<Vec<ChildWindowInfo>, Box<dyn Error>> {
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumChildWindows, GetClassNameW, GetWindowRect, GetWindowTextW, IsWindowVisible,
+    };
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let handles = &mut *(lparam.0 as *mut Vec<HWND>);
+        handles.push(hwnd);
+        BOOL(1)
+    }
+
+    let parent = HWND(parent_id as usize as _);
+    let mut handles: Vec<HWND> = Vec::new();
+    let mut parent_rect = RECT::default();
+
+    unsafe {
+        GetWindowRect(parent, &mut parent_rect)?;
+        let _ = EnumChildWindows(parent, Some(collect), LPARAM(&mut handles as *mut Vec<HWND> as isize));
+    }
+
+    let mut children = Vec::new();
+    for hwnd in handles {
+        let mut rect = RECT::default();
+        if unsafe { GetWindowRect(hwnd, &mut rect) }.is_err() {
+            continue;
+        }
+
+        let mut class_buf = [0u16; 256];
+        let class_len = unsafe { GetClassNameW(hwnd, &mut class_buf) }.max(0) as usize;
+        let mut title_buf = [0u16; 512];
+        let title_len = unsafe { GetWindowTextW(hwnd, &mut title_buf) }.max(0) as usize;
+
+        children.push(ChildWindowInfo {
+            id: hwnd.0 as usize as u32,
+            parent_id,
+            class_name: String::from_utf16_lossy(&class_buf[..class_len]),
+            title: String::from_utf16_lossy(&title_buf[..title_len]),
+            rect: Rect {
+                x: rect.left - parent_rect.left,
+                y: rect.top - parent_rect.top,
+                width: (rect.right - rect.left).max(0) as u32,
+                height: (rect.bottom - rect.top).max(0) as u32,
+            },
+            is_visible: unsafe { IsWindowVisible(hwnd) }.as_bool(),
+        });
+    }
+    Ok(children)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn enumerate_child_windows(_parent_id: u32) -> Result<Vec<ChildWindowInfo>, Box<dyn Error>> {
+    Err("Child window enumeration is only supported on Windows".into())
+}
+
+/// 按平台激活窗口（Windows 使用窗口句柄，macOS 使用进程 PID）
+pub fn activate_window_info(window: &WindowInfo) -> Result<(), Box<dyn Error>> {
+    #[cfg(target_os = "windows")]
+    activate_window(window.id)?;
+
+    #[cfg(target_os = "macos")]
+    activate_window_by_pid(window.pid)?;
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let _ = window;
+
+    Ok(())
+}
+
+/// 设置窗口置顶
+#[cfg(target_os = "windows")]
+pub fn set_window_topmost(id: u32, topmost: bool) -> Result<(), Box<dyn Error>> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SetWindowPos, HWND_NOTOPMOST, HWND_TOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+    };
+
+    let hwnd = HWND(id as _);
+    let insert_after = if topmost { HWND_TOPMOST } else { HWND_NOTOPMOST };
+    unsafe {
+        SetWindowPos(hwnd, insert_after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE)?;
+    }
+    Ok(())
+}
+
+/// 设置窗口不透明度（0.1-1.0），1.0 时恢复为普通窗口
+#[cfg(target_os = "windows")]
+pub fn set_window_opacity(id: u32, opacity: f32) -> Result<(), Box<dyn Error>> {
+    use windows::Win32::Foundation::{COLORREF, HWND};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongW, SetLayeredWindowAttributes, SetWindowLongW, GWL_EXSTYLE, LWA_ALPHA,
+        WS_EX_LAYERED,
+    };
+
+    let hwnd = HWND(id as _);
+    // 限制最小值，避免窗口完全不可见后无法找回
+    let opacity = opacity.clamp(0.1, 1.0);
+
+    unsafe {
+        let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE);
+        if opacity >= 1.0 {
+            SetWindowLongW(hwnd, GWL_EXSTYLE, ex_style & !(WS_EX_LAYERED.0 as i32));
+            return Ok(());
+        }
+        SetWindowLongW(hwnd, GWL_EXSTYLE, ex_style | WS_EX_LAYERED.0 as i32);
+        SetLayeredWindowAttributes(hwnd, COLORREF(0), (opacity * 255.0).round() as u8, LWA_ALPHA)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn set_window_topmost(_id: u32, _topmost: bool) -> Result<(), Box<dyn Error>> {
+    Err("Setting other windows topmost is only supported on Windows".into())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn set_window_opacity(_id: u32, _opacity: f32) -> Result<(), Box<dyn Error>> {
+    Err("Setting window opacity is only supported on Windows".into())
+}
+
+/// 窗口显示模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,          // 无边框全屏（覆盖整个显示器的普通窗口）
+    ExclusiveFullscreen, // 独占全屏，通常会阻止切换焦点和覆盖层
+}
+
+/// 检测窗口是窗口化、无边框全屏还是独占全屏
+#[cfg(target_os = "windows")]
+pub fn display_mode(window: &WindowInfo) -> Result<DisplayMode, Box<dyn Error>> {
+    use windows::Win32::Foundation::{HWND, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+    };
+    use windows::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_RUNNING_D3D_FULL_SCREEN};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowLongW, GetWindowRect, GWL_STYLE, WS_CAPTION,
+    };
+
+    let hwnd = HWND(window.id as usize as _);
+    unsafe {
+        let mut rect = RECT::default();
+        GetWindowRect(hwnd, &mut rect)?;
+
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return Err("Failed to get monitor info".into());
+        }
+
+        let screen = info.rcMonitor;
+        let covers_monitor = rect.left <= screen.left
+            && rect.top <= screen.top
+            && rect.right >= screen.right
+            && rect.bottom >= screen.bottom;
+        let style = GetWindowLongW(hwnd, GWL_STYLE) as u32;
+        let has_caption = style & WS_CAPTION.0 == WS_CAPTION.0;
+
+        if !covers_monitor || has_caption {
+            return Ok(DisplayMode::Windowed);
+        }
+
+        // 系统只对前台应用报告 D3D 独占全屏状态
+        if GetForegroundWindow() == hwnd
+            && SHQueryUserNotificationState()? == QUNS_RUNNING_D3D_FULL_SCREEN
+        {
+            return Ok(DisplayMode::ExclusiveFullscreen);
+        }
+    }
+    Ok(DisplayMode::Borderless)
+}
+
+/// 其他平台无法区分独占全屏，只按窗口是否覆盖整个显示器判断
+#[cfg(not(target_os = "windows"))]
+pub fn display_mode(window: &WindowInfo) -> Result<DisplayMode, Box<dyn Error>> {
+    let target = Window::all()?
+        .into_iter()
+        .find(|w| w.id().unwrap_or(0) == window.id)
+        .ok_or("Window not found")?;
+    let monitor = target.current_monitor()?;
+
+    let covers_monitor = window.x <= monitor.x()?
+        && window.y <= monitor.y()?
+        && window.x as i64 + window.width as i64 >= monitor.x()? as i64 + monitor.width()? as i64
+        && window.y as i64 + window.height as i64 >= monitor.y()? as i64 + monitor.height()? as i64;
+
+    Ok(if covers_monitor {
+        DisplayMode::Borderless
+    } else {
+        DisplayMode::Windowed
+    })
+}
+
+/// 读取屏幕坐标处的像素颜色 (r, g, b)，同一显示器上的点只截图一次
+pub fn pixel_colors(points: &[(i32, i32)]) -> Result<Vec<[u8; 3]>, Box<dyn Error>> {
+    let mut colors = vec![[0u8; 3]; points.len()];
+    let mut pending: Vec<usize> = (0..points.len()).collect();
+    while let Some(&first) = pending.first() {
+        let (x, y) = points[first];
+        let monitor = xcap::Monitor::from_point(x, y)?;
+        let (left, top) = (monitor.x()?, monitor.y()?);
+        let (width, height) = (monitor.width()?, monitor.height()?);
+        let image = monitor.capture_image()?;
+        // 截图按物理像素，显示器尺寸可能是缩放后的逻辑像素
+        let scale_x = image.width() as f64 / width.max(1) as f64;
+        let scale_y = image.height() as f64 / height.max(1) as f64;
+
+        let mut rest = Vec::new();
+        for index in pending {
+            let (x, y) = points[index];
+            let (dx, dy) = (x as i64 - left as i64, y as i64 - top as i64);
+            if dx < 0 || dy < 0 || dx >= width as i64 || dy >= height as i64 {
+                if index == first {
+                    return Err(format!("Point ({}, {}) is outside all monitors", x, y).into());
+                }
+                rest.push(index);
+                continue;
+            }
+            let pixel = image
+                .get_pixel_checked((dx as f64 * scale_x) as u32, (dy as f64 * scale_y) as u32)
+                .ok_or_else(|| format!("Point ({}, {}) is outside the screenshot", x, y))?;
+            colors[index] = [pixel.0[0], pixel.0[1], pixel.0[2]];
+        }
+        pending = rest;
+    }
+    Ok(colors)
+}
+
+/// 判断进程是否仍在运行
+#[cfg(target_os = "windows")]
+pub fn is_process_alive(pid: u32) -> bool {
+    use windows::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+            Ok(handle) => handle,
+            // 权限不足（如以管理员运行的游戏）时无法判断，视为仍在运行
+            Err(_) => return true,
+        };
+        let mut exit_code = 0u32;
+        let alive = GetExitCodeProcess(handle, &mut exit_code).is_err()
+            || exit_code == STILL_ACTIVE.0 as u32;
+        let _ = CloseHandle(handle);
+        alive
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn is_process_alive(pid: u32) -> bool {
+    std::path::Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(true)
+}
+
+/// 当前进程是否以管理员权限运行
+/// 以管理员运行的游戏会拦截非管理员进程发送的模拟输入
+#[cfg(target_os = "windows")]
+pub fn is_elevated() -> Result<bool, Box<dyn Error>> {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token)?;
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut returned = 0u32;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        );
+        let _ = CloseHandle(token);
+        result?;
+        Ok(elevation.TokenIsElevated != 0)
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_elevated() -> Result<bool, Box<dyn Error>> {
+    Err("Elevation check is only supported on Windows".into())
+}
+
+/// 判断窗口当前是否位于前台
+#[cfg(target_os = "windows")]
+pub fn is_window_foreground(window: &WindowInfo) -> Result<bool, Box<dyn Error>> {
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    let hwnd = unsafe { GetForegroundWindow() };
+    Ok(hwnd.0 as usize as u32 == window.id)
+}
+
+/// 判断窗口当前是否位于前台（macOS 下按前台应用的 PID 比较）
+#[cfg(target_os = "macos")]
+pub fn is_window_foreground(window: &WindowInfo) -> Result<bool, Box<dyn Error>> {
+    let output = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg("tell application \"System Events\" to get unix id of first process whose frontmost is true")
+        .output()?;
+    let pid: u32 = String::from_utf8_lossy(&output.stdout).trim().parse()?;
+    Ok(pid == window.pid)
+}
+
+/// 其他平台无法检测前台窗口，视为已在前台
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn is_window_foreground(_window: &WindowInfo) -> Result<bool, Box<dyn Error>> {
+    Ok(true)
+}
+
 #[cfg(target_os = "macos")]
 pub fn activate_window_by_pid(pid: u32) -> Result<(), Box<dyn Error>> {
     // use objc2_app_kit::{NSRunningApplication, NSApplicationActivationOptions};
@@ -0,0 +1,190 @@
+use crate::error::AppError;
+use crate::input_hook;
+use crate::input_service::InputHandle;
+use crate::keypress_simulator::KeyEvent;
+use crate::state::PlaybackControl;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use uni_input::keyboard::resolve_key_combo;
+use uni_input::KeyCombo;
+
+// 循环伴奏：把一小段伴奏型（如 4 小节的和弦）在主旋律下方反复播放。
+// 伴奏在独立线程中运行，时间跟随主旋律的播放位置，主旋律暂停、跳转、停止时伴奏一起变化。
+
+// 等待下一个伴奏音时的最长休眠
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+// 播放位置比下一个伴奏音超前这么多时视为向前跳转，不补发跳过的音
+const SEEK_TOLERANCE_SECS: f64 = 0.25;
+// 播放位置倒退超过这么多时视为向后跳转
+const REWIND_TOLERANCE_SECS: f64 = 0.05;
+
+/// 循环的伴奏型：events 中 slice_start..slice_end 之间按下的事件，
+/// 从主旋律的 from 位置开始循环，到 until（默认主旋律结束）为止
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccompanimentLoop {
+    pub events: Vec<KeyEvent>,
+    pub slice_start: f64,
+    pub slice_end: f64,
+    #[serde(default)]
+    pub from: f64,
+    #[serde(default)]
+    pub until: Option<f64>,
+}
+
+/// 检查并解析好的伴奏型，伴奏音一律点按
+pub struct Pattern {
+    notes: Vec<(f64, KeyCombo, String)>, // 相对切片开头的时间、组合键和按键字符串
+    period: f64,
+    from: f64,
+    until: f64,
+}
+
+impl Pattern {
+    pub fn compile(layer: AccompanimentLoop) -> Result<Self, AppError> {
+        let period = layer.slice_end - layer.slice_start;
+        if !layer.slice_start.is_finite()
+            || layer.slice_start < 0.0
+            || !period.is_finite()
+            || period <= 0.0
+        {
+            return Err(AppError::invalid(format!(
+                "Invalid accompaniment slice: {} - {}",
+                layer.slice_start, layer.slice_end
+            )));
+        }
+        let mut notes = Vec::new();
+        for event in &layer.events {
+            if event.time < layer.slice_start || event.time >= layer.slice_end {
+                continue;
+            }
+            let combo = resolve_key_combo(&event.key).map_err(|e| {
+                AppError::invalid("Invalid accompaniment key")
+                    .with_context(format!("\"{}\" ({})", event.key, e))
+            })?;
+            notes.push((event.time - layer.slice_start, combo, event.key.clone()));
+        }
+        if notes.is_empty() {
+            return Err(AppError::invalid("No events in accompaniment slice"));
+        }
+        notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Self {
+            notes,
+            period,
+            from: layer.from.max(0.0),
+            until: layer.until.unwrap_or(f64::INFINITY),
+        })
+    }
+
+    // 第 cycle 轮第 index 个伴奏音在主旋律上的时间
+    fn time_of(&self, (cycle, index): (u64, usize)) -> f64 {
+        self.from + cycle as f64 * self.period + self.notes[index].0
+    }
+
+    // 播放位置 position 之后（含）的第一个伴奏音
+    fn locate(&self, position: f64) -> (u64, usize) {
+        if position <= self.from {
+            return (0, 0);
+        }
+        let relative = position - self.from;
+        let cycle = (relative / self.period).floor();
+        let within = relative - cycle * self.period;
+        let index = self.notes.partition_point(|(offset, ..)| *offset < within);
+        if index == self.notes.len() {
+            (cycle as u64 + 1, 0)
+        } else {
+            (cycle as u64, index)
+        }
+    }
+
+    fn next(&self, (cycle, index): (u64, usize)) -> (u64, usize) {
+        if index + 1 == self.notes.len() {
+            (cycle + 1, 0)
+        } else {
+            (cycle, index + 1)
+        }
+    }
+}
+
+/// 伴奏线程的句柄，同一时间只有一层伴奏
+#[derive(Default)]
+pub struct Accompaniment {
+    running: Mutex<Option<(Arc<AtomicBool>, thread::JoinHandle<()>)>>,
+}
+
+impl Accompaniment {
+    /// 跟随 melody 的播放位置循环伴奏型，主旋律结束时伴奏随之结束
+    pub fn start(&self, melody: Arc<PlaybackControl>, input: InputHandle, pattern: Pattern) {
+        self.stop();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || run(&melody, &input, &pattern, &flag));
+        *self.running.lock().unwrap() = Some((stop, handle));
+    }
+
+    /// 停止伴奏，主旋律继续
+    pub fn stop(&self) {
+        let running = self.running.lock().unwrap().take();
+        if let Some((stop, handle)) = running {
+            stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(_, handle)| !handle.is_finished())
+    }
+}
+
+fn run(melody: &PlaybackControl, input: &InputHandle, pattern: &Pattern, stop: &AtomicBool) {
+    let mut cursor: Option<(u64, usize)> = None;
+    let mut last_position = 0.0;
+    let mut played = 0usize;
+
+    // 主旋律的线程可能还在倒计时，is_playing 此时已为 true
+    while !stop.load(Ordering::SeqCst) && melody.is_playing() {
+        let Some(position) = melody.position() else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        let next = match cursor {
+            Some(next)
+                if position >= last_position - REWIND_TOLERANCE_SECS
+                    && position <= pattern.time_of(next) + SEEK_TOLERANCE_SECS =>
+            {
+                next
+            }
+            // 第一次取位置或主旋律跳转后重新定位
+            _ => pattern.locate(position),
+        };
+        cursor = Some(next);
+        last_position = position;
+
+        let at = pattern.time_of(next);
+        if melody.is_paused() || at >= pattern.until {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        if position < at {
+            thread::sleep(Duration::from_secs_f64(at - position).min(POLL_INTERVAL));
+            continue;
+        }
+
+        input_hook::begin_injection();
+        let (_, combo, name) = &pattern.notes[next.1];
+        if let Err(e) = input.tap_combo(combo, name) {
+            log::warn!("Failed to play accompaniment note: {}", e);
+        }
+        input_hook::end_injection();
+        played += 1;
+        cursor = Some(pattern.next(next));
+    }
+    log::info!("Accompaniment finished: {} notes played", played);
+}
@@ -0,0 +1,152 @@
+use crate::error::AppError;
+use crate::input_hook;
+use crate::input_service::{self, InputHandle};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use uni_input::keyboard::resolve_key_combo;
+
+// 播放结束后的动作：无人值守时播完自动执行宏、在聊天框发一句话或退出程序。
+// 单曲播放结束和队列结束时执行，队列中的每一首不单独触发。
+
+// 打开聊天框后等待输入框出现
+const CHAT_OPEN_DELAY: Duration = Duration::from_millis(300);
+// 输入文字后等待游戏处理完再发送
+const CHAT_SEND_DELAY: Duration = Duration::from_millis(100);
+
+fn default_macro_interval_ms() -> u64 {
+    100
+}
+
+fn default_chat_key() -> String {
+    "enter".to_string()
+}
+
+/// 播放结束后执行的动作，按顺序执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FinishAction {
+    /// 依次点按组合键，如 ["esc", "ctrl+s"]
+    Macro {
+        keys: Vec<String>,
+        #[serde(default = "default_macro_interval_ms")]
+        interval_ms: u64,
+    },
+    /// 按 open_key 打开聊天框，输入文字后按回车发送
+    Chat {
+        text: String,
+        #[serde(default = "default_chat_key")]
+        open_key: String,
+    },
+    /// 退出程序，之后的动作不再执行
+    Quit,
+}
+
+/// 播放结束后的设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AfterPlayback {
+    pub actions: Vec<FinishAction>,
+    pub run_when_stopped: bool,          // 中途停止时也执行
+    pub stop_after_songs: Option<usize>, // 队列播完这么多首后停止
+}
+
+impl AfterPlayback {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.stop_after_songs == Some(0) {
+            return Err(AppError::invalid("Song limit must be at least 1"));
+        }
+        for action in &self.actions {
+            let keys: Vec<&String> = match action {
+                FinishAction::Macro { keys, .. } => keys.iter().collect(),
+                FinishAction::Chat { open_key, .. } => vec![open_key],
+                FinishAction::Quit => Vec::new(),
+            };
+            for key in keys {
+                resolve_key_combo(key).map_err(|e| {
+                    AppError::invalid("Invalid key in after-playback action")
+                        .with_context(format!("\"{}\" ({})", key, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct ActionEvent {
+    index: usize,
+    action: FinishAction,
+}
+
+/// 播放或队列结束时调用，按设置在后台线程中执行动作
+pub fn on_finished(app: &AppHandle, completed: bool) {
+    let settings = app
+        .state::<AppState>()
+        .after_playback
+        .lock()
+        .unwrap()
+        .clone();
+    if settings.actions.is_empty() || !(completed || settings.run_when_stopped) {
+        return;
+    }
+    let app = app.clone();
+    thread::spawn(move || {
+        let input = match input_service::handle() {
+            Ok(input) => input,
+            Err(e) => {
+                log::warn!("After-playback actions skipped: {}", e);
+                return;
+            }
+        };
+        for (index, action) in settings.actions.into_iter().enumerate() {
+            let _ = app.emit(
+                "playback://after-action",
+                ActionEvent {
+                    index,
+                    action: action.clone(),
+                },
+            );
+            if let FinishAction::Quit = action {
+                log::info!("Quitting after playback");
+                app.exit(0);
+                return;
+            }
+            input_hook::begin_injection();
+            let result = perform(&input, &action);
+            input_hook::end_injection();
+            if let Err(e) = result {
+                log::warn!("After-playback action {} failed: {}", index, e);
+            }
+        }
+    });
+}
+
+// 按键在设置时已检查过
+fn tap(input: &InputHandle, key: &str) -> Result<(), String> {
+    input.tap_combo(&resolve_key_combo(key)?, key)
+}
+
+fn perform(input: &InputHandle, action: &FinishAction) -> Result<(), String> {
+    match action {
+        FinishAction::Macro { keys, interval_ms } => {
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    thread::sleep(Duration::from_millis(*interval_ms));
+                }
+                tap(input, key)?;
+            }
+        }
+        FinishAction::Chat { text, open_key } => {
+            tap(input, open_key)?;
+            thread::sleep(CHAT_OPEN_DELAY);
+            input.type_text(text)?;
+            thread::sleep(CHAT_SEND_DELAY);
+            tap(input, "enter")?;
+        }
+        FinishAction::Quit => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,996 @@
+use crate::midi_analyzer::{BeatMark, MidiEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+// 轮廓折叠时向前、向后各参考的音符数
+const CONTOUR_WINDOW: usize = 4;
+
+/// 超出音域的音符如何按八度移回音域内
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FoldMode {
+    #[default]
+    Off,
+    /// 逐个音符移到离原音高最近的八度
+    Nearest,
+    /// 参考同一音轨前后的音符选择八度，避免乐句中间出现八度跳跃
+    Contour,
+}
+
+// 同音名且在音域内的所有音高；音域不足一个八度时取离音域最近的一个
+fn fold_candidates(note: u8, min_note: u8, max_note: u8) -> Vec<u8> {
+    let same_pc = (note % 12..=127).step_by(12);
+    let candidates: Vec<u8> = same_pc
+        .clone()
+        .filter(|n| (min_note..=max_note).contains(n))
+        .collect();
+    if !candidates.is_empty() {
+        return candidates;
+    }
+
+    same_pc
+        .min_by_key(|&n| {
+            if n < min_note {
+                min_note - n
+            } else {
+                n.saturating_sub(max_note)
+            }
+        })
+        .into_iter()
+        .collect()
+}
+
+fn nearest_fold(note: u8, min_note: u8, max_note: u8) -> u8 {
+    fold_candidates(note, min_note, max_note)
+        .into_iter()
+        .min_by_key(|&c| c.abs_diff(note))
+        .unwrap_or(note)
+}
+
+/// 把超出音域的音符按八度移入音域，note_off 随对应的 note_on 一起移动
+/// events 需已按时间排序，返回被移动的音符数
+pub fn fold_into_range(
+    events: &mut [MidiEvent],
+    min_note: u8,
+    max_note: u8,
+    mode: FoldMode,
+) -> usize {
+    if mode == FoldMode::Off {
+        return 0;
+    }
+
+    // 按音轨分组，旋律走向只在同一音轨内比较
+    let mut track_ons: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, event) in events.iter().enumerate() {
+        if event.type_ == "note_on" {
+            track_ons.entry(event.track).or_default().push(i);
+        }
+    }
+
+    let mut folded: Vec<Option<u8>> = vec![None; events.len()];
+    let mut folded_count = 0;
+
+    for ons in track_ons.values() {
+        let original: Vec<u8> = ons.iter().map(|&i| events[i].note).collect();
+        let naive: Vec<u8> = original
+            .iter()
+            .map(|&n| nearest_fold(n, min_note, max_note))
+            .collect();
+        let mut placed: Vec<u8> = Vec::with_capacity(ons.len());
+
+        for (k, &note) in original.iter().enumerate() {
+            if (min_note..=max_note).contains(&note) {
+                placed.push(note);
+                continue;
+            }
+            folded_count += 1;
+
+            let choice = match mode {
+                FoldMode::Contour => {
+                    // 前面用已确定的音高，后面用最近八度的预估音高
+                    let before = &placed[k.saturating_sub(CONTOUR_WINDOW)..k];
+                    let after = &naive[k + 1..(k + 1 + CONTOUR_WINDOW).min(naive.len())];
+                    fold_candidates(note, min_note, max_note)
+                        .into_iter()
+                        .min_by_key(|&c| {
+                            let cost: u32 = before
+                                .iter()
+                                .chain(after)
+                                .map(|&n| c.abs_diff(n) as u32)
+                                .sum();
+                            (cost, c.abs_diff(note))
+                        })
+                        .unwrap_or(naive[k])
+                }
+                _ => naive[k],
+            };
+            placed.push(choice);
+        }
+
+        for (&i, &note) in ons.iter().zip(&placed) {
+            folded[i] = Some(note);
+        }
+    }
+
+    // 按 (音轨, 通道, 原音高) 依次配对 note_off
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<u8>> = HashMap::new();
+    for (i, event) in events.iter_mut().enumerate() {
+        let key = (event.track, event.channel, event.note);
+        if let Some(note) = folded[i] {
+            pending.entry(key).or_default().push_back(note);
+            event.note = note;
+        } else if event.type_ == "note_off" {
+            if let Some(note) = pending.get_mut(&key).and_then(|q| q.pop_front()) {
+                event.note = note;
+            }
+        }
+    }
+
+    folded_count
+}
+
+// 起音时间相差在此范围内的音符视为同一个和弦
+const CHORD_WINDOW_SECS: f64 = 0.03;
+// 低音在该间隔内重复同一音高时视为伴奏型，只保留第一个
+const MIN_BASS_REPEAT_SECS: f64 = 0.25;
+
+/// 和声简化：每个和弦只保留最高音（旋律）和最低音（根音），
+/// 去掉内声部，并稀疏化密集重复的低音伴奏。返回去掉的音符数
+pub fn reduce_harmony(events: &mut Vec<MidiEvent>) -> usize {
+    let ons: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.type_ == "note_on")
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut keep = vec![true; events.len()];
+    let mut last_bass: Option<(u8, f64)> = None;
+    let mut removed = 0;
+
+    let mut start = 0;
+    while start < ons.len() {
+        let chord_time = events[ons[start]].time;
+        let mut end = start + 1;
+        while end < ons.len() && events[ons[end]].time - chord_time <= CHORD_WINDOW_SECS {
+            end += 1;
+        }
+        let chord = &ons[start..end];
+        start = end;
+
+        let melody = *chord.iter().max_by_key(|&&i| events[i].note).unwrap();
+        let bass = *chord.iter().min_by_key(|&&i| events[i].note).unwrap();
+        let bass_note = events[bass].note;
+        let has_bass = bass_note < events[melody].note;
+
+        let repeated = has_bass
+            && last_bass.is_some_and(|(note, time)| {
+                note == bass_note && chord_time - time < MIN_BASS_REPEAT_SECS
+            });
+        if has_bass && !repeated {
+            last_bass = Some((bass_note, chord_time));
+        }
+
+        for &i in chord {
+            if i == melody || (i == bass && !repeated) {
+                continue;
+            }
+            keep[i] = false;
+            removed += 1;
+        }
+    }
+
+    drop_notes(events, keep);
+    removed
+}
+
+// 去掉 keep 为 false 的 note_on，note_off 跟随对应的 note_on 一起去掉
+fn drop_notes(events: &mut Vec<MidiEvent>, mut keep: Vec<bool>) {
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<bool>> = HashMap::new();
+    for (i, event) in events.iter().enumerate() {
+        let key = (event.track, event.channel, event.note);
+        if event.type_ == "note_on" {
+            pending.entry(key).or_default().push_back(keep[i]);
+        } else if let Some(kept) = pending.get_mut(&key).and_then(|q| q.pop_front()) {
+            keep[i] = kept;
+        }
+    }
+
+    let mut index = 0;
+    events.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+}
+
+/// 提取旋律时保留哪一条外声部
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MelodyLine {
+    Highest,
+    Lowest,
+}
+
+/// 提取旋律（天际线算法）：任意时刻只保留最高（或最低）的一个音。
+/// 更外侧的音开始时截断正在响的音，被外侧的音盖住时开始的音去掉。返回去掉的音符数
+pub fn extract_melody(events: &mut Vec<MidiEvent>, line: MelodyLine) -> usize {
+    let outer = |a: u8, b: u8| match line {
+        MelodyLine::Highest => a > b,
+        MelodyLine::Lowest => a < b,
+    };
+    let note_ons: Vec<MidiEvent> = events
+        .iter()
+        .filter(|e| e.type_ == "note_on")
+        .cloned()
+        .collect();
+    let total = note_ons.len();
+
+    let mut melody: Vec<MidiEvent> = Vec::with_capacity(total);
+    let mut start = 0;
+    while start < note_ons.len() {
+        let chord_time = note_ons[start].time;
+        let mut end = start + 1;
+        while end < note_ons.len() && note_ons[end].time - chord_time <= CHORD_WINDOW_SECS {
+            end += 1;
+        }
+        // 同时开始的音只取最外侧的一个
+        let mut candidate = note_ons[start].clone();
+        for event in &note_ons[start + 1..end] {
+            if outer(event.note, candidate.note) {
+                candidate = event.clone();
+            }
+        }
+        start = end;
+
+        if let Some(current) = melody.last_mut().filter(|c| c.end > candidate.time) {
+            if !outer(candidate.note, current.note) {
+                continue;
+            }
+            current.end = candidate.time;
+            current.duration = current.end - current.time;
+        }
+        melody.push(candidate);
+    }
+
+    let removed = total - melody.len();
+    if removed == 0 {
+        return 0;
+    }
+    let mut result = Vec::with_capacity(melody.len() * 2);
+    for note_on in melody {
+        let note_off = note_off_for(&note_on);
+        result.push(note_on);
+        result.push(note_off);
+    }
+    result.sort_by(|a, b| a.time.total_cmp(&b.time));
+    *events = result;
+    removed
+}
+
+/// 密集段落的简化，让复杂的管弦乐 MIDI 也能在按键较少的游戏乐器上演奏
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Simplify {
+    pub max_polyphony: Option<usize>, // 同时按住的音最多这么多个
+    pub min_duration_ms: f64,         // 短于该时值的装饰音去掉，0 表示不去掉
+    pub keep_melody: bool,            // 每个和弦的最高音（旋律）总是保留
+}
+
+impl Default for Simplify {
+    fn default() -> Self {
+        Self {
+            max_polyphony: Some(3),
+            min_duration_ms: 60.0,
+            keep_melody: true,
+        }
+    }
+}
+
+/// 按设置简化音符：先去掉装饰音，再按旋律、低音、其余从高到低的顺序
+/// 保留不超过最大复音数的音符。返回去掉的音符数
+pub fn simplify(events: &mut Vec<MidiEvent>, settings: Simplify) -> usize {
+    let min_duration = settings.min_duration_ms.max(0.0) / 1000.0;
+    let ons: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.type_ == "note_on" && e.duration >= min_duration)
+        .map(|(i, _)| i)
+        .collect();
+    let mut keep = vec![true; events.len()];
+    for (i, event) in events.iter().enumerate() {
+        if event.type_ == "note_on" && event.duration < min_duration {
+            keep[i] = false;
+        }
+    }
+
+    if let Some(max_polyphony) = settings.max_polyphony {
+        // 已保留且还在响的音的结束时间
+        let mut sounding: Vec<f64> = Vec::new();
+        let mut start = 0;
+        while start < ons.len() {
+            let chord_time = events[ons[start]].time;
+            let mut end = start + 1;
+            while end < ons.len() && events[ons[end]].time - chord_time <= CHORD_WINDOW_SECS {
+                end += 1;
+            }
+            let mut chord = ons[start..end].to_vec();
+            start = end;
+
+            // 旋律、低音，其余从高到低
+            chord.sort_by(|&a, &b| events[b].note.cmp(&events[a].note));
+            if chord.len() > 2 {
+                let bass = chord.pop().unwrap();
+                chord.insert(1, bass);
+            }
+
+            sounding.retain(|&end| end > chord_time);
+            let mut room = max_polyphony.max(1).saturating_sub(sounding.len());
+            if settings.keep_melody {
+                room = room.max(1);
+            }
+            for (rank, &i) in chord.iter().enumerate() {
+                if rank < room {
+                    sounding.push(events[i].end);
+                } else {
+                    keep[i] = false;
+                }
+            }
+        }
+    }
+
+    let removed = events
+        .iter()
+        .zip(&keep)
+        .filter(|(e, &kept)| e.type_ == "note_on" && !kept)
+        .count();
+    if removed > 0 {
+        drop_notes(events, keep);
+    }
+    removed
+}
+
+// 律动模板按十六分音符划分每拍
+const GROOVE_SUBDIVISIONS: f64 = 4.0;
+
+/// 把参考音轨的微小时间偏移套用到目标音轨
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GrooveTransfer {
+    pub reference_track: usize,
+    pub target_track: usize,
+}
+
+// 音符在小节内最近的网格位置：(位置编号, 网格时间, 拍长)
+fn groove_slot(beats: &[BeatMark], time: f64) -> Option<(u32, f64, f64)> {
+    let index = beats.partition_point(|b| b.time <= time).checked_sub(1)?;
+    let beat = &beats[index];
+    let beat_len = match (beats.get(index + 1), index.checked_sub(1)) {
+        (Some(next), _) => next.time - beat.time,
+        (None, Some(prev)) => beat.time - beats[prev].time,
+        _ => return None,
+    };
+    if beat_len <= 0.0 {
+        return None;
+    }
+
+    let step = beat_len / GROOVE_SUBDIVISIONS;
+    let sub = ((time - beat.time) / step).round();
+    let slot = (beat.beat - 1) * GROOVE_SUBDIVISIONS as u32 + sub as u32;
+    Some((slot, beat.time + sub * step, beat_len))
+}
+
+/// 提取参考音轨在小节内各网格位置上的平均偏移（以拍长为单位）
+fn extract_groove(events: &[MidiEvent], track: usize, beats: &[BeatMark]) -> HashMap<u32, f64> {
+    let mut sums: HashMap<u32, (f64, usize)> = HashMap::new();
+    for event in events
+        .iter()
+        .filter(|e| e.track == track && e.type_ == "note_on")
+    {
+        if let Some((slot, grid_time, beat_len)) = groove_slot(beats, event.time) {
+            let entry = sums.entry(slot).or_default();
+            entry.0 += (event.time - grid_time) / beat_len;
+            entry.1 += 1;
+        }
+    }
+    sums.into_iter()
+        .map(|(slot, (sum, count))| (slot, sum / count as f64))
+        .collect()
+}
+
+/// 用参考音轨的律动移动目标音轨的音符，时长不变，完成后重新按时间排序
+pub fn apply_groove(events: &mut [MidiEvent], transfer: GrooveTransfer, beats: &[BeatMark]) {
+    let groove = extract_groove(events, transfer.reference_track, beats);
+    if groove.is_empty() {
+        return;
+    }
+
+    let mut pending: HashMap<(u8, u8), VecDeque<f64>> = HashMap::new();
+    for event in events
+        .iter_mut()
+        .filter(|e| e.track == transfer.target_track)
+    {
+        let key = (event.channel, event.note);
+        let offset = if event.type_ == "note_on" {
+            let offset = groove_slot(beats, event.time)
+                .and_then(|(slot, grid_time, beat_len)| {
+                    groove
+                        .get(&slot)
+                        .map(|o| grid_time + o * beat_len - event.time)
+                })
+                .unwrap_or(0.0);
+            pending.entry(key).or_default().push_back(offset);
+            offset
+        } else {
+            pending
+                .get_mut(&key)
+                .and_then(|q| q.pop_front())
+                .unwrap_or(0.0)
+        };
+        event.time = (event.time + offset).max(0.0);
+        event.end = (event.end + offset).max(0.0);
+    }
+
+    events.sort_by(|a, b| {
+        a.time
+            .partial_cmp(&b.time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// 量化：把按下时间吸附到按拍线细分的网格上，让略有偏差的演奏录音更整齐
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quantize {
+    pub grid: u32,           // 网格的音符时值，16 表示十六分音符
+    pub strength: f64,       // 0-1，1 表示完全吸附到网格
+    pub quantize_ends: bool, // 松开时间也吸附，否则保持原时值
+}
+
+impl Default for Quantize {
+    fn default() -> Self {
+        Self {
+            grid: 16,
+            strength: 1.0,
+            quantize_ends: false,
+        }
+    }
+}
+
+// 时间所在拍内最近的网格时间，按拍号的分母换算每拍的格数；第一拍之前不量化
+fn quantize_time(beats: &[BeatMark], time: f64, grid: u32) -> Option<f64> {
+    let index = beats.partition_point(|b| b.time <= time).checked_sub(1)?;
+    let beat = &beats[index];
+    let beat_len = match (beats.get(index + 1), index.checked_sub(1)) {
+        (Some(next), _) => next.time - beat.time,
+        (None, Some(prev)) => beat.time - beats[prev].time,
+        _ => return None,
+    };
+    if beat_len <= 0.0 {
+        return None;
+    }
+    let per_beat = (grid / beat.denominator.max(1) as u32).max(1) as f64;
+    let step = beat_len / per_beat;
+    Some(beat.time + ((time - beat.time) / step).round() * step)
+}
+
+/// 按设置量化音符，note_off 跟随对应的 note_on 移动。返回移动了的音符数
+pub fn quantize(events: &mut [MidiEvent], settings: Quantize, beats: &[BeatMark]) -> usize {
+    let strength = settings.strength.clamp(0.0, 1.0);
+    if settings.grid == 0 || strength == 0.0 {
+        return 0;
+    }
+    let snap = |time: f64| {
+        quantize_time(beats, time, settings.grid).map(|grid| time + (grid - time) * strength)
+    };
+
+    let mut moved = 0;
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<f64>> = HashMap::new();
+    for event in events.iter_mut() {
+        let key = (event.track, event.channel, event.note);
+        if event.type_ == "note_on" {
+            let time = snap(event.time).unwrap_or(event.time).max(0.0);
+            let end = if settings.quantize_ends {
+                snap(event.end).filter(|&end| end > time)
+            } else {
+                None
+            }
+            .unwrap_or(time + event.duration);
+            if (time - event.time).abs() > 1e-9 || (end - event.end).abs() > 1e-9 {
+                moved += 1;
+            }
+            event.time = time;
+            event.end = end;
+            event.duration = end - time;
+            pending.entry(key).or_default().push_back(end);
+        } else if let Some(end) = pending.get_mut(&key).and_then(|q| q.pop_front()) {
+            event.time = end;
+            event.end = end;
+        }
+    }
+
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    moved
+}
+
+// 提前松开后音符至少保留的时长
+const MIN_NOTE_SECS: f64 = 0.01;
+
+/// 提前松开：每个音符缩短固定毫秒数或按比例缩短
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseEarly {
+    Millis(f64),
+    Percent(f64),
+}
+
+/// 缩短所有音符，避免连奏时松开与同键的下一次按下重叠导致游戏漏掉重触发
+pub fn release_early(events: &mut [MidiEvent], release: ReleaseEarly) {
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<f64>> = HashMap::new();
+    for event in events.iter_mut() {
+        let key = (event.track, event.channel, event.note);
+        if event.type_ == "note_on" {
+            let cut = match release {
+                ReleaseEarly::Millis(ms) => ms / 1000.0,
+                ReleaseEarly::Percent(p) => event.duration * p.clamp(0.0, 100.0) / 100.0,
+            };
+            let duration = (event.duration - cut.max(0.0)).max(MIN_NOTE_SECS.min(event.duration));
+            event.duration = duration;
+            event.end = event.time + duration;
+            pending.entry(key).or_default().push_back(event.end);
+        } else if let Some(end) = pending.get_mut(&key).and_then(|q| q.pop_front()) {
+            event.time = end;
+            event.end = end;
+        }
+    }
+
+    events.sort_by(|a, b| {
+        a.time
+            .partial_cmp(&b.time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+// 重复按下时太短的尾巴不再按
+const MIN_REPEAT_SECS: f64 = 0.1;
+// 重复按下时松开到下一次按下的间隔
+const REPEAT_GAP_SECS: f64 = 0.01;
+
+/// 长音处理：超过 max_secs 的音符截短到 max_secs；repeat 时在原时值内
+/// 每隔 interval_secs 重新按下一次（长按会衰减的游戏乐器用它模拟延音）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LongNoteTrim {
+    pub max_secs: f64,
+    pub repeat: bool,
+    pub interval_secs: f64,
+}
+
+impl Default for LongNoteTrim {
+    fn default() -> Self {
+        Self {
+            max_secs: 0.99,
+            repeat: false,
+            interval_secs: 1.0,
+        }
+    }
+}
+
+/// 截短或拆分长音，返回处理的音符数。按 note_on 重建所有 note_off，成对关系不变
+pub fn trim_long_notes(events: &mut Vec<MidiEvent>, settings: LongNoteTrim) -> usize {
+    let max_secs = settings.max_secs.max(MIN_NOTE_SECS);
+    let interval = settings.interval_secs.max(MIN_REPEAT_SECS);
+    let mut trimmed = 0;
+    let mut result = Vec::with_capacity(events.len());
+    for event in events.iter().filter(|e| e.type_ == "note_on") {
+        let mut presses = vec![(event.time, event.duration)];
+        if event.duration > max_secs {
+            trimmed += 1;
+            presses[0].1 = max_secs;
+            if settings.repeat {
+                let hold = max_secs.min(interval - REPEAT_GAP_SECS);
+                presses[0].1 = hold;
+                let mut offset = interval;
+                while event.duration - offset >= MIN_REPEAT_SECS {
+                    presses.push((event.time + offset, hold.min(event.duration - offset)));
+                    offset += interval;
+                }
+            }
+        }
+        for (time, duration) in presses {
+            let note_on = MidiEvent {
+                time,
+                duration,
+                end: time + duration,
+                ..event.clone()
+            };
+            let note_off = note_off_for(&note_on);
+            result.push(note_on);
+            result.push(note_off);
+        }
+    }
+    result.sort_by(|a, b| a.time.total_cmp(&b.time));
+    *events = result;
+    trimmed
+}
+
+// 与 note_on 配对的 note_off
+fn note_off_for(note_on: &MidiEvent) -> MidiEvent {
+    MidiEvent {
+        time: note_on.end,
+        type_: "note_off".to_string(),
+        velocity: 0,
+        duration: 0.0,
+        ..note_on.clone()
+    }
+}
+
+/// 合并重复音符：多个音轨同时出现的同一个音只按一次
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteMerge {
+    pub tolerance_ms: f64,    // 开始时间相差不超过这么多视为同时
+    pub merge_overlaps: bool, // 前一个同音还没松开时按下的也合并进去
+}
+
+impl Default for NoteMerge {
+    fn default() -> Self {
+        Self {
+            tolerance_ms: 20.0,
+            merge_overlaps: true,
+        }
+    }
+}
+
+/// 合并同一音高的重复音符，保留较早的一个，时值延长到两者中较晚的结束，力度取较大者。
+/// 返回去掉的音符数
+pub fn merge_duplicates(events: &mut Vec<MidiEvent>, settings: NoteMerge) -> usize {
+    let tolerance = settings.tolerance_ms.max(0.0) / 1000.0;
+    let mut note_ons: Vec<MidiEvent> = events
+        .iter()
+        .filter(|e| e.type_ == "note_on")
+        .cloned()
+        .collect();
+    let mut presses: HashMap<u8, Vec<usize>> = HashMap::new();
+    for (index, event) in note_ons.iter().enumerate() {
+        presses.entry(event.note).or_default().push(index);
+    }
+
+    let mut removed = vec![false; note_ons.len()];
+    for indices in presses.values() {
+        let mut kept = indices[0];
+        for &index in &indices[1..] {
+            let (earlier, later) = (&note_ons[kept], &note_ons[index]);
+            let simultaneous = later.time - earlier.time <= tolerance;
+            let overlapping = settings.merge_overlaps && later.time < earlier.end;
+            if !simultaneous && !overlapping {
+                kept = index;
+                continue;
+            }
+            let (end, velocity) = (later.end, later.velocity);
+            let earlier = &mut note_ons[kept];
+            earlier.end = earlier.end.max(end);
+            earlier.duration = earlier.end - earlier.time;
+            earlier.velocity = earlier.velocity.max(velocity);
+            removed[index] = true;
+        }
+    }
+    let count = removed.iter().filter(|&&r| r).count();
+    if count == 0 {
+        return 0;
+    }
+
+    let mut result = Vec::with_capacity(events.len() - count * 2);
+    for (note_on, removed) in note_ons.into_iter().zip(removed) {
+        if !removed {
+            let note_off = note_off_for(&note_on);
+            result.push(note_on);
+            result.push(note_off);
+        }
+    }
+    result.sort_by(|a, b| a.time.total_cmp(&b.time));
+    *events = result;
+    count
+}
+
+/// 同一个音（同一个键）连续按下时，前一个音至少在下一次按下前 gap_secs 松开，
+/// 否则游戏可能因为键没有完全抬起而漏掉第二次按下。返回缩短的音符数
+pub fn enforce_release_gap(events: &mut [MidiEvent], gap_secs: f64) -> usize {
+    let gap = gap_secs.max(0.0);
+    // 每个音高的按下按时间排列
+    let mut presses: HashMap<u8, Vec<usize>> = HashMap::new();
+    for (index, event) in events.iter().enumerate() {
+        if event.type_ == "note_on" {
+            presses.entry(event.note).or_default().push(index);
+        }
+    }
+    let mut shortened = 0;
+    for indices in presses.values() {
+        for pair in indices.windows(2) {
+            let next_time = events[pair[1]].time;
+            let event = &mut events[pair[0]];
+            let latest_end = next_time - gap;
+            if event.end > latest_end {
+                // 间隔不够时至少保留最短时值
+                let duration = (latest_end - event.time).max(MIN_NOTE_SECS.min(event.duration));
+                event.duration = duration;
+                event.end = event.time + duration;
+                shortened += 1;
+            }
+        }
+    }
+    if shortened == 0 {
+        return 0;
+    }
+
+    // note_off 跟随各自的 note_on
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<f64>> = HashMap::new();
+    for event in events.iter_mut() {
+        let key = (event.track, event.channel, event.note);
+        if event.type_ == "note_on" {
+            pending.entry(key).or_default().push_back(event.end);
+        } else if let Some(end) = pending.get_mut(&key).and_then(|q| q.pop_front()) {
+            event.time = end;
+            event.end = end;
+        }
+    }
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    shortened
+}
+
+/// 按音轨或通道整体延后（负数为提前）固定毫秒数，note_off 随 note_on 一起移动。
+/// 提前后有事件早于 0 时整首后移，返回后移的秒数，拍线等需要同样后移
+pub fn apply_delays(events: &mut [MidiEvent], delay_ms: impl Fn(&MidiEvent) -> f64) -> f64 {
+    let mut earliest: f64 = 0.0;
+    for event in events.iter_mut() {
+        let delay = delay_ms(event) / 1000.0;
+        if delay == 0.0 || !delay.is_finite() {
+            continue;
+        }
+        event.time += delay;
+        event.end += delay;
+        earliest = earliest.min(event.time);
+    }
+    let lead = -earliest;
+    if lead > 0.0 {
+        for event in events.iter_mut() {
+            event.time += lead;
+            event.end += lead;
+        }
+    }
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    lead
+}
+
+/// 力度压缩：先按整首最响的音归一化，再按 ratio 压缩动态范围，映射到 floor..=ceiling
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VelocityCompression {
+    pub ratio: f64, // 1 为只归一化，越大弱音被抬得越高
+    pub floor: u8,
+    pub ceiling: u8,
+}
+
+impl Default for VelocityCompression {
+    fn default() -> Self {
+        Self {
+            ratio: 2.0,
+            floor: 40,
+            ceiling: 127,
+        }
+    }
+}
+
+/// 压缩 note_on 的力度，让弱奏段落也能触发按力度区分的音符；note_off 不变
+pub fn compress_velocities(events: &mut [MidiEvent], settings: VelocityCompression) {
+    let loudest = events
+        .iter()
+        .filter(|e| e.type_ == "note_on")
+        .map(|e| e.velocity)
+        .max()
+        .unwrap_or(0);
+    if loudest == 0 {
+        return;
+    }
+    let ratio = if settings.ratio.is_finite() && settings.ratio > 0.0 {
+        settings.ratio
+    } else {
+        1.0
+    };
+    let floor = settings.floor.clamp(1, 127) as f64;
+    let ceiling = (settings.ceiling.min(127) as f64).max(floor);
+
+    for event in events.iter_mut().filter(|e| e.type_ == "note_on") {
+        let level = (event.velocity as f64 / loudest as f64).powf(1.0 / ratio);
+        event.velocity = (floor + level * (ceiling - floor)).round() as u8;
+    }
+}
+
+// 被删掉的一段静默：原时间 from..to 之间的部分，removed 为到 to 为止累计删掉的时长
+#[derive(Debug, Clone, Copy)]
+struct GapCut {
+    from: f64,
+    to: f64,
+    removed: f64,
+}
+
+/// 静默压缩的结果，用于把拍线、和弦等其他时间对齐到压缩后的时间轴
+#[derive(Debug, Clone, Default)]
+pub struct GapCompression {
+    cuts: Vec<GapCut>,
+}
+
+impl GapCompression {
+    pub fn gaps(&self) -> usize {
+        self.cuts.len()
+    }
+
+    pub fn removed_secs(&self) -> f64 {
+        self.cuts.last().map_or(0.0, |c| c.removed)
+    }
+
+    /// 是否落在被删掉的静默中
+    pub fn is_removed(&self, time: f64) -> bool {
+        self.cuts.iter().any(|c| time > c.from && time < c.to)
+    }
+
+    /// 原时间对应的压缩后时间，落在被删掉的静默中时取该段的起点
+    pub fn map_time(&self, time: f64) -> f64 {
+        match self.cuts.iter().rev().find(|c| c.from <= time) {
+            Some(cut) if time < cut.to => cut.from - (cut.removed - (cut.to - cut.from)),
+            Some(cut) => time - cut.removed,
+            None => time,
+        }
+    }
+}
+
+/// 把超过 max_secs 的静默（所有音符都已松开到下一个音符按下之间，包括开头）缩短到 max_secs
+pub fn compress_gaps(events: &mut [MidiEvent], max_secs: f64) -> GapCompression {
+    let max_secs = max_secs.max(0.0);
+    let mut compression = GapCompression::default();
+    let mut sounding_until = 0.0_f64;
+    for event in events.iter() {
+        if event.type_ != "note_on" {
+            continue;
+        }
+        if event.time - sounding_until > max_secs {
+            let from = sounding_until + max_secs;
+            compression.cuts.push(GapCut {
+                from,
+                to: event.time,
+                removed: compression.removed_secs() + (event.time - from),
+            });
+        }
+        sounding_until = sounding_until.max(event.end);
+    }
+
+    // 之前的音符都在静默开始前结束，静默之后的事件整体前移
+    for event in events.iter_mut() {
+        event.time = compression.map_time(event.time);
+        event.end = compression.map_time(event.end);
+    }
+    compression
+}
+
+/// 自动裁剪：按音符密度去掉开头零散的前奏和结尾稀疏的延长音
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoTrim {
+    pub window_secs: f64,       // 统计密度的时间窗口
+    pub min_notes_per_sec: f64, // 窗口内达到该密度才算进入正曲
+}
+
+impl Default for AutoTrim {
+    fn default() -> Self {
+        Self {
+            window_secs: 4.0,
+            min_notes_per_sec: 2.0,
+        }
+    }
+}
+
+/// 被裁掉的时间段（原时间轴，秒）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TrimmedRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrimResult {
+    pub ranges: Vec<TrimmedRange>,
+    pub removed_notes: usize,
+    pub offset: f64,    // 裁掉前奏后整体前移的时长
+    pub keep_from: f64, // 保留区间的起止（原时间轴）
+    pub keep_until: f64,
+}
+
+/// 只保留密度达标的第一个窗口开始、到最后一个达标窗口结束之间按下的音符，
+/// 并把保留部分移到从 0 开始。整首都达不到密度时不裁剪，返回 None
+pub fn auto_trim(events: &mut Vec<MidiEvent>, settings: AutoTrim) -> Option<TrimResult> {
+    let window = settings.window_secs.max(0.1);
+    let needed = (settings.min_notes_per_sec.max(0.0) * window)
+        .ceil()
+        .max(1.0) as usize;
+    let onsets: Vec<f64> = events
+        .iter()
+        .filter(|e| e.type_ == "note_on")
+        .map(|e| e.time)
+        .collect();
+    let song_end = events.iter().map(|e| e.end).fold(0.0, f64::max);
+
+    // 从第 i 个音符开始的窗口内有多少音符
+    let count_from = |i: usize| {
+        onsets[i..]
+            .iter()
+            .take_while(|&&t| t < onsets[i] + window)
+            .count()
+    };
+    // 到第 j 个音符为止的窗口内有多少音符
+    let count_until = |j: usize| {
+        onsets[..=j]
+            .iter()
+            .rev()
+            .take_while(|&&t| t > onsets[j] - window)
+            .count()
+    };
+    let first = (0..onsets.len()).find(|&i| count_from(i) >= needed)?;
+    let last = (0..onsets.len())
+        .rev()
+        .find(|&j| count_until(j) >= needed)?;
+    let (keep_from, keep_until) = (onsets[first], onsets[last]);
+    if first == 0 && last == onsets.len() - 1 {
+        return Some(TrimResult {
+            keep_from: 0.0,
+            keep_until: song_end,
+            ..Default::default()
+        });
+    }
+
+    // 按下和松开按同一音符先进先出配对，松开跟随按下一起保留或删除
+    let mut pending: HashMap<(usize, u8, u8), VecDeque<bool>> = HashMap::new();
+    let mut removed_notes = 0;
+    let mut last_kept_end = keep_from;
+    events.retain(|event| {
+        let key = (event.track, event.channel, event.note);
+        if event.type_ == "note_on" {
+            let keep = event.time >= keep_from && event.time <= keep_until;
+            if keep {
+                last_kept_end = last_kept_end.max(event.end);
+            } else {
+                removed_notes += 1;
+            }
+            pending.entry(key).or_default().push_back(keep);
+            keep
+        } else {
+            pending
+                .get_mut(&key)
+                .and_then(|q| q.pop_front())
+                .unwrap_or(true)
+        }
+    });
+
+    let mut ranges = Vec::new();
+    let offset = if first > 0 { keep_from } else { 0.0 };
+    if first > 0 {
+        ranges.push(TrimmedRange {
+            start: 0.0,
+            end: keep_from,
+        });
+    }
+    if last < onsets.len() - 1 {
+        ranges.push(TrimmedRange {
+            start: last_kept_end,
+            end: song_end,
+        });
+    }
+    for event in events.iter_mut() {
+        event.time -= offset;
+        event.end -= offset;
+    }
+    Some(TrimResult {
+        ranges,
+        removed_notes,
+        offset,
+        keep_from,
+        keep_until: last_kept_end,
+    })
+}
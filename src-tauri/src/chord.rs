@@ -0,0 +1,179 @@
+use crate::midi_analyzer::{MidiEvent, SHARP_NAMES};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+// 短于该时长的和弦视为经过音，不单独标注
+const MIN_CHORD_SECS: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Dominant7,
+    Major7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+}
+
+impl ChordQuality {
+    // 四和弦排在前面，音符都在时优先匹配
+    const ALL: [ChordQuality; 11] = [
+        ChordQuality::Dominant7,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::HalfDiminished7,
+        ChordQuality::Diminished7,
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+    ];
+
+    /// 相对根音的音程（半音）
+    fn intervals(self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::HalfDiminished7 => &[0, 3, 6, 10],
+            ChordQuality::Diminished7 => &[0, 3, 6, 9],
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "maj",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::HalfDiminished7 => "m7b5",
+            ChordQuality::Diminished7 => "dim7",
+        }
+    }
+}
+
+/// 一段时间内的和弦标注
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChordLabel {
+    pub time: f64,
+    pub end: f64,
+    pub root: u8, // 根音音级 0-11，0 为 C
+    pub quality: ChordQuality,
+    pub name: String, // 如 "Cmaj"、"Am7"
+}
+
+// 按同时发声的音级识别和弦，最低音优先作为根音
+fn identify(pitch_classes: u16, bass_pc: u8) -> Option<(u8, ChordQuality)> {
+    if pitch_classes.count_ones() < 3 {
+        return None;
+    }
+
+    let mut best: Option<(u8, ChordQuality, (Reverse<u32>, bool))> = None;
+    for root in 0..12u8 {
+        if pitch_classes & (1 << root) == 0 {
+            continue;
+        }
+        for quality in ChordQuality::ALL {
+            let mask = quality
+                .intervals()
+                .iter()
+                .fold(0u16, |m, &i| m | 1 << ((root + i) % 12));
+            if pitch_classes & mask != mask {
+                continue;
+            }
+            // 多余的音越少越好，其次根音是最低音
+            let extra = (pitch_classes & !mask).count_ones();
+            let score = (Reverse(extra), root == bass_pc);
+            if best.is_none_or(|(_, _, s)| score > s) {
+                best = Some((root, quality, score));
+            }
+        }
+    }
+    best.map(|(root, quality, _)| (root, quality))
+}
+
+/// 识别随时间变化的和弦，events 需已按时间排序
+pub fn detect_chords(events: &[MidiEvent]) -> Vec<ChordLabel> {
+    let mut sounding = [0i32; 128];
+    let mut labels: Vec<ChordLabel> = Vec::new();
+    let mut current: Option<(f64, u8, ChordQuality)> = None;
+
+    let close = |labels: &mut Vec<ChordLabel>, start: f64, end: f64, root: u8, quality| {
+        if end - start < MIN_CHORD_SECS {
+            return;
+        }
+        // 与上一段相同的和弦合并（中间的经过音被忽略时）
+        if let Some(last) = labels.last_mut() {
+            if last.root == root && last.quality == quality {
+                last.end = end;
+                return;
+            }
+        }
+        labels.push(ChordLabel {
+            time: start,
+            end,
+            root,
+            quality,
+            name: chord_name(root, quality),
+        });
+    };
+
+    let mut i = 0;
+    while i < events.len() {
+        let time = events[i].time;
+        while i < events.len() && events[i].time == time {
+            let delta = if events[i].type_ == "note_on" { 1 } else { -1 };
+            sounding[events[i].note as usize] += delta;
+            i += 1;
+        }
+
+        let mut pitch_classes = 0u16;
+        let mut bass = None;
+        for (note, &count) in sounding.iter().enumerate() {
+            if count > 0 {
+                pitch_classes |= 1 << (note % 12);
+                bass.get_or_insert(note as u8 % 12);
+            }
+        }
+        let chord = bass.and_then(|bass_pc| identify(pitch_classes, bass_pc));
+
+        if current.map(|(_, r, q)| (r, q)) != chord {
+            if let Some((start, root, quality)) = current {
+                close(&mut labels, start, time, root, quality);
+            }
+            current = chord.map(|(root, quality)| (time, root, quality));
+        }
+    }
+
+    if let (Some((start, root, quality)), Some(last)) = (current, events.last()) {
+        close(&mut labels, start, last.time, root, quality);
+    }
+    labels
+}
+
+fn chord_name(root: u8, quality: ChordQuality) -> String {
+    format!(
+        "{}{}",
+        SHARP_NAMES[root as usize].to_uppercase(),
+        quality.suffix()
+    )
+}
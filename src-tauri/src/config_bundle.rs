@@ -0,0 +1,94 @@
+use crate::emergency_stop::{self, EmergencyStopSettings};
+use crate::json_store;
+use crate::keymap::Keymap;
+use crate::profile::ProfileStore;
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// 把档案、映射表、热键等全部配置导出为一个 JSON 文件，用于迁移到新电脑或分享给队友。
+// 曲库只记录本机的文件路径，不随配置导出。
+
+const BUNDLE_VERSION: u32 = 1;
+
+/// 配置包内容，缺少的部分导入时保持不变
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: u32,
+    pub app_version: String,
+    pub exported_at: u64, // Unix 时间戳（秒）
+    pub profiles: Option<ProfileStore>,
+    pub keymaps: Option<Vec<Keymap>>, // 只包含自定义映射表
+    pub emergency_stop: Option<EmergencyStopSettings>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub profiles: usize,
+    pub keymaps: usize,
+    pub emergency_stop: bool,
+}
+
+/// 导出当前配置到指定文件
+pub fn export(app: &AppHandle, path: &Path) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let bundle = ConfigBundle {
+        version: BUNDLE_VERSION,
+        app_version: app.package_info().version.to_string(),
+        exported_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        profiles: Some(state.profiles.snapshot()),
+        keymaps: Some(state.keymaps.snapshot()),
+        emergency_stop: Some(state.emergency_stop.settings()),
+    };
+    json_store::save(path, &bundle)?;
+    log::info!("Exported configuration to {}", path.display());
+    Ok(())
+}
+
+/// 从配置包导入；replace 时整体替换档案，否则按名称合并
+pub fn import(app: &AppHandle, path: &Path, replace: bool) -> Result<ImportSummary, String> {
+    let bundle: ConfigBundle = json_store::load(path)?
+        .ok_or_else(|| format!("Config file not found: {}", path.display()))?;
+    if bundle.version > BUNDLE_VERSION {
+        // 新版本增加的部分会被忽略，已知部分仍然导入
+        log::warn!(
+            "Config bundle version {} is newer than supported version {}",
+            bundle.version,
+            BUNDLE_VERSION
+        );
+    }
+
+    let state = app.state::<AppState>();
+    let profiles = match bundle.profiles {
+        Some(store) => state.profiles.import(store, replace)?,
+        None => 0,
+    };
+    let keymaps = match bundle.keymaps {
+        Some(keymaps) => state.keymaps.import(keymaps, replace)?,
+        None => 0,
+    };
+    let emergency_stop = match bundle.emergency_stop {
+        Some(settings) => {
+            emergency_stop::update(app, settings)?;
+            true
+        }
+        None => false,
+    };
+
+    log::info!(
+        "Imported configuration from {} ({} profiles, {} keymaps)",
+        path.display(),
+        profiles,
+        keymaps
+    );
+    Ok(ImportSummary {
+        profiles,
+        keymaps,
+        emergency_stop,
+    })
+}
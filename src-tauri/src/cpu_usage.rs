@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+// 本进程的 CPU 占用：播放期间定时采样，长时间的队列播放中占用持续偏高时，
+// 自适应调度切换到省电等待（离下一个事件较远时睡得更久、忙等窗口更短）。
+// 取不到进程 CPU 时间的平台上不采样，调度保持不变。
+
+// 占用超过这么多（100 为占满一个核）视为偏高
+const HIGH_PERCENT: f64 = 50.0;
+// 连续这么多次采样偏高才切换，避免短暂的峰值
+const HIGH_SAMPLES: u32 = 5;
+
+/// 按采样间隔计算占用率
+#[derive(Default)]
+pub struct CpuMonitor {
+    last: Option<(Instant, Duration)>, // 上次采样的时刻和进程累计 CPU 时间
+    high_samples: u32,
+}
+
+impl CpuMonitor {
+    /// 上次采样以来的平均占用（百分比，100 为占满一个核）；第一次采样或平台不支持时为 None
+    pub fn sample(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let cpu = process_cpu_time()?;
+        let (last_at, last_cpu) = self.last.replace((now, cpu))?;
+        let wall = now.duration_since(last_at).as_secs_f64();
+        if wall <= 0.0 {
+            return None;
+        }
+        let percent = cpu.saturating_sub(last_cpu).as_secs_f64() / wall * 100.0;
+        if percent >= HIGH_PERCENT {
+            self.high_samples += 1;
+        } else {
+            self.high_samples = 0;
+        }
+        Some(percent)
+    }
+
+    /// 最近连续 HIGH_SAMPLES 次采样的占用都偏高
+    pub fn is_sustained_high(&self) -> bool {
+        self.high_samples >= HIGH_SAMPLES
+    }
+}
+
+#[cfg(windows)]
+fn process_cpu_time() -> Option<Duration> {
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+    let (mut creation, mut exit, mut kernel, mut user) = (
+        FILETIME::default(),
+        FILETIME::default(),
+        FILETIME::default(),
+        FILETIME::default(),
+    );
+    unsafe {
+        GetProcessTimes(
+            GetCurrentProcess(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+    }
+    .ok()?;
+    // FILETIME 以 100 纳秒为单位
+    let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+    Some(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn process_cpu_time() -> Option<Duration> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let micros = |t: &libc::timeval| t.tv_sec.max(0) as u64 * 1_000_000 + t.tv_usec.max(0) as u64;
+    Some(Duration::from_micros(
+        micros(&usage.ru_utime) + micros(&usage.ru_stime),
+    ))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
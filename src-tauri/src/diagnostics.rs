@@ -0,0 +1,193 @@
+use crate::input_backend::{self, InputBackendErrorKind, InputBackendStatus};
+use serde::Serialize;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+// 只用于测试能否注册全局快捷键，几乎不会与其他程序冲突
+const TEST_SHORTCUT: &str = "CommandOrControl+Alt+Shift+F12";
+const TIMER_SAMPLES: u32 = 20;
+// 1ms 的 sleep 平均超过该值时认为计时器精度不足
+const COARSE_TIMER_MS: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub id: String, // "permissions" | "input_backend" | "window_enumeration" | ...
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DiagnosticCheck {
+    fn new(id: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub os: String,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+/// 依次执行各项检查，返回可在界面上逐项显示的报告
+pub fn run(app: &AppHandle) -> DiagnosticsReport {
+    let backend = input_backend::probe();
+
+    DiagnosticsReport {
+        os: std::env::consts::OS.to_string(),
+        checks: vec![
+            check_permissions(&backend),
+            check_input_backend(&backend),
+            check_window_enumeration(),
+            check_global_hotkey(app),
+            check_timer_resolution(),
+            check_display_scaling(),
+        ],
+    }
+}
+
+fn check_permissions(backend: &InputBackendStatus) -> DiagnosticCheck {
+    const ID: &str = "permissions";
+
+    if backend
+        .error
+        .as_ref()
+        .is_some_and(|e| e.kind == InputBackendErrorKind::NoPermission)
+    {
+        return DiagnosticCheck::new(
+            ID,
+            CheckStatus::Error,
+            backend.error.as_ref().unwrap().hint.clone(),
+        );
+    }
+
+    match uni_window::is_elevated() {
+        Ok(true) => DiagnosticCheck::new(ID, CheckStatus::Ok, "Running as administrator"),
+        Ok(false) => DiagnosticCheck::new(
+            ID,
+            CheckStatus::Warning,
+            "Not running as administrator; games running as administrator will ignore simulated input",
+        ),
+        // 非 Windows 平台没有提权的概念，能初始化输入后端即可
+        Err(_) => DiagnosticCheck::new(ID, CheckStatus::Ok, "Input simulation is permitted"),
+    }
+}
+
+fn check_input_backend(backend: &InputBackendStatus) -> DiagnosticCheck {
+    const ID: &str = "input_backend";
+
+    match &backend.error {
+        None => DiagnosticCheck::new(ID, CheckStatus::Ok, backend.backend.clone()),
+        Some(e) => DiagnosticCheck::new(ID, CheckStatus::Error, e.to_string()),
+    }
+}
+
+fn check_window_enumeration() -> DiagnosticCheck {
+    const ID: &str = "window_enumeration";
+
+    match uni_window::enumerate_windows() {
+        Ok(windows) if windows.is_empty() => {
+            DiagnosticCheck::new(ID, CheckStatus::Warning, "No windows found")
+        }
+        Ok(windows) => DiagnosticCheck::new(
+            ID,
+            CheckStatus::Ok,
+            format!("{} windows found", windows.len()),
+        ),
+        Err(e) => DiagnosticCheck::new(ID, CheckStatus::Error, e.to_string()),
+    }
+}
+
+fn check_global_hotkey(app: &AppHandle) -> DiagnosticCheck {
+    const ID: &str = "global_hotkey";
+    let shortcuts = app.global_shortcut();
+
+    if shortcuts.is_registered(TEST_SHORTCUT) {
+        return DiagnosticCheck::new(ID, CheckStatus::Ok, "Global hotkeys are available");
+    }
+
+    match shortcuts.register(TEST_SHORTCUT) {
+        Ok(()) => {
+            let _ = shortcuts.unregister(TEST_SHORTCUT);
+            DiagnosticCheck::new(ID, CheckStatus::Ok, "Global hotkeys are available")
+        }
+        Err(e) => DiagnosticCheck::new(
+            ID,
+            CheckStatus::Error,
+            format!("Failed to register a test hotkey: {}", e),
+        ),
+    }
+}
+
+// 测量 1ms sleep 的实际耗时，Windows 默认计时器精度约 15.6ms
+fn check_timer_resolution() -> DiagnosticCheck {
+    const ID: &str = "timer_resolution";
+
+    let start = Instant::now();
+    for _ in 0..TIMER_SAMPLES {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let avg_ms = start.elapsed().as_secs_f64() * 1000.0 / TIMER_SAMPLES as f64;
+
+    if avg_ms > COARSE_TIMER_MS {
+        DiagnosticCheck::new(
+            ID,
+            CheckStatus::Warning,
+            format!(
+                "A 1 ms sleep takes {:.1} ms on average; playback timing may jitter",
+                avg_ms
+            ),
+        )
+    } else {
+        DiagnosticCheck::new(
+            ID,
+            CheckStatus::Ok,
+            format!("A 1 ms sleep takes {:.1} ms on average", avg_ms),
+        )
+    }
+}
+
+fn check_display_scaling() -> DiagnosticCheck {
+    const ID: &str = "display_scaling";
+
+    let monitors = match uni_window::enumerate_monitors() {
+        Ok(monitors) => monitors,
+        Err(e) => return DiagnosticCheck::new(ID, CheckStatus::Error, e.to_string()),
+    };
+
+    let detail = monitors
+        .iter()
+        .map(|m| format!("{}: {}%", m.name, (m.scale_factor * 100.0).round()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // 多个显示器缩放比例不同时，拾取的坐标容易与游戏内坐标对不上
+    let mixed = monitors
+        .windows(2)
+        .any(|pair| pair[0].scale_factor != pair[1].scale_factor);
+    if mixed {
+        DiagnosticCheck::new(
+            ID,
+            CheckStatus::Warning,
+            format!(
+                "Monitors use different scaling ({}); pick coordinates per monitor to avoid offsets",
+                detail
+            ),
+        )
+    } else {
+        DiagnosticCheck::new(ID, CheckStatus::Ok, detail)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::json_store;
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+// 紧急停止热键：即使游戏窗口在前台也能立即停止所有播放
+const DEFAULT_HOTKEY: &str = "F12";
+const SETTINGS_FILE_NAME: &str = "emergency_stop.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmergencyStopSettings {
+    pub enabled: bool,
+    pub hotkey: String, // 如 "F12"、"Escape"、"ctrl+shift+s"
+}
+
+impl Default for EmergencyStopSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hotkey: DEFAULT_HOTKEY.to_string(),
+        }
+    }
+}
+
+/// 紧急停止设置，保存在配置目录的 emergency_stop.json 中
+pub struct EmergencyStop {
+    settings: Mutex<EmergencyStopSettings>,
+    path: PathBuf,
+}
+
+impl EmergencyStop {
+    pub fn load(config_dir: PathBuf) -> Self {
+        let path = config_dir.join(SETTINGS_FILE_NAME);
+        let settings = json_store::load(&path)
+            .unwrap_or_else(|e| {
+                log::error!("Failed to load emergency stop settings: {}", e);
+                None
+            })
+            .unwrap_or_default();
+        Self {
+            settings: Mutex::new(settings),
+            path,
+        }
+    }
+
+    pub fn settings(&self) -> EmergencyStopSettings {
+        self.settings.lock().unwrap().clone()
+    }
+}
+
+// 停止键盘和鼠标播放；播放线程退出时会松开仍按住的键
+fn trigger(app: &AppHandle) {
+    log::warn!("Emergency stop triggered");
+    app.state::<AppState>().stop_all();
+    let _ = app.emit("playback://emergency-stop", ());
+}
+
+fn register(app: &AppHandle, hotkey: &str) -> Result<(), String> {
+    app.global_shortcut()
+        .on_shortcut(hotkey, |app, _, event| {
+            if event.state == ShortcutState::Pressed {
+                trigger(app);
+            }
+        })
+        .map_err(|e| format!("Failed to register hotkey {}: {}", hotkey, e))
+}
+
+/// 启动时按保存的设置注册热键
+pub fn install(app: &AppHandle) {
+    let settings = app.state::<AppState>().emergency_stop.settings();
+    if settings.enabled {
+        if let Err(e) = register(app, &settings.hotkey) {
+            log::error!("{}", e);
+        }
+    }
+}
+
+/// 更换热键：先注册新的，成功后再注销旧的并保存
+pub fn update(app: &AppHandle, settings: EmergencyStopSettings) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let manager = &state.emergency_stop;
+    let mut current = manager.settings.lock().unwrap();
+
+    let unchanged = current.enabled && current.hotkey.eq_ignore_ascii_case(&settings.hotkey);
+    if settings.enabled && !unchanged {
+        register(app, &settings.hotkey)?;
+    }
+    if current.enabled && !(settings.enabled && unchanged) {
+        if let Err(e) = app.global_shortcut().unregister(current.hotkey.as_str()) {
+            log::warn!("Failed to unregister hotkey {}: {}", current.hotkey, e);
+        }
+    }
+
+    *current = settings;
+    json_store::save(&manager.path, &*current)
+}
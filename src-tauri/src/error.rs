@@ -0,0 +1,109 @@
+use crate::input_backend::{InputBackendError, InputBackendErrorKind};
+use serde::Serialize;
+use std::fmt;
+
+/// 错误代码，前端据此区分处理（如窗口丢失时提示重新锁定）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidArgument,  // 参数不合法
+    NotFound,         // 文件、档案、时间线等不存在
+    WindowLost,       // 锁定的窗口已不存在或无法激活
+    WindowError,      // 其他窗口操作失败
+    PermissionDenied, // 没有模拟输入的权限
+    InputUnavailable, // 输入后端无法使用
+    MidiParseError,   // 乐曲文件无法解析
+    InvalidEvents,    // 播放事件的时间或时长不合法
+    PlaybackBusy,     // 已有播放在进行
+    NotPlaying,       // 没有播放在进行
+    Io,               // 读写文件失败
+    Timeout,          // 等待用户操作超时
+    Other,
+}
+
+/// 命令返回给前端的错误：code 供程序判断，message 为英文描述，context 为相关的文件、窗口等
+#[derive(Debug, Clone, Serialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            context: None,
+        }
+    }
+
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidArgument, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::MidiParseError, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Io, message)
+    }
+
+    pub fn busy() -> Self {
+        Self::new(ErrorCode::PlaybackBusy, "Playback already in progress")
+    }
+
+    pub fn not_playing() -> Self {
+        Self::new(ErrorCode::NotPlaying, "No playback in progress")
+    }
+
+    /// 窗口操作（uni-window）失败
+    pub fn window(error: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::WindowError, error.to_string())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{} ({})", self.message, context),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// 尚未细分错误的模块仍返回 String
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Other, message)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(error: AppError) -> Self {
+        error.to_string()
+    }
+}
+
+impl From<InputBackendError> for AppError {
+    fn from(error: InputBackendError) -> Self {
+        let code = match error.kind {
+            InputBackendErrorKind::NoPermission => ErrorCode::PermissionDenied,
+            _ => ErrorCode::InputUnavailable,
+        };
+        Self::new(code, error.to_string())
+    }
+}
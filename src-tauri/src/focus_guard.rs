@@ -0,0 +1,104 @@
+use crate::profile::{FocusGuardMode, FocusGuardSettings};
+use crate::state::AppState;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use uni_window::WindowInfo;
+
+// 同一时间只运行一个检测线程
+static GUARD_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusEventPayload {
+    pub window: WindowInfo,
+    pub action: String, // "paused" | "resumed" | "reactivated" | "reactivate_failed" | "none"
+}
+
+/// 播放期间监视锁定窗口的焦点，失焦时按配置暂停或重新激活，暂停的播放在回到前台后自动继续；
+/// 也可以定时重新激活窗口。都在独立线程中进行，不影响播放计时
+pub fn start(app: AppHandle, window: WindowInfo, settings: FocusGuardSettings) {
+    let reassert = settings
+        .reassert_secs
+        .filter(|s| *s > 0)
+        .map(Duration::from_secs);
+    if settings.mode == FocusGuardMode::Off && reassert.is_none() {
+        return;
+    }
+    if GUARD_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let poll = Duration::from_millis(settings.poll_ms.max(50));
+        let mut was_focused = true;
+        // 由失焦暂停的播放才自动继续，用户手动暂停的不受影响
+        let mut paused_by_guard = false;
+        let mut last_reassert = Instant::now();
+
+        while state.is_any_playing() {
+            thread::sleep(poll);
+
+            // 暂停中不抢焦点，用户可能正在操作其他窗口
+            if reassert.is_some_and(|every| last_reassert.elapsed() >= every) {
+                last_reassert = Instant::now();
+                if !state.is_any_paused() {
+                    if let Err(e) = uni_window::activate_window_info(&window) {
+                        log::warn!("Failed to reassert window focus: {}", e);
+                    }
+                }
+            }
+
+            // 检测失败时视为仍在前台，避免误暂停
+            let focused = uni_window::is_window_foreground(&window).unwrap_or(true);
+
+            if was_focused && !focused {
+                let action = match settings.mode {
+                    FocusGuardMode::Pause if state.is_any_paused() => "none",
+                    FocusGuardMode::Pause => {
+                        state.pause_all();
+                        paused_by_guard = true;
+                        "paused"
+                    }
+                    FocusGuardMode::Reactivate => match uni_window::activate_window_info(&window) {
+                        Ok(()) => "reactivated",
+                        Err(e) => {
+                            log::warn!("Failed to reactivate window: {}", e);
+                            "reactivate_failed"
+                        }
+                    },
+                    FocusGuardMode::Off => "none",
+                };
+
+                let _ = app.emit(
+                    "focus://lost",
+                    FocusEventPayload {
+                        window: window.clone(),
+                        action: action.to_string(),
+                    },
+                );
+            } else if !was_focused && focused {
+                // 期间用户已手动继续时不再重复
+                let resume = std::mem::take(&mut paused_by_guard)
+                    && settings.auto_resume
+                    && state.is_any_paused();
+                if resume {
+                    state.resume_all();
+                }
+                let _ = app.emit(
+                    "focus://regained",
+                    FocusEventPayload {
+                        window: window.clone(),
+                        action: if resume { "resumed" } else { "none" }.to_string(),
+                    },
+                );
+            }
+
+            was_focused = focused;
+        }
+
+        GUARD_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
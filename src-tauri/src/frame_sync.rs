@@ -0,0 +1,36 @@
+use crate::keypress_simulator::KeyEvent;
+use crate::mouse_simulator::MouseEvent;
+
+// 帧同步：按固定帧读取输入的游戏在一帧中间收到的事件可能被丢掉，
+// 把事件时间向后对齐到帧边界，同一帧内的事件就会合成一批在帧开始时发送。
+
+fn align(time: f64, frame_secs: f64) -> f64 {
+    // 减去一个很小的量，避免本来就在边界上的事件因浮点误差被推到下一帧
+    ((time / frame_secs) - 1e-9).ceil().max(0.0) * frame_secs
+}
+
+fn frame_secs(frame_ms: f64) -> Option<f64> {
+    (frame_ms.is_finite() && frame_ms > 0.0).then_some(frame_ms / 1000.0)
+}
+
+/// 按下和松开都对齐到帧边界，按住时长至少一帧
+pub fn align_key_events(events: &mut [KeyEvent], frame_ms: f64) {
+    let Some(frame) = frame_secs(frame_ms) else {
+        return;
+    };
+    for event in events.iter_mut() {
+        let start = align(event.time, frame);
+        let end = align(event.time + event.duration, frame).max(start + frame);
+        event.time = start;
+        event.duration = end - start;
+    }
+}
+
+pub fn align_mouse_events(events: &mut [MouseEvent], frame_ms: f64) {
+    let Some(frame) = frame_secs(frame_ms) else {
+        return;
+    };
+    for event in events.iter_mut() {
+        event.time = align(event.time, frame);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::error::AppError;
+use crate::keymap::{KEYMAP_21, KEYMAP_36, KEYMAP_SKY, KEYMAP_VIRTUAL_PIANO};
+use crate::midi_analyzer::BlackKeyMode;
+use crate::state::AppState;
+use serde::Serialize;
+
+// 常见演奏类游戏的预设：映射表和音域。新用户选一个游戏即可开始，不用手动配置音域。
+
+/// 游戏预设
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GamePreset {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub keymap: &'static str,
+    pub min_note: u8,
+    pub max_note: u8,
+    pub black_key_mode: BlackKeyMode,
+}
+
+const PRESETS: [GamePreset; 5] = [
+    GamePreset {
+        id: "where_winds_meet_36",
+        name: "燕云十六声(36键)",
+        keymap: KEYMAP_36,
+        min_note: 48,
+        max_note: 83,
+        black_key_mode: BlackKeyMode::SupportBlackKey,
+    },
+    GamePreset {
+        id: "where_winds_meet_21",
+        name: "燕云十六声(21键)",
+        keymap: KEYMAP_21,
+        min_note: 48,
+        max_note: 83,
+        black_key_mode: BlackKeyMode::AutoSharp,
+    },
+    // 风物之诗琴、老旧的诗琴、镜花之琴布局相同
+    GamePreset {
+        id: "genshin_lyre",
+        name: "原神(风物之诗琴)",
+        keymap: KEYMAP_21,
+        min_note: 48,
+        max_note: 83,
+        black_key_mode: BlackKeyMode::AutoSharp,
+    },
+    GamePreset {
+        id: "sky",
+        name: "光遇",
+        keymap: KEYMAP_SKY,
+        min_note: 60,
+        max_note: 84,
+        black_key_mode: BlackKeyMode::AutoSharp,
+    },
+    GamePreset {
+        id: "roblox_piano",
+        name: "Roblox 钢琴(Virtual Piano)",
+        keymap: KEYMAP_VIRTUAL_PIANO,
+        min_note: 36,
+        max_note: 96,
+        black_key_mode: BlackKeyMode::SupportBlackKey,
+    },
+];
+
+pub fn presets() -> Vec<GamePreset> {
+    PRESETS.to_vec()
+}
+
+/// 应用预设：切换到以游戏命名的档案（没有时新建）并设置其映射表，
+/// 返回预设供前端设置音域和黑键处理方式
+pub fn apply(state: &AppState, id: &str) -> Result<GamePreset, AppError> {
+    let preset = *PRESETS
+        .iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| AppError::not_found("Game preset not found").with_context(id))?;
+
+    let mut profile = state
+        .profiles
+        .list_profiles()
+        .into_iter()
+        .find(|p| p.name == preset.name)
+        .unwrap_or_default();
+    profile.name = preset.name.to_string();
+    profile.keymap = Some(preset.keymap.to_string());
+    state.profiles.save_profile(profile)?;
+    state.profiles.set_active_profile(preset.name)?;
+    log::info!("Applied game preset {}", preset.id);
+    Ok(preset)
+}
@@ -0,0 +1,95 @@
+use crate::keypress_simulator::KeyEvent;
+use crate::profile::KeyboardMatrixSettings;
+use crate::warning::Warning;
+use serde::Serialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GhostingCheck {
+    pub events: Vec<KeyEvent>,
+    pub warnings: Vec<Warning>,
+}
+
+// 组合键拆成单个物理按键，如 "shift+a" -> ["shift", "a"]
+fn physical_keys(key: &str) -> impl Iterator<Item = String> + '_ {
+    key.split('+').map(|k| k.trim().to_lowercase())
+}
+
+fn conflicts(pressed: &HashSet<String>, settings: &KeyboardMatrixSettings) -> bool {
+    if settings
+        .max_simultaneous
+        .is_some_and(|max| pressed.len() > max)
+    {
+        return true;
+    }
+    settings.ghost_combos.iter().any(|combo| {
+        !combo.is_empty()
+            && combo
+                .iter()
+                .all(|k| pressed.contains(&k.trim().to_lowercase()))
+    })
+}
+
+/// 检查按键序列中会在键盘矩阵上串键的按下，返回冲突数
+/// revoice 开启时去掉冲突的按键，保留先按下的键
+pub fn check(events: &mut Vec<KeyEvent>, settings: &KeyboardMatrixSettings) -> usize {
+    if settings.max_simultaneous.is_none() && settings.ghost_combos.is_empty() {
+        return 0;
+    }
+
+    let mut order: Vec<usize> = (0..events.len()).collect();
+    order.sort_by(|&a, &b| {
+        events[a]
+            .time
+            .partial_cmp(&events[b].time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    // (松开时间, 物理按键)
+    let mut held: Vec<(f64, Vec<String>)> = Vec::new();
+    let mut keep = vec![true; events.len()];
+    let mut conflict_count = 0;
+
+    for i in order {
+        let event = &events[i];
+        held.retain(|(end, _)| *end > event.time);
+
+        let keys: Vec<String> = physical_keys(&event.key).collect();
+        let pressed: HashSet<String> = held
+            .iter()
+            .flat_map(|(_, k)| k.iter().cloned())
+            .chain(keys.iter().cloned())
+            .collect();
+
+        if conflicts(&pressed, settings) {
+            conflict_count += 1;
+            if settings.revoice {
+                keep[i] = false;
+                continue;
+            }
+        }
+        held.push((event.time + event.duration, keys));
+    }
+
+    if settings.revoice {
+        let mut index = 0;
+        events.retain(|_| {
+            index += 1;
+            keep[index - 1]
+        });
+    }
+    conflict_count
+}
+
+/// 检查并返回处理后的序列和警告，供前端播放前提示
+pub fn check_events(mut events: Vec<KeyEvent>, settings: &KeyboardMatrixSettings) -> GhostingCheck {
+    let count = check(&mut events, settings);
+    let mut warnings = Vec::new();
+    if count > 0 {
+        warnings.push(Warning::KeyGhosting {
+            count,
+            revoiced: settings.revoice,
+        });
+    }
+    GhostingCheck { events, warnings }
+}
@@ -0,0 +1,364 @@
+use crate::guitar_pro::{extend_tied, select_tracks, ParsedTrack};
+use crate::score_import::{ImportedNote, ImportedSong, TICKS_PER_BEAT};
+use crate::xml_tree::{parse_xml, Node};
+
+// Guitar Pro 6 的 .gpx 是压缩的 BCFZ 容器，内部的 BCFS 文件系统中保存 score.gpif（XML）
+
+const SECTOR_SIZE: usize = 0x1000;
+const SCORE_FILE: &str = "score.gpif";
+const WHOLE_NOTE: f64 = TICKS_PER_BEAT as f64 * 4.0;
+const DEFAULT_VELOCITY: u8 = 95;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // 以位计
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Option<usize> {
+        let byte = *self.data.get(self.pos / 8)?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Some(bit as usize)
+    }
+
+    // 高位在前
+    fn bits(&mut self, count: usize) -> Option<usize> {
+        (0..count).try_fold(0, |value, _| Some((value << 1) | self.bit()?))
+    }
+
+    // 低位在前
+    fn bits_reversed(&mut self, count: usize) -> Option<usize> {
+        (0..count).try_fold(0, |value, i| Some(value | (self.bit()? << i)))
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+// BCFZ 解压：每段以 1 位标志开头，1 为引用前文的一段，0 为直接存放的若干字节
+fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let expected = read_u32(data, 0).ok_or("Invalid GPX file")?;
+    let mut reader = BitReader {
+        data: &data[4..],
+        pos: 0,
+    };
+    // 头部的长度来自文件本身，预分配不超过压缩数据的几倍，避免构造的文件一次申请过多内存
+    let mut output: Vec<u8> = Vec::with_capacity(expected.min(data.len().saturating_mul(4)));
+
+    while output.len() < expected {
+        let Some(flag) = reader.bit() else {
+            break;
+        };
+        if flag == 1 {
+            let word_size = reader.bits(4).ok_or("Invalid GPX file")?;
+            let offset = reader.bits_reversed(word_size).ok_or("Invalid GPX file")?;
+            let size = reader.bits_reversed(word_size).ok_or("Invalid GPX file")?;
+            if offset == 0 || offset > output.len() {
+                return Err("Invalid GPX file".to_string());
+            }
+            let start = output.len() - offset;
+            output.extend_from_within(start..start + offset.min(size));
+        } else {
+            let size = reader.bits_reversed(2).ok_or("Invalid GPX file")?;
+            for _ in 0..size {
+                output.push(reader.bits(8).ok_or("Invalid GPX file")? as u8);
+            }
+        }
+    }
+    Ok(output)
+}
+
+// 从 BCFS 文件系统中取出指定文件，data 不含 "BCFS" 头
+fn read_file(data: &[u8], name: &str) -> Option<Vec<u8>> {
+    let mut offset = SECTOR_SIZE;
+    while offset + 3 < data.len() {
+        if read_u32(data, offset) == Some(2) {
+            let name_bytes = data.get(offset + 0x04..offset + 0x04 + 127)?;
+            let end = name_bytes.iter().position(|&b| b == 0).unwrap_or(127);
+            let file_name = String::from_utf8_lossy(&name_bytes[..end]);
+            let file_size = read_u32(data, offset + 0x8C)?;
+
+            // 文件内容分散在若干扇区中，扇区号列表以 0 结束
+            let table = offset + 0x94;
+            let mut content = Vec::new();
+            let mut index = 0;
+            while let Some(sector) = read_u32(data, table + 4 * index).filter(|&s| s != 0) {
+                let start = sector * SECTOR_SIZE;
+                let end = (start + SECTOR_SIZE).min(data.len());
+                content.extend_from_slice(data.get(start..end)?);
+                index += 1;
+            }
+
+            if file_name == name {
+                content.truncate(file_size);
+                return Some(content);
+            }
+        }
+        offset += SECTOR_SIZE;
+    }
+    None
+}
+
+fn ids(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+}
+
+// 时值：音符类型、附点和连音
+fn rhythm_length(rhythm: &Node) -> u32 {
+    let divisor = match rhythm.child("NoteValue").map(|n| n.text()) {
+        Some("DoubleWhole") => 0.5,
+        Some("Whole") => 1.0,
+        Some("Half") => 2.0,
+        Some("Eighth") => 8.0,
+        Some("16th") => 16.0,
+        Some("32nd") => 32.0,
+        Some("64th") => 64.0,
+        Some("128th") => 128.0,
+        _ => 4.0,
+    };
+    let mut length = WHOLE_NOTE / divisor;
+
+    let dots = rhythm
+        .child("AugmentationDot")
+        .and_then(|d| d.attr("count"))
+        .and_then(|c| c.parse::<i32>().ok())
+        .unwrap_or(0);
+    length *= 2.0 - 0.5f64.powi(dots);
+
+    if let Some(tuplet) = rhythm.child("PrimaryTuplet") {
+        let num: f64 = tuplet
+            .attr("num")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1.0);
+        let den: f64 = tuplet
+            .attr("den")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1.0);
+        if num > 0.0 {
+            length = length * den / num;
+        }
+    }
+    length.round() as u32
+}
+
+fn property<'a>(note: &'a Node, name: &str) -> Option<&'a Node> {
+    note.child("Properties")?
+        .children
+        .iter()
+        .find(|p| p.attr("name") == Some(name))
+}
+
+fn property_value(note: &Node, name: &str, field: &str) -> Option<i32> {
+    property(note, name)?.child(field)?.text().parse().ok()
+}
+
+// 音高依次取 MIDI 音高、弦和品、记谱音高
+fn note_key(note: &Node, tuning: &[i32]) -> Option<u8> {
+    let key = if let Some(midi) = property_value(note, "Midi", "Number") {
+        midi
+    } else if let (Some(string), Some(fret)) = (
+        property_value(note, "String", "String"),
+        property_value(note, "Fret", "Fret"),
+    ) {
+        tuning.get(usize::try_from(string).ok()?)? + fret
+    } else {
+        let pitch = property(note, "ConcertPitch")?.child("Pitch")?;
+        let step = match pitch.child("Step")?.text() {
+            "C" => 0,
+            "D" => 2,
+            "E" => 4,
+            "F" => 5,
+            "G" => 7,
+            "A" => 9,
+            "B" => 11,
+            _ => return None,
+        };
+        let accidental = match pitch.child("Accidental").map(|a| a.text()) {
+            Some("#") => 1,
+            Some("##") | Some("x") => 2,
+            Some("b") => -1,
+            Some("bb") => -2,
+            _ => 0,
+        };
+        let octave: i32 = pitch.child("Octave")?.text().parse().ok()?;
+        (octave + 1) * 12 + step + accidental
+    };
+    Some(key.clamp(0, 127) as u8)
+}
+
+// 速度值形如 "120 2"，第二项为参考时值（1 八分、2 四分、3 附点四分、4 二分、5 附点二分）
+fn tempo_bpm(value: &str) -> Option<f64> {
+    let mut parts = value.split_whitespace();
+    let bpm: f64 = parts.next()?.parse().ok()?;
+    let factor = match parts.next() {
+        Some("1") => 0.5,
+        Some("3") => 1.5,
+        Some("4") => 2.0,
+        Some("5") => 3.0,
+        _ => 1.0,
+    };
+    Some(bpm * factor)
+}
+
+/// 解析 .gpx 文件
+pub fn parse(bytes: &[u8], selected: Option<usize>) -> Result<ImportedSong, String> {
+    let filesystem = match bytes.get(..4) {
+        Some(b"BCFZ") => {
+            let data = decompress(&bytes[4..])?;
+            data.get(4..).unwrap_or_default().to_vec()
+        }
+        Some(b"BCFS") => bytes[4..].to_vec(),
+        _ => return Err("Invalid GPX file".to_string()),
+    };
+    let score = read_file(&filesystem, SCORE_FILE).ok_or("score.gpif not found in GPX file")?;
+    let root = parse_xml(&String::from_utf8_lossy(&score))?;
+
+    let section = |name: &str| {
+        root.child(name)
+            .map(|n| n.index_by_id())
+            .unwrap_or_default()
+    };
+    let bars = section("Bars");
+    let voices = section("Voices");
+    let beats = section("Beats");
+    let notes = section("Notes");
+    let rhythms = section("Rhythms");
+    let master_bars: Vec<&Node> = root
+        .child("MasterBars")
+        .map(|m| m.children.iter().collect())
+        .unwrap_or_default();
+
+    let score_text = |name: &str| {
+        root.path(&["Score", name])
+            .map(|n| n.text().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let mut song = ImportedSong {
+        title: score_text("Title"),
+        composer: score_text("Music").or_else(|| score_text("Artist")),
+        ..Default::default()
+    };
+
+    // 每个小节的起始 tick 和拍号
+    let mut bar_starts = Vec::with_capacity(master_bars.len());
+    let mut tick = 0;
+    let mut last_time = String::new();
+    for master_bar in &master_bars {
+        let time = master_bar.child("Time").map(|t| t.text()).unwrap_or("4/4");
+        let (num, den) = time.split_once('/').unwrap_or(("4", "4"));
+        let (num, den): (u8, u8) = (num.parse().unwrap_or(4), den.parse().unwrap_or(4));
+        if time != last_time {
+            song.time_signatures.push((tick, num, den));
+            last_time = time.to_string();
+        }
+        bar_starts.push(tick);
+        tick += num as u32 * WHOLE_NOTE as u32 / den.max(1) as u32;
+    }
+
+    if let Some(automations) = root.path(&["MasterTrack", "Automations"]) {
+        for automation in &automations.children {
+            if automation.child("Type").map(|t| t.text()) != Some("Tempo") {
+                continue;
+            }
+            let bar: usize = automation
+                .child("Bar")
+                .and_then(|b| b.text().parse().ok())
+                .unwrap_or(0);
+            let position: f64 = automation
+                .child("Position")
+                .and_then(|p| p.text().parse().ok())
+                .unwrap_or(0.0);
+            let (Some(&start), Some(bpm)) = (
+                bar_starts.get(bar),
+                automation.child("Value").and_then(|v| tempo_bpm(v.text())),
+            ) else {
+                continue;
+            };
+            let bar_end = bar_starts.get(bar + 1).copied().unwrap_or(tick);
+            let offset = ((bar_end - start) as f64 * position.clamp(0.0, 1.0)) as u32;
+            song.tempo_changes.push((start + offset, bpm));
+        }
+    }
+
+    let track_nodes: Vec<&Node> = root
+        .child("Tracks")
+        .map(|t| t.children.iter().collect())
+        .unwrap_or_default();
+    let mut tracks = Vec::with_capacity(track_nodes.len());
+
+    for (t, track) in track_nodes.iter().enumerate() {
+        let tuning: Vec<i32> = track
+            .find(&|n| n.name == "Property" && n.attr("name") == Some("Tuning"))
+            .and_then(|p| p.child("Pitches"))
+            .map(|p| ids(p.text()).filter_map(|v| v.parse().ok()).collect())
+            .unwrap_or_default();
+        let percussion = track
+            .find(&|n| {
+                n.attr("table") == Some("Percussion") || (n.name == "Type" && n.text() == "drumKit")
+            })
+            .is_some();
+
+        let mut track_notes: Vec<ImportedNote> = Vec::new();
+        for (master_bar, &bar_start) in master_bars.iter().zip(&bar_starts) {
+            let bar = master_bar
+                .child("Bars")
+                .and_then(|b| ids(b.text()).nth(t))
+                .and_then(|id| bars.get(id));
+            let Some(bar) = bar else {
+                continue;
+            };
+
+            let voice_ids = bar.child("Voices").map(|v| v.text()).unwrap_or_default();
+            for voice in ids(voice_ids).filter_map(|id| voices.get(id)) {
+                let mut tick = bar_start;
+                let beat_ids = voice.child("Beats").map(|b| b.text()).unwrap_or_default();
+                for beat in ids(beat_ids).filter_map(|id| beats.get(id)) {
+                    // 装饰音不占拍
+                    if beat.child("GraceNotes").is_some() {
+                        continue;
+                    }
+                    let length = beat
+                        .child("Rhythm")
+                        .and_then(|r| r.attr("ref"))
+                        .and_then(|id| rhythms.get(id))
+                        .map(|r| rhythm_length(r))
+                        .unwrap_or(TICKS_PER_BEAT as u32);
+
+                    let note_ids = beat.child("Notes").map(|n| n.text()).unwrap_or_default();
+                    for note in ids(note_ids).filter_map(|id| notes.get(id)) {
+                        let Some(key) = note_key(note, &tuning) else {
+                            continue;
+                        };
+                        let tied =
+                            note.child("Tie").and_then(|t| t.attr("destination")) == Some("true");
+                        if tied && extend_tied(&mut track_notes, key, tick, length) {
+                            continue;
+                        }
+                        track_notes.push(ImportedNote {
+                            tick,
+                            length,
+                            key,
+                            velocity: DEFAULT_VELOCITY,
+                        });
+                    }
+                    tick += length;
+                }
+            }
+        }
+
+        tracks.push(ParsedTrack {
+            name: track
+                .child("Name")
+                .map(|n| n.text().to_string())
+                .unwrap_or_else(|| format!("Track {}", t + 1)),
+            percussion,
+            notes: track_notes,
+        });
+    }
+
+    select_tracks(&mut song, tracks, selected)?;
+    Ok(song)
+}
@@ -0,0 +1,499 @@
+use crate::gpx;
+use crate::score_import::{ImportedNote, ImportedSong, ImportedTrack, TICKS_PER_BEAT};
+
+// 全音符的 tick 数
+const WHOLE_NOTE: u32 = TICKS_PER_BEAT as u32 * 4;
+// 未标力度的音符按 f 处理
+const DEFAULT_VELOCITY: u8 = 95;
+const PERCUSSION_CHANNEL: i32 = 9;
+
+/// 按扩展名导入 Guitar Pro 文件，track 为要导入的音轨序号，None 时导入所有非打击乐音轨
+pub fn import(bytes: &[u8], extension: &str, track: Option<usize>) -> Result<ImportedSong, String> {
+    match extension {
+        "gp5" => parse_gp5(bytes, track),
+        "gpx" => gpx::parse(bytes, track),
+        _ => Err(format!("Unsupported Guitar Pro format: .{}", extension)),
+    }
+}
+
+/// 解析得到的单个音轨，选择音轨前先全部读出
+pub(crate) struct ParsedTrack {
+    pub name: String,
+    pub percussion: bool,
+    pub notes: Vec<ImportedNote>,
+}
+
+/// 按选择的音轨组装成导入结果
+pub(crate) fn select_tracks(
+    song: &mut ImportedSong,
+    tracks: Vec<ParsedTrack>,
+    selected: Option<usize>,
+) -> Result<(), String> {
+    if let Some(index) = selected {
+        if index >= tracks.len() {
+            return Err(format!(
+                "Track {} not found, file has {} tracks",
+                index,
+                tracks.len()
+            ));
+        }
+    }
+
+    for (i, track) in tracks.into_iter().enumerate() {
+        let wanted = match selected {
+            Some(index) => index == i,
+            None => !track.percussion,
+        };
+        if wanted {
+            song.tracks.push(ImportedTrack {
+                name: track.name,
+                channel: if track.percussion {
+                    PERCUSSION_CHANNEL as u8
+                } else {
+                    (song.tracks.len() % 9) as u8
+                },
+                notes: track.notes,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 同音连音线：延长在 tick 处结束的同音高音符
+pub(crate) fn extend_tied(notes: &mut [ImportedNote], key: u8, tick: u32, length: u32) -> bool {
+    match notes
+        .iter_mut()
+        .rev()
+        .find(|n| n.key == key && n.tick + n.length == tick)
+    {
+        Some(note) => {
+            note.length += length;
+            true
+        }
+        None => false,
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(count)
+            .filter(|&e| e <= self.data.len());
+        let end = end.ok_or("Unexpected end of Guitar Pro file")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, String> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        usize::try_from(self.i32()?).map_err(|_| "Invalid Guitar Pro file".to_string())
+    }
+
+    // 1 字节长度 + 固定 size 字节
+    fn byte_size_string(&mut self, size: usize) -> Result<String, String> {
+        let length = self.u8()? as usize;
+        let bytes = self.bytes(size)?;
+        Ok(String::from_utf8_lossy(&bytes[..length.min(size)]).to_string())
+    }
+
+    // 4 字节总长度 + 1 字节长度 + 内容
+    fn int_byte_size_string(&mut self) -> Result<String, String> {
+        let size = self.count()?;
+        self.byte_size_string(size.saturating_sub(1))
+    }
+
+    fn int_size_string(&mut self) -> Result<String, String> {
+        let size = self.count()?;
+        Ok(String::from_utf8_lossy(self.bytes(size)?).to_string())
+    }
+
+    fn bend(&mut self) -> Result<(), String> {
+        self.skip(5)?;
+        let points = self.count()?;
+        self.skip(points * 9)
+    }
+}
+
+struct Gp5Track {
+    name: String,
+    tuning: Vec<i32>, // 从 1 弦（最高音）开始
+    percussion: bool,
+    notes: Vec<ImportedNote>,
+}
+
+fn parse_gp5(bytes: &[u8], selected: Option<usize>) -> Result<ImportedSong, String> {
+    let mut r = Reader {
+        data: bytes,
+        pos: 0,
+    };
+
+    let version = r.byte_size_string(30)?;
+    let v510 = match version.as_str() {
+        "FICHIER GUITAR PRO v5.00" => false,
+        "FICHIER GUITAR PRO v5.10" => true,
+        _ => return Err(format!("Unsupported Guitar Pro version: {}", version)),
+    };
+
+    // 曲目信息：曲名、副标题、艺术家、专辑、作词、作曲、版权、制谱、说明
+    let mut info = Vec::with_capacity(9);
+    for _ in 0..9 {
+        info.push(r.int_byte_size_string()?);
+    }
+    for _ in 0..r.count()? {
+        r.int_byte_size_string()?;
+    }
+    // 歌词
+    r.i32()?;
+    for _ in 0..5 {
+        r.i32()?;
+        r.int_size_string()?;
+    }
+    if v510 {
+        r.skip(19)?; // RSE 主效果
+    }
+    // 页面设置、页眉页脚文字和速度名称
+    r.skip(30)?;
+    for _ in 0..11 {
+        r.int_byte_size_string()?;
+    }
+    let tempo = r.i32()?;
+    if v510 {
+        r.skip(1)?;
+    }
+    r.skip(5)?; // 调号和八度
+    r.skip(64 * 12)?; // MIDI 通道
+    r.skip(42)?; // 反复记号和混响
+
+    let measure_count = r.count()?;
+    let track_count = r.count()?;
+
+    let mut song = ImportedSong {
+        title: Some(info[0].trim().to_string()).filter(|t| !t.is_empty()),
+        composer: [&info[5], &info[2]]
+            .into_iter()
+            .map(|c| c.trim().to_string())
+            .find(|c| !c.is_empty()),
+        tempo_changes: vec![(0, tempo as f64)],
+        ..Default::default()
+    };
+
+    // 小节头：拍号
+    let mut signature = (4u8, 4u8);
+    let mut signatures = Vec::with_capacity(measure_count);
+    for i in 0..measure_count {
+        if i > 0 {
+            r.skip(1)?;
+        }
+        let flags = r.u8()?;
+        if flags & 0x01 != 0 {
+            signature.0 = r.u8()?;
+        }
+        if flags & 0x02 != 0 {
+            signature.1 = r.u8()?;
+        }
+        if flags & 0x08 != 0 {
+            r.skip(1)?; // 反复结束
+        }
+        if flags & 0x20 != 0 {
+            r.int_byte_size_string()?; // 标记名和颜色
+            r.skip(4)?;
+        }
+        if flags & 0x10 != 0 {
+            r.skip(1)?; // 反复跳房子
+        }
+        if flags & 0x40 != 0 {
+            r.skip(2)?; // 调号
+        }
+        if flags & 0x03 != 0 {
+            r.skip(4)?; // 符杠分组
+        }
+        if flags & 0x10 == 0 {
+            r.skip(1)?;
+        }
+        r.skip(1)?; // 三连音律动
+        signatures.push((signature, i == 0 || flags & 0x03 != 0));
+    }
+
+    let mut tracks = Vec::with_capacity(track_count);
+    for i in 0..track_count {
+        if i == 0 || !v510 {
+            r.skip(1)?;
+        }
+        r.skip(1)?; // 音轨标志
+        let name = r.byte_size_string(40)?;
+        let string_count = r.count()?;
+        let mut tuning = Vec::new();
+        for s in 0..7 {
+            let pitch = r.i32()?;
+            if s < string_count {
+                tuning.push(pitch);
+            }
+        }
+        r.i32()?; // 端口
+        let channel = r.i32()? - 1;
+        r.i32()?; // 效果通道
+        r.i32()?; // 品数
+        r.i32()?; // 变调夹
+        r.skip(4)?; // 颜色
+        r.skip(if v510 { 49 } else { 44 })?; // 显示设置和 RSE
+        if v510 {
+            r.int_byte_size_string()?;
+            r.int_byte_size_string()?;
+        }
+        tracks.push(Gp5Track {
+            name: name.trim().to_string(),
+            tuning,
+            percussion: channel.rem_euclid(16) == PERCUSSION_CHANNEL,
+            notes: Vec::new(),
+        });
+    }
+    r.skip(if v510 { 1 } else { 2 })?;
+
+    let mut measure_start = 0;
+    for &((numerator, denominator), changed) in &signatures {
+        if changed {
+            song.time_signatures
+                .push((measure_start, numerator, denominator));
+        }
+        for track in tracks.iter_mut() {
+            for _ in 0..2 {
+                let mut tick = measure_start;
+                for _ in 0..r.count()? {
+                    tick += read_beat(&mut r, v510, track, tick, &mut song.tempo_changes)?;
+                }
+            }
+            r.skip(1)?; // 换行标志
+        }
+        measure_start += numerator as u32 * WHOLE_NOTE / denominator.max(1) as u32;
+    }
+
+    let parsed = tracks
+        .into_iter()
+        .map(|t| ParsedTrack {
+            name: t.name,
+            percussion: t.percussion,
+            notes: t.notes,
+        })
+        .collect();
+    select_tracks(&mut song, parsed, selected)?;
+    Ok(song)
+}
+
+// 读取一拍，返回该拍占用的 tick 数
+fn read_beat(
+    r: &mut Reader,
+    v510: bool,
+    track: &mut Gp5Track,
+    tick: u32,
+    tempo_changes: &mut Vec<(u32, f64)>,
+) -> Result<u32, String> {
+    let flags = r.u8()?;
+    // 0 为空拍，不占时间
+    let status = if flags & 0x40 != 0 { r.u8()? } else { 1 };
+
+    let value = r.i8()?.clamp(-2, 5);
+    let mut length = WHOLE_NOTE >> (value + 2);
+    if flags & 0x01 != 0 {
+        length = length * 3 / 2;
+    }
+    if flags & 0x20 != 0 {
+        let (enters, times) = match r.i32()? {
+            3 => (3, 2),
+            n @ 5..=7 => (n as u32, 4),
+            n @ 9..=13 => (n as u32, 8),
+            _ => (1, 1),
+        };
+        length = length * times / enters;
+    }
+
+    if flags & 0x02 != 0 {
+        // 和弦图
+        r.skip(17)?;
+        r.byte_size_string(21)?;
+        r.skip(4 + 4 + 7 * 4 + 32)?;
+    }
+    if flags & 0x04 != 0 {
+        r.int_byte_size_string()?;
+    }
+    if flags & 0x08 != 0 {
+        let flags1 = r.u8()?;
+        let flags2 = r.u8()?;
+        if flags1 & 0x20 != 0 {
+            r.skip(1)?;
+        }
+        if flags2 & 0x04 != 0 {
+            r.bend()?;
+        }
+        if flags1 & 0x40 != 0 {
+            r.skip(2)?;
+        }
+        if flags2 & 0x02 != 0 {
+            r.skip(1)?;
+        }
+    }
+    if flags & 0x10 != 0 {
+        read_mix_table(r, v510, tick, tempo_changes)?;
+    }
+
+    let string_flags = r.u8()?;
+    for s in 0..track.tuning.len() {
+        if string_flags & (1 << (6 - s)) == 0 {
+            continue;
+        }
+        let note = read_note(r)?;
+        if status == 0 || note.kind == NoteKind::Dead {
+            continue;
+        }
+
+        let key = if track.percussion {
+            note.fret
+        } else {
+            track.tuning[s] + note.fret
+        }
+        .clamp(0, 127) as u8;
+        if note.kind == NoteKind::Tied && extend_tied(&mut track.notes, key, tick, length) {
+            continue;
+        }
+        track.notes.push(ImportedNote {
+            tick,
+            length,
+            key,
+            velocity: note.velocity,
+        });
+    }
+
+    r.skip(1)?;
+    if r.u8()? & 0x08 != 0 {
+        r.skip(1)?;
+    }
+    Ok(if status == 0 { 0 } else { length })
+}
+
+fn read_mix_table(
+    r: &mut Reader,
+    v510: bool,
+    tick: u32,
+    tempo_changes: &mut Vec<(u32, f64)>,
+) -> Result<(), String> {
+    r.skip(1)?; // 乐器
+    r.skip(16)?; // RSE 乐器
+    let mut values = [0i8; 6]; // 音量、声像、合唱、混响、移相、颤音
+    for value in values.iter_mut() {
+        *value = r.i8()?;
+    }
+    r.int_byte_size_string()?;
+    let tempo = r.i32()?;
+
+    // 有变化的项目各带一个渐变时长
+    for value in values {
+        if value >= 0 {
+            r.skip(1)?;
+        }
+    }
+    if tempo >= 0 {
+        r.skip(if v510 { 2 } else { 1 })?;
+        tempo_changes.push((tick, tempo as f64));
+    }
+    r.skip(2)?; // 应用范围标志和哇音
+    if v510 {
+        r.int_byte_size_string()?;
+        r.int_byte_size_string()?;
+    }
+    Ok(())
+}
+
+#[derive(PartialEq, Eq)]
+enum NoteKind {
+    Normal,
+    Tied,
+    Dead,
+}
+
+struct Gp5Note {
+    kind: NoteKind,
+    fret: i32,
+    velocity: u8,
+}
+
+fn read_note(r: &mut Reader) -> Result<Gp5Note, String> {
+    let flags = r.u8()?;
+    let kind = if flags & 0x20 != 0 {
+        match r.u8()? {
+            2 => NoteKind::Tied,
+            3 => NoteKind::Dead,
+            _ => NoteKind::Normal,
+        }
+    } else {
+        NoteKind::Normal
+    };
+    let velocity = if flags & 0x10 != 0 {
+        // 力度等级 ppp(1) 到 fff(8)
+        (15 + 16 * (r.i8()? as i32 - 1)).clamp(1, 127) as u8
+    } else {
+        DEFAULT_VELOCITY
+    };
+    let fret = if flags & 0x20 != 0 { r.i8()? as i32 } else { 0 };
+    if flags & 0x80 != 0 {
+        r.skip(2)?; // 指法
+    }
+    if flags & 0x01 != 0 {
+        r.skip(8)?; // 时值百分比
+    }
+    r.skip(1)?;
+
+    if flags & 0x08 != 0 {
+        let flags1 = r.u8()?;
+        let flags2 = r.u8()?;
+        if flags1 & 0x01 != 0 {
+            r.bend()?;
+        }
+        if flags1 & 0x10 != 0 {
+            r.skip(5)?; // 装饰音
+        }
+        if flags2 & 0x04 != 0 {
+            r.skip(1)?; // 轮指
+        }
+        if flags2 & 0x08 != 0 {
+            r.skip(1)?; // 滑音
+        }
+        if flags2 & 0x10 != 0 {
+            match r.i8()? {
+                2 => r.skip(3)?,
+                3 => r.skip(1)?,
+                _ => {}
+            }
+        }
+        if flags2 & 0x20 != 0 {
+            r.skip(2)?; // 颤音
+        }
+    }
+
+    Ok(Gp5Note {
+        kind,
+        fret,
+        velocity,
+    })
+}
@@ -0,0 +1,252 @@
+use crate::score_import::{ImportedNote, ImportedSong, ImportedTrack, TICKS_PER_BEAT};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_RECORD_SECS: f64 = 30.0;
+const DEFAULT_RECORD_SECS: f64 = 8.0;
+const RECORDINGS_DIR: &str = "recordings";
+
+// 音高检测的分析窗口和步长
+const FRAME_SECS: f64 = 0.04;
+const HOP_SECS: f64 = 0.01;
+// 哼唱和口哨的音高范围
+const MIN_FREQ: f64 = 70.0;
+const MAX_FREQ: f64 = 2000.0;
+// 低于此音量视为静音
+const SILENCE_RMS: f32 = 0.02;
+// YIN 算法的判定阈值，越小越严格
+const YIN_THRESHOLD: f32 = 0.15;
+// 中值滤波的帧数，去掉八度跳变等孤立错误
+const MEDIAN_FRAMES: usize = 5;
+// 比这更短的音符视为噪声
+const MIN_NOTE_SECS: f64 = 0.08;
+// 录音按 120 BPM 保存，每秒对应的 tick 数
+const TICKS_PER_SEC: f64 = TICKS_PER_BEAT as f64 * 2.0;
+
+/// 从默认麦克风录音，识别成单声部旋律并保存为 MIDI 文件，返回文件路径
+pub fn record_melody(data_dir: &Path, seconds: Option<f64>) -> Result<PathBuf, String> {
+    let seconds = seconds
+        .unwrap_or(DEFAULT_RECORD_SECS)
+        .clamp(1.0, MAX_RECORD_SECS);
+    let (samples, sample_rate) = record(seconds)?;
+    let notes = transcribe(&samples, sample_rate);
+    if notes.is_empty() {
+        return Err("No pitched sound detected in recording".to_string());
+    }
+    log::info!("Transcribed {} notes from recording", notes.len());
+
+    let song = ImportedSong {
+        title: Some("Hummed melody".to_string()),
+        tempo_changes: vec![(0, 120.0)],
+        tracks: vec![ImportedTrack {
+            name: "Melody".to_string(),
+            channel: 0,
+            notes,
+        }],
+        ..Default::default()
+    };
+    let bytes = song.to_smf_bytes()?;
+
+    let dir = data_dir.join(RECORDINGS_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("hum-{}.mid", stamp));
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(path)
+}
+
+// 录制指定时长，混合为单声道
+fn record(seconds: f64) -> Result<(Vec<f32>, u32), String> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("No microphone found")?;
+    let config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to query microphone: {}", e))?;
+    let channels = config.channels().max(1) as usize;
+    let sample_rate = config.sample_rate().0;
+
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let on_error = |e| log::warn!("Microphone stream error: {}", e);
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => {
+            let buffer = Arc::clone(&buffer);
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[f32], _: &_| push_mono(&buffer, data, channels, |s| s),
+                on_error,
+                None,
+            )
+        }
+        cpal::SampleFormat::I16 => {
+            let buffer = Arc::clone(&buffer);
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[i16], _: &_| {
+                    push_mono(&buffer, data, channels, |s| s as f32 / i16::MAX as f32)
+                },
+                on_error,
+                None,
+            )
+        }
+        cpal::SampleFormat::U16 => {
+            let buffer = Arc::clone(&buffer);
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[u16], _: &_| {
+                    push_mono(&buffer, data, channels, |s| (s as f32 - 32768.0) / 32768.0)
+                },
+                on_error,
+                None,
+            )
+        }
+        other => return Err(format!("Unsupported microphone sample format: {:?}", other)),
+    }
+    .map_err(|e| format!("Failed to open microphone: {}", e))?;
+
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start recording: {}", e))?;
+    std::thread::sleep(Duration::from_secs_f64(seconds));
+    drop(stream);
+
+    let samples = std::mem::take(&mut *buffer.lock().unwrap());
+    Ok((samples, sample_rate))
+}
+
+fn push_mono<T: Copy>(
+    buffer: &Mutex<Vec<f32>>,
+    data: &[T],
+    channels: usize,
+    to_f32: impl Fn(T) -> f32,
+) {
+    let mut buffer = buffer.lock().unwrap();
+    for frame in data.chunks(channels) {
+        let sum: f32 = frame.iter().map(|&s| to_f32(s)).sum();
+        buffer.push(sum / frame.len() as f32);
+    }
+}
+
+/// 逐帧检测音高，再把相同音高的连续帧合并成音符
+fn transcribe(samples: &[f32], sample_rate: u32) -> Vec<ImportedNote> {
+    let rate = sample_rate as f64;
+    let frame_len = (FRAME_SECS * rate) as usize;
+    let hop = ((HOP_SECS * rate) as usize).max(1);
+    if samples.len() < frame_len {
+        return Vec::new();
+    }
+
+    let pitches: Vec<Option<u8>> = (0..=(samples.len() - frame_len) / hop)
+        .map(|i| detect_pitch(&samples[i * hop..i * hop + frame_len], rate))
+        .collect();
+    let pitches = median_filter(&pitches);
+
+    let min_frames = (MIN_NOTE_SECS / HOP_SECS).ceil() as usize;
+    let mut notes = Vec::new();
+    let mut start = 0;
+    for i in 1..=pitches.len() {
+        if i < pitches.len() && pitches[i] == pitches[start] {
+            continue;
+        }
+        if let Some(key) = pitches[start] {
+            if i - start >= min_frames {
+                let tick = (start as f64 * HOP_SECS * TICKS_PER_SEC) as u32;
+                let end = (i as f64 * HOP_SECS * TICKS_PER_SEC) as u32;
+                notes.push(ImportedNote {
+                    tick,
+                    length: end - tick,
+                    key,
+                    velocity: 100,
+                });
+            }
+        }
+        start = i;
+    }
+    notes
+}
+
+// YIN 基频检测，返回最接近的 MIDI 音高
+fn detect_pitch(frame: &[f32], rate: f64) -> Option<u8> {
+    let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+    if rms < SILENCE_RMS {
+        return None;
+    }
+
+    let min_tau = (rate / MAX_FREQ) as usize;
+    let max_tau = ((rate / MIN_FREQ) as usize).min(frame.len() / 2);
+    if min_tau < 2 || min_tau >= max_tau {
+        return None;
+    }
+    let window = frame.len() - max_tau;
+
+    // 差分函数和累积均值归一化
+    let mut diff = vec![0.0f32; max_tau + 1];
+    for (tau, d) in diff.iter_mut().enumerate().skip(1) {
+        *d = (0..window)
+            .map(|j| {
+                let delta = frame[j] - frame[j + tau];
+                delta * delta
+            })
+            .sum();
+    }
+    let mut running = 0.0;
+    for (tau, d) in diff.iter_mut().enumerate().skip(1) {
+        running += *d;
+        *d = if running > 0.0 {
+            *d * tau as f32 / running
+        } else {
+            1.0
+        };
+    }
+
+    let mut tau = min_tau;
+    while tau < max_tau {
+        if diff[tau] < YIN_THRESHOLD {
+            // 找到谷底
+            while tau + 1 < max_tau && diff[tau + 1] < diff[tau] {
+                tau += 1;
+            }
+            break;
+        }
+        tau += 1;
+    }
+    if tau >= max_tau {
+        return None;
+    }
+
+    // 抛物线插值得到更精确的周期
+    let (a, b, c) = (diff[tau - 1], diff[tau], diff[tau + 1]);
+    let denom = a - 2.0 * b + c;
+    let period = if denom.abs() > f32::EPSILON {
+        tau as f64 + 0.5 * (a - c) as f64 / denom as f64
+    } else {
+        tau as f64
+    };
+
+    let freq = rate / period;
+    let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+    (0.0..=127.0).contains(&midi).then(|| midi.round() as u8)
+}
+
+fn median_filter(pitches: &[Option<u8>]) -> Vec<Option<u8>> {
+    let half = MEDIAN_FRAMES / 2;
+    (0..pitches.len())
+        .map(|i| {
+            let lo = i.saturating_sub(half);
+            let hi = (i + half + 1).min(pitches.len());
+            let voiced: Vec<u8> = pitches[lo..hi].iter().flatten().copied().collect();
+            // 多数帧无声时当作静音
+            if voiced.len() * 2 <= hi - lo {
+                return None;
+            }
+            let mut sorted = voiced;
+            sorted.sort_unstable();
+            Some(sorted[sorted.len() / 2])
+        })
+        .collect()
+}
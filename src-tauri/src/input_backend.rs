@@ -0,0 +1,111 @@
+use crate::input_hook::INJECTION_TAG;
+use enigo::{Enigo, NewConError, Settings};
+use serde::Serialize;
+use std::fmt;
+
+/// 输入后端初始化失败的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputBackendErrorKind {
+    NoPermission, // 没有模拟输入的权限（如 macOS 辅助功能）
+    NoConnection, // 无法连接显示服务（如纯 Wayland 会话）
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputBackendError {
+    pub kind: InputBackendErrorKind,
+    pub message: String,
+    pub hint: String, // 给用户的解决建议
+}
+
+impl fmt::Display for InputBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to initialize input backend: {}. {}",
+            self.message, self.hint
+        )
+    }
+}
+
+impl From<NewConError> for InputBackendError {
+    fn from(e: NewConError) -> Self {
+        let kind = match e {
+            NewConError::NoPermission => InputBackendErrorKind::NoPermission,
+            NewConError::EstablishCon(_) => InputBackendErrorKind::NoConnection,
+            _ => InputBackendErrorKind::Other,
+        };
+        Self {
+            kind,
+            message: e.to_string(),
+            hint: remediation_hint(kind).to_string(),
+        }
+    }
+}
+
+impl InputBackendError {
+    pub fn other(message: &str) -> Self {
+        Self {
+            kind: InputBackendErrorKind::Other,
+            message: message.to_string(),
+            hint: remediation_hint(InputBackendErrorKind::Other).to_string(),
+        }
+    }
+}
+
+fn remediation_hint(kind: InputBackendErrorKind) -> &'static str {
+    match kind {
+        InputBackendErrorKind::NoPermission if cfg!(target_os = "macos") => {
+            "Grant Accessibility permission in System Settings > Privacy & Security > Accessibility, then restart the app"
+        }
+        InputBackendErrorKind::NoPermission => {
+            "Run the app with permission to simulate input (the game may be running as administrator)"
+        }
+        InputBackendErrorKind::NoConnection if cfg!(target_os = "linux") => {
+            "Make sure an X11 session is available; pure Wayland sessions need XWayland"
+        }
+        _ => "Restart the app; if the problem persists, report it together with the log",
+    }
+}
+
+/// 当前平台使用的输入后端名称
+pub fn backend_name() -> String {
+    if cfg!(target_os = "windows") {
+        "SendInput".to_string()
+    } else if cfg!(target_os = "macos") {
+        "CGEvent".to_string()
+    } else {
+        match std::env::var("XDG_SESSION_TYPE") {
+            Ok(session) if !session.is_empty() => format!("X11 ({} session)", session),
+            _ => "X11".to_string(),
+        }
+    }
+}
+
+/// 创建 Enigo 实例，失败时返回带解决建议的错误。发送的输入都带有 INJECTION_TAG 标记
+pub fn create() -> Result<Enigo, InputBackendError> {
+    let settings = Settings {
+        windows_dw_extra_info: Some(INJECTION_TAG),
+        event_source_user_data: Some(INJECTION_TAG as i64),
+        ..Settings::default()
+    };
+    Enigo::new(&settings).map_err(InputBackendError::from)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputBackendStatus {
+    pub available: bool,
+    pub backend: String,
+    pub error: Option<InputBackendError>,
+}
+
+/// 尝试初始化输入后端，检查能否模拟输入
+pub fn probe() -> InputBackendStatus {
+    let error = create().err();
+    InputBackendStatus {
+        available: error.is_none(),
+        backend: backend_name(),
+        error,
+    }
+}
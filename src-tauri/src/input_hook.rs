@@ -0,0 +1,151 @@
+use rdev::{listen, Event, EventType};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 写入模拟输入的标记值（Windows 的 dwExtraInfo、macOS 的 EVENT_SOURCE_USER_DATA），
+/// 用来区分程序自身发送的输入和用户的真实输入
+pub const INJECTION_TAG: usize = 0x4F47_4150;
+
+// 无法读取标记的平台（rdev 不提供该字段）按时间判断：
+// 注入结束后仍可能收到自身事件的回调，留出一点余量
+const INJECTION_GRACE: Duration = Duration::from_millis(50);
+
+// rdev::listen 每个进程只能启动一次，由这里统一管理
+// 钩子本身是进程级的，因此这里的状态不放入 AppState
+static HOOK_STARTED: AtomicBool = AtomicBool::new(false);
+// 正在注入的模拟输入数量（键盘和鼠标可能同时播放）
+static INJECTING: AtomicUsize = AtomicUsize::new(0);
+// 捕获模式下记录所有事件（包括程序自身的输入），用于自检
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+static CAPTURED: Mutex<Vec<(Instant, EventType)>> = Mutex::new(Vec::new());
+static LAST_USER_INPUT: Mutex<Option<Instant>> = Mutex::new(None);
+static LAST_INJECTION_END: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// 监听线程是否在运行（启动失败时会恢复为 false）
+pub fn is_running() -> bool {
+    HOOK_STARTED.load(Ordering::SeqCst)
+}
+
+/// 启动全局键鼠监听（重复调用无副作用）
+pub fn ensure_started() {
+    if HOOK_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    // Windows 上由读取标记的底层钩子判断用户输入
+    #[cfg(windows)]
+    thread::spawn(|| {
+        if let Err(e) = tagged::run() {
+            log::error!("Failed to install input hooks: {}", e);
+        }
+    });
+
+    thread::spawn(|| {
+        let callback = |event: Event| {
+            if CAPTURING.load(Ordering::SeqCst) {
+                CAPTURED
+                    .lock()
+                    .unwrap()
+                    .push((Instant::now(), event.event_type));
+            }
+            if cfg!(windows) || is_self_injected() {
+                return;
+            }
+            record_user_input();
+        };
+
+        if let Err(e) = listen(callback) {
+            log::error!("Failed to listen input events: {:?}", e);
+            HOOK_STARTED.store(false, Ordering::SeqCst);
+        }
+    });
+}
+
+fn record_user_input() {
+    *LAST_USER_INPUT.lock().unwrap() = Some(Instant::now());
+}
+
+// 注入期间及结束后的短时间内收到的事件视为程序自身的输入
+fn is_self_injected() -> bool {
+    if INJECTING.load(Ordering::SeqCst) > 0 {
+        return true;
+    }
+    LAST_INJECTION_END
+        .lock()
+        .unwrap()
+        .is_some_and(|t| t.elapsed() < INJECTION_GRACE)
+}
+
+/// 开始发送模拟输入
+pub fn begin_injection() {
+    INJECTING.fetch_add(1, Ordering::SeqCst);
+}
+
+/// 模拟输入发送完毕
+pub fn end_injection() {
+    *LAST_INJECTION_END.lock().unwrap() = Some(Instant::now());
+    INJECTING.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// 最近一次真实用户输入的时间
+pub fn last_user_input() -> Option<Instant> {
+    *LAST_USER_INPUT.lock().unwrap()
+}
+
+/// 开始捕获所有键鼠事件
+pub fn start_capture() {
+    CAPTURED.lock().unwrap().clear();
+    CAPTURING.store(true, Ordering::SeqCst);
+}
+
+/// 结束捕获并取出捕获到的事件
+pub fn stop_capture() -> Vec<(Instant, EventType)> {
+    CAPTURING.store(false, Ordering::SeqCst);
+    std::mem::take(&mut *CAPTURED.lock().unwrap())
+}
+
+// 底层键盘和鼠标钩子能读到 dwExtraInfo，带标记的事件是程序自身发送的
+#[cfg(windows)]
+mod tagged {
+    use super::{record_user_input, INJECTION_TAG};
+    use windows::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, SetWindowsHookExW, HC_ACTION, KBDLLHOOKSTRUCT, MSG,
+        MSLLHOOKSTRUCT, WH_KEYBOARD_LL, WH_MOUSE_LL,
+    };
+
+    unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code == HC_ACTION as i32 {
+            let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+            if info.dwExtraInfo != INJECTION_TAG {
+                record_user_input();
+            }
+        }
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+
+    unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code == HC_ACTION as i32 {
+            let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+            if info.dwExtraInfo != INJECTION_TAG {
+                record_user_input();
+            }
+        }
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+
+    /// 安装钩子并运行消息循环，钩子回调在本线程执行
+    pub fn run() -> Result<(), String> {
+        unsafe {
+            SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), None, 0)
+                .map_err(|e| e.to_string())?;
+            SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), None, 0).map_err(|e| e.to_string())?;
+            let mut message = MSG::default();
+            while GetMessageW(&mut message, None, 0, 0).as_bool() {}
+        }
+        Ok(())
+    }
+}
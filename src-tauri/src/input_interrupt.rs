@@ -0,0 +1,73 @@
+use crate::input_hook;
+use crate::profile::InputInterruptSettings;
+use crate::state::AppState;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptEventPayload {
+    pub idle_resume_secs: Option<f64>,
+}
+
+/// 用户操作键鼠时紧急暂停播放，可选在空闲 N 秒后从暂停处自动恢复
+pub fn start(app: AppHandle, settings: InputInterruptSettings) {
+    if !settings.enabled {
+        return;
+    }
+    if MONITOR_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    input_hook::ensure_started();
+
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+        // 只响应本次播放开始之后、且尚未处理过的输入
+        let mut handled_until = Instant::now();
+        let mut interrupted = false;
+        let payload = InterruptEventPayload {
+            idle_resume_secs: settings.idle_resume_secs,
+        };
+
+        while state.is_any_playing() {
+            thread::sleep(POLL_INTERVAL);
+
+            let last_input = input_hook::last_user_input();
+
+            if !interrupted {
+                if last_input.is_some_and(|t| t > handled_until) {
+                    state.pause_all();
+                    interrupted = true;
+                    let _ = app.emit("interrupt://paused", payload.clone());
+                }
+                continue;
+            }
+
+            // 用户已手动恢复
+            if !state.is_any_paused() {
+                interrupted = false;
+                handled_until = last_input.unwrap_or(handled_until);
+                continue;
+            }
+
+            if let (Some(secs), Some(t)) = (settings.idle_resume_secs, last_input) {
+                let idle = Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX);
+                if t.elapsed() >= idle {
+                    state.resume_all();
+                    interrupted = false;
+                    handled_until = t;
+                    let _ = app.emit("interrupt://resumed", payload.clone());
+                }
+            }
+        }
+
+        MONITOR_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
@@ -0,0 +1,255 @@
+use crate::input_backend::{self, InputBackendError};
+use enigo::{Direction, Enigo, Keyboard};
+use serde::Serialize;
+use std::fmt;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use uni_input::{
+    ClickTiming, DragWaypoint, KeyCombo, NativeKey, SmartKeyboard, SmoothMouse, TracePoint,
+};
+
+// 所有播放共用一个长期存在的 Enigo：由专用的输入线程持有，播放线程通过通道提交操作并等待结果，
+// 不再每次播放都重新创建（有启动延迟，偶尔会失败）。
+// 操作在输入线程中依次执行，键盘和鼠标同时播放时会互相等待，需要严格同步时用合并播放。
+
+type Job = Box<dyn FnOnce(&mut Enigo) + Send>;
+
+// Enigo 是进程级资源，和输入钩子一样不放入 AppState
+static SERVICE: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+
+const STOPPED: &str = "Input service stopped unexpectedly";
+
+/// 空跑时本该发送的输入
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum InputAction {
+    KeyPress {
+        key: String,
+    },
+    KeyRelease {
+        key: String,
+    },
+    Tap {
+        keys: String,
+    },
+    Text {
+        text: String,
+    },
+    Click {
+        x: i32,
+        y: i32,
+    },
+    Hover {
+        x: i32,
+        y: i32,
+    },
+    Drag {
+        x: i32,
+        y: i32,
+        waypoints: Vec<(i32, i32)>,
+    },
+}
+
+type DryRunSink = Arc<dyn Fn(InputAction) + Send + Sync>;
+
+#[derive(Clone)]
+enum Target {
+    Service(Sender<Job>),
+    DryRun(DryRunSink),
+}
+
+/// 输入线程的句柄，可以克隆给多个播放线程
+#[derive(Clone)]
+pub struct InputHandle {
+    target: Target,
+}
+
+impl fmt::Debug for InputHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::Service(_) => f.write_str("InputHandle"),
+            Target::DryRun(_) => f.write_str("InputHandle(dry run)"),
+        }
+    }
+}
+
+/// 取得输入线程句柄，第一次调用时启动线程并创建 Enigo；创建失败时下次调用会重试
+pub fn handle() -> Result<InputHandle, InputBackendError> {
+    let mut service = SERVICE.lock().unwrap();
+    if let Some(sender) = service.as_ref() {
+        return Ok(InputHandle {
+            target: Target::Service(sender.clone()),
+        });
+    }
+
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let (ready_sender, ready) = mpsc::channel();
+    thread::spawn(move || {
+        let mut enigo = match input_backend::create() {
+            Ok(enigo) => {
+                let _ = ready_sender.send(Ok(()));
+                enigo
+            }
+            Err(e) => {
+                let _ = ready_sender.send(Err(e));
+                return;
+            }
+        };
+        for job in receiver {
+            job(&mut enigo);
+        }
+    });
+    ready
+        .recv()
+        .map_err(|_| InputBackendError::other(STOPPED))??;
+
+    *service = Some(sender.clone());
+    Ok(InputHandle {
+        target: Target::Service(sender),
+    })
+}
+
+/// 放弃当前的输入线程（如卡在系统调用中），下次取句柄时重新创建；已取得的句柄仍指向旧线程
+pub fn reset() {
+    SERVICE.lock().unwrap().take();
+}
+
+/// 空跑用的句柄：不发送任何系统输入，每个操作交给 on_input
+pub fn dry_run(on_input: impl Fn(InputAction) + Send + Sync + 'static) -> InputHandle {
+    InputHandle {
+        target: Target::DryRun(Arc::new(on_input)),
+    }
+}
+
+impl InputHandle {
+    // 在输入线程中执行操作并等待结果。线程已退出时清除服务，下次取句柄会重新创建
+    fn run<R: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut Enigo) -> R + Send + 'static,
+    ) -> Result<R, String> {
+        let Target::Service(service) = &self.target else {
+            return Err("Input is disabled in dry run".to_string());
+        };
+        let (sender, result) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move |enigo| {
+            let _ = sender.send(job(enigo));
+        });
+        if service.send(job).is_err() {
+            SERVICE.lock().unwrap().take();
+            return Err(STOPPED.to_string());
+        }
+        result.recv().map_err(|_| {
+            SERVICE.lock().unwrap().take();
+            STOPPED.to_string()
+        })
+    }
+
+    // 空跑时记录操作并返回 true
+    fn dry_run(&self, action: impl FnOnce() -> InputAction) -> bool {
+        match &self.target {
+            Target::DryRun(sink) => {
+                sink(action());
+                true
+            }
+            Target::Service(_) => false,
+        }
+    }
+
+    /// 发送单个按键，name 为用户写的按键字符串，空跑时原样记录
+    pub fn native_key(
+        &self,
+        key: NativeKey,
+        name: &str,
+        direction: Direction,
+    ) -> Result<(), String> {
+        let recorded = self.dry_run(|| {
+            let key = name.to_string();
+            match direction {
+                Direction::Press => InputAction::KeyPress { key },
+                Direction::Release => InputAction::KeyRelease { key },
+                Direction::Click => InputAction::Tap { keys: key },
+            }
+        });
+        if recorded {
+            return Ok(());
+        }
+        self.run(move |enigo| enigo.native_key(key, direction))?
+    }
+
+    /// 短按组合键，name 为用户写的组合键字符串（如 "shift+a"），空跑时原样记录
+    pub fn tap_combo(&self, combo: &KeyCombo, name: &str) -> Result<(), String> {
+        let recorded = self.dry_run(|| InputAction::Tap {
+            keys: name.to_string(),
+        });
+        if recorded {
+            return Ok(());
+        }
+        let combo = combo.clone();
+        self.run(move |enigo| enigo.tap_combo(&combo))?
+    }
+
+    /// 输入一段文字（如游戏聊天），不经过按键映射
+    pub fn type_text(&self, text: &str) -> Result<(), String> {
+        if self.dry_run(|| InputAction::Text {
+            text: text.to_string(),
+        }) {
+            return Ok(());
+        }
+        let text = text.to_string();
+        self.run(move |enigo| enigo.text(&text).map_err(|e| e.to_string()))?
+    }
+
+    pub fn mouse_click_smooth(&self, x: i32, y: i32, timing: ClickTiming) -> Result<(), String> {
+        if self.dry_run(|| InputAction::Click { x, y }) {
+            return Ok(());
+        }
+        self.run(move |enigo| enigo.mouse_click_smooth(x, y, &timing))?
+    }
+
+    /// 移动到目标但不点击，有轨迹时沿轨迹移动
+    pub fn mouse_hover(
+        &self,
+        x: i32,
+        y: i32,
+        trace: Option<Arc<[TracePoint]>>,
+        timing: ClickTiming,
+    ) -> Result<(), String> {
+        if self.dry_run(|| InputAction::Hover { x, y }) {
+            return Ok(());
+        }
+        self.run(move |enigo| enigo.mouse_hover(x, y, trace.as_deref(), &timing))?
+    }
+
+    /// 从 (x, y) 按住左键拖动，依次经过途经点后松开
+    pub fn mouse_drag(
+        &self,
+        x: i32,
+        y: i32,
+        waypoints: Vec<DragWaypoint>,
+        timing: ClickTiming,
+    ) -> Result<(), String> {
+        if self.dry_run(|| InputAction::Drag {
+            x,
+            y,
+            waypoints: waypoints.iter().map(|w| (w.x, w.y)).collect(),
+        }) {
+            return Ok(());
+        }
+        self.run(move |enigo| enigo.mouse_drag(x, y, &waypoints, &timing))?
+    }
+
+    /// 沿录制的真人轨迹移动后点击
+    pub fn mouse_click_trace(
+        &self,
+        x: i32,
+        y: i32,
+        trace: Arc<[TracePoint]>,
+        timing: ClickTiming,
+    ) -> Result<(), String> {
+        if self.dry_run(|| InputAction::Click { x, y }) {
+            return Ok(());
+        }
+        self.run(move |enigo| enigo.mouse_click_trace(x, y, &trace, &timing))?
+    }
+}
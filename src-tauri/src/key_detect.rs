@@ -0,0 +1,131 @@
+use crate::midi_analyzer::{MidiEvent, BLACK_PCS, SHARP_NAMES};
+use serde::{Deserialize, Serialize};
+
+// 调性识别：优先使用文件中的调号，没有调号时按音级分布（以时值加权）与
+// Krumhansl-Schmuckler 调性轮廓的相关系数判断。
+
+// 大调和小调的调性轮廓，从主音开始
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMode {
+    Major,
+    Minor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Signature, // 文件中的调号
+    Histogram, // 按音级分布推断
+}
+
+/// 识别出的调性
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedKey {
+    pub tonic: u8, // 主音的音级，0 为 C
+    pub mode: KeyMode,
+    pub name: String, // 如 "G major"、"F# minor"
+    pub source: KeySource,
+}
+
+impl DetectedKey {
+    fn new(tonic: u8, mode: KeyMode, source: KeySource) -> Self {
+        let mode_name = match mode {
+            KeyMode::Major => "major",
+            KeyMode::Minor => "minor",
+        };
+        Self {
+            tonic,
+            mode,
+            name: format!(
+                "{} {}",
+                SHARP_NAMES[tonic as usize].to_uppercase(),
+                mode_name
+            ),
+            source,
+        }
+    }
+
+    /// 按调号（升号为正、降号为负的个数）确定调性
+    pub fn from_signature(sharps: i8, minor: bool) -> Self {
+        // 每个升号使主音上移纯五度
+        let major_tonic = (sharps as i32 * 7).rem_euclid(12) as u8;
+        if minor {
+            Self::new((major_tonic + 9) % 12, KeyMode::Minor, KeySource::Signature)
+        } else {
+            Self::new(major_tonic, KeyMode::Major, KeySource::Signature)
+        }
+    }
+
+    /// 按音级分布推断调性，没有音符时返回 None
+    pub fn from_notes(events: &[MidiEvent]) -> Option<Self> {
+        let histogram = pitch_class_histogram(events);
+        if histogram.iter().all(|&w| w == 0.0) {
+            return None;
+        }
+        let mut best: Option<(f64, u8, KeyMode)> = None;
+        for tonic in 0..12u8 {
+            for (mode, profile) in [
+                (KeyMode::Major, &MAJOR_PROFILE),
+                (KeyMode::Minor, &MINOR_PROFILE),
+            ] {
+                let rotated: Vec<f64> = (0..12)
+                    .map(|pc| histogram[(pc + tonic as usize) % 12])
+                    .collect();
+                let score = correlation(&rotated, profile);
+                if best.is_none_or(|(s, _, _)| score > s) {
+                    best = Some((score, tonic, mode));
+                }
+            }
+        }
+        best.map(|(_, tonic, mode)| Self::new(tonic, mode, KeySource::Histogram))
+    }
+}
+
+// 各音级按时值加权的分布
+fn pitch_class_histogram(events: &[MidiEvent]) -> [f64; 12] {
+    let mut histogram = [0.0; 12];
+    for event in events.iter().filter(|e| e.type_ == "note_on") {
+        histogram[(event.note % 12) as usize] += event.duration.max(0.0);
+    }
+    histogram
+}
+
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+/// 移调到黑键最少的半音数（-6 到 +5），黑键数相同时取移动较小的；没有音符时为 0
+pub fn fewest_black_keys_shift(events: &[MidiEvent]) -> i32 {
+    let mut counts = [0usize; 12];
+    for event in events.iter().filter(|e| e.type_ == "note_on") {
+        counts[(event.note % 12) as usize] += 1;
+    }
+    (-6..=5)
+        .min_by_key(|&shift: &i32| {
+            let black: usize = (0..12)
+                .filter(|&pc| BLACK_PCS.contains(&((pc + shift).rem_euclid(12) as u8)))
+                .map(|pc| counts[pc as usize])
+                .sum();
+            (black, shift.abs())
+        })
+        .unwrap_or(0)
+}
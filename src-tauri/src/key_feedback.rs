@@ -0,0 +1,132 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+// 按键可视化反馈：播放时把每次按下和松开通知前端的屏幕键盘，与节流的进度事件分开。
+// 变化先攒在缓冲区中，每帧合并为一个 "playback://keys" 事件；密集的乐曲一帧内变化太多时
+// 丢弃这一帧的变化，改为发送当前按住的全部按键，前端据此重新同步，IPC 不会被刷屏。
+
+// 约 30 帧每秒
+const FLUSH_INTERVAL: Duration = Duration::from_millis(33);
+// 一帧内最多发送的变化数
+const MAX_CHANGES: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyChange {
+    pub key: String, // 按键字符串，与播放事件的 key 相同
+    pub down: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct KeyFrame {
+    changes: Vec<KeyChange>,
+    // 变化被丢弃时为当前按住的全部按键，前端用它替换显示状态
+    #[serde(skip_serializing_if = "Option::is_none")]
+    held: Option<Vec<String>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    changes: Vec<KeyChange>,
+    overflowed: bool,
+    held: BTreeMap<String, usize>, // 按住的按键和按下次数
+}
+
+impl Pending {
+    fn push(&mut self, key: &str, down: bool) {
+        if self.overflowed {
+            return;
+        }
+        if self.changes.len() >= MAX_CHANGES {
+            self.overflowed = true;
+            self.changes.clear();
+            return;
+        }
+        self.changes.push(KeyChange {
+            key: key.to_string(),
+            down,
+        });
+    }
+
+    fn take_frame(&mut self) -> Option<KeyFrame> {
+        if std::mem::take(&mut self.overflowed) {
+            return Some(KeyFrame {
+                changes: Vec::new(),
+                held: Some(self.held.keys().cloned().collect()),
+            });
+        }
+        if self.changes.is_empty() {
+            return None;
+        }
+        Some(KeyFrame {
+            changes: std::mem::take(&mut self.changes),
+            held: None,
+        })
+    }
+}
+
+/// 反馈的发送端，可以克隆；所有克隆都被丢弃后发送线程发完剩余的变化并结束
+#[derive(Clone)]
+pub struct KeyFeedback {
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl fmt::Debug for KeyFeedback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyFeedback")
+    }
+}
+
+impl KeyFeedback {
+    /// 按下（已按住时再次按下也会通知，前端可以闪烁一下）
+    pub fn down(&self, key: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending.held.entry(key.to_string()).or_default() += 1;
+        pending.push(key, true);
+    }
+
+    /// 松开，按下几次就要松开几次才通知
+    pub fn up(&self, key: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(count) = pending.held.get_mut(key) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            pending.held.remove(key);
+            pending.push(key, false);
+        }
+    }
+
+    /// 松开所有按住的键
+    pub fn release_all(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        for key in std::mem::take(&mut pending.held).into_keys() {
+            pending.push(&key, false);
+        }
+    }
+}
+
+/// 开始一次播放的反馈，返回的发送端交给播放线程
+pub fn start(app: AppHandle) -> KeyFeedback {
+    let pending = Arc::new(Mutex::new(Pending::default()));
+    let feedback = KeyFeedback {
+        pending: Arc::clone(&pending),
+    };
+    thread::spawn(move || loop {
+        thread::sleep(FLUSH_INTERVAL);
+        let finished = Arc::strong_count(&pending) == 1;
+        let frame = pending.lock().unwrap().take_frame();
+        if let Some(frame) = frame {
+            let _ = app.emit("playback://keys", frame);
+        }
+        if finished {
+            break;
+        }
+    });
+    feedback
+}
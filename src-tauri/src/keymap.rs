@@ -0,0 +1,378 @@
+use crate::error::AppError;
+use crate::json_store;
+use crate::keypress_simulator::KeyEvent;
+use crate::midi_analyzer::{MidiEvent, BLACK_PCS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use uni_input::keyboard::resolve_key_combo;
+
+// MIDI 音符到按键的映射表。内置常见游戏的布局，用户可以另存自定义的映射表，
+// 播放时后端直接按映射表把音符转换为按键事件。
+
+const KEYMAP_FILE_NAME: &str = "keymaps.json";
+pub const KEYMAP_36: &str = "36-key";
+pub const KEYMAP_21: &str = "21-key";
+pub const KEYMAP_SKY: &str = "sky-15";
+pub const KEYMAP_VIRTUAL_PIANO: &str = "virtual-piano-61";
+// 音符没有时长时的默认按键时长（秒），和前端一致
+const DEFAULT_NOTE_SECS: f64 = 0.1;
+
+// 36 键布局：低、中、高音区各 12 个半音，半音用 shift（升）或 ctrl（降）
+const LAYOUT_36: [(u8, &str); 36] = [
+    (48, "z"),
+    (49, "shift+z"),
+    (50, "x"),
+    (51, "ctrl+c"),
+    (52, "c"),
+    (53, "v"),
+    (54, "shift+v"),
+    (55, "b"),
+    (56, "shift+b"),
+    (57, "n"),
+    (58, "ctrl+m"),
+    (59, "m"),
+    (60, "a"),
+    (61, "shift+a"),
+    (62, "s"),
+    (63, "ctrl+d"),
+    (64, "d"),
+    (65, "f"),
+    (66, "shift+f"),
+    (67, "g"),
+    (68, "shift+g"),
+    (69, "h"),
+    (70, "ctrl+j"),
+    (71, "j"),
+    (72, "q"),
+    (73, "shift+q"),
+    (74, "w"),
+    (75, "ctrl+e"),
+    (76, "e"),
+    (77, "r"),
+    (78, "shift+r"),
+    (79, "t"),
+    (80, "shift+t"),
+    (81, "y"),
+    (82, "ctrl+u"),
+    (83, "u"),
+];
+
+// Sky 光遇：两个八度加一个 C（C4-C6），只有白键，三行五键
+const LAYOUT_SKY: [&str; 15] = [
+    "y", "u", "i", "o", "p", "h", "j", "k", "l", ";", "n", "m", ",", ".", "/",
+];
+// Virtual Piano 布局（Roblox 钢琴等）：C2-C7 的白键依次为数字和字母，黑键为左边白键加 shift
+const VIRTUAL_PIANO_WHITE: &str = "1234567890qwertyuiopasdfghjklzxcvbnm";
+const VIRTUAL_PIANO_LOWEST: u8 = 36;
+
+/// 一张映射表，notes 的键是 MIDI 音符编号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keymap {
+    pub name: String,
+    pub notes: BTreeMap<u8, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeymapSummary {
+    pub name: String,
+    pub builtin: bool,
+    pub notes: usize,
+    pub lowest: Option<u8>,
+    pub highest: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidKey {
+    pub note: u8,
+    pub key: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedKey {
+    pub key: String,
+    pub notes: Vec<u8>,
+}
+
+/// 检查结果：无法解析的按键不能保存，多个音符共用一个按键只作提示
+#[derive(Debug, Clone, Serialize)]
+pub struct KeymapValidation {
+    pub valid: bool,
+    pub invalid_keys: Vec<InvalidKey>,
+    pub shared_keys: Vec<SharedKey>,
+}
+
+/// 按映射表转换后的按键序列，unmapped 为映射表里没有的音符数
+#[derive(Debug, Clone)]
+pub struct MappedEvents {
+    pub events: Vec<KeyEvent>,
+    pub unmapped: usize,
+}
+
+impl Keymap {
+    pub fn validate(&self) -> KeymapValidation {
+        let mut invalid_keys = Vec::new();
+        let mut by_key: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        for (&note, key) in &self.notes {
+            if note > 127 {
+                invalid_keys.push(InvalidKey {
+                    note,
+                    key: key.clone(),
+                    reason: "Note is outside the MIDI range".to_string(),
+                });
+                continue;
+            }
+            match resolve_key_combo(key) {
+                Ok(_) => by_key.entry(key.as_str()).or_default().push(note),
+                Err(e) => invalid_keys.push(InvalidKey {
+                    note,
+                    key: key.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        let shared_keys = by_key
+            .into_iter()
+            .filter(|(_, notes)| notes.len() > 1)
+            .map(|(key, notes)| SharedKey {
+                key: key.to_string(),
+                notes,
+            })
+            .collect();
+
+        KeymapValidation {
+            valid: invalid_keys.is_empty(),
+            invalid_keys,
+            shared_keys,
+        }
+    }
+
+    // 音符对应的按键；shift_black_keys 时黑键用 shift + 下方白键的按键
+    fn key_for(&self, note: u8, shift_black_keys: bool) -> Option<String> {
+        if shift_black_keys && BLACK_PCS.contains(&(note % 12)) {
+            return self
+                .notes
+                .get(&(note - 1))
+                .filter(|key| !key.is_empty() && !key.contains('+'))
+                .map(|key| format!("shift+{}", key));
+        }
+        self.notes.get(&note).filter(|key| !key.is_empty()).cloned()
+    }
+
+    /// 把 note_on 事件转换为按键事件，映射表里没有的音符跳过
+    pub fn map_events(&self, events: &[MidiEvent], shift_black_keys: bool) -> MappedEvents {
+        let mut mapped = Vec::with_capacity(events.len());
+        let mut unmapped = 0;
+        for event in events {
+            if event.type_ != "note_on" || event.velocity == 0 {
+                continue;
+            }
+            match self.key_for(event.note, shift_black_keys) {
+                Some(key) => mapped.push(KeyEvent {
+                    time: event.time,
+                    key,
+                    duration: if event.duration > 0.0 {
+                        event.duration
+                    } else {
+                        DEFAULT_NOTE_SECS
+                    },
+                    velocity: Some(event.velocity),
+                }),
+                None => unmapped += 1,
+            }
+        }
+        mapped.sort_by(|a, b| a.time.total_cmp(&b.time));
+        MappedEvents {
+            events: mapped,
+            unmapped,
+        }
+    }
+
+    fn summary(&self, builtin: bool) -> KeymapSummary {
+        KeymapSummary {
+            name: self.name.clone(),
+            builtin,
+            notes: self.notes.len(),
+            lowest: self.notes.keys().next().copied(),
+            highest: self.notes.keys().next_back().copied(),
+        }
+    }
+}
+
+fn builtin_keymaps() -> Vec<Keymap> {
+    let full: BTreeMap<u8, String> = LAYOUT_36
+        .iter()
+        .map(|&(note, key)| (note, key.to_string()))
+        .collect();
+    // 21 键只有白键，不用组合键
+    let white = full
+        .iter()
+        .filter(|(_, key)| !key.contains('+'))
+        .map(|(&note, key)| (note, key.clone()))
+        .collect();
+    vec![
+        Keymap {
+            name: KEYMAP_36.to_string(),
+            notes: full,
+        },
+        Keymap {
+            name: KEYMAP_21.to_string(),
+            notes: white,
+        },
+        Keymap {
+            name: KEYMAP_SKY.to_string(),
+            notes: white_keys(60, LAYOUT_SKY.iter().map(|key| key.to_string())),
+        },
+        Keymap {
+            name: KEYMAP_VIRTUAL_PIANO.to_string(),
+            notes: virtual_piano(),
+        },
+    ]
+}
+
+// 从 lowest 开始依次把白键分配给 keys
+fn white_keys(lowest: u8, keys: impl Iterator<Item = String>) -> BTreeMap<u8, String> {
+    (lowest..=127)
+        .filter(|note| !BLACK_PCS.contains(&(note % 12)))
+        .zip(keys)
+        .collect()
+}
+
+fn virtual_piano() -> BTreeMap<u8, String> {
+    let mut notes = white_keys(
+        VIRTUAL_PIANO_LOWEST,
+        VIRTUAL_PIANO_WHITE.chars().map(String::from),
+    );
+    let black: Vec<(u8, String)> = notes
+        .iter()
+        .filter(|(note, _)| BLACK_PCS.contains(&((*note + 1) % 12)))
+        .filter(|(note, _)| **note < VIRTUAL_PIANO_LOWEST + 60)
+        .map(|(note, key)| (note + 1, format!("shift+{}", key)))
+        .collect();
+    notes.extend(black);
+    notes
+}
+
+/// 映射表管理，自定义映射表保存在配置目录的 keymaps.json 中，内置映射表不能修改
+pub struct KeymapManager {
+    builtin: Vec<Keymap>,
+    custom: RwLock<Vec<Keymap>>,
+    path: PathBuf,
+}
+
+impl KeymapManager {
+    pub fn load(config_dir: PathBuf) -> Self {
+        let path = config_dir.join(KEYMAP_FILE_NAME);
+        let custom = match json_store::load::<Vec<Keymap>>(&path) {
+            Ok(keymaps) => keymaps.unwrap_or_default(),
+            Err(e) => {
+                log::error!("Failed to load keymaps: {}", e);
+                Vec::new()
+            }
+        };
+        Self {
+            builtin: builtin_keymaps(),
+            custom: RwLock::new(custom),
+            path,
+        }
+    }
+
+    pub fn list(&self) -> Vec<KeymapSummary> {
+        let custom = self.custom.read().unwrap();
+        self.builtin
+            .iter()
+            .map(|k| k.summary(true))
+            .chain(custom.iter().map(|k| k.summary(false)))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Keymap, AppError> {
+        self.builtin
+            .iter()
+            .find(|k| k.name == name)
+            .cloned()
+            .or_else(|| {
+                let custom = self.custom.read().unwrap();
+                custom.iter().find(|k| k.name == name).cloned()
+            })
+            .ok_or_else(|| AppError::not_found("Keymap not found").with_context(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    /// 新增或覆盖同名的自定义映射表，有无法解析的按键时拒绝保存
+    pub fn save(&self, keymap: Keymap) -> Result<KeymapValidation, AppError> {
+        if keymap.name.trim().is_empty() {
+            return Err(AppError::invalid("Keymap name cannot be empty"));
+        }
+        if self.builtin.iter().any(|k| k.name == keymap.name) {
+            return Err(
+                AppError::invalid("Built-in keymaps cannot be modified").with_context(&keymap.name)
+            );
+        }
+        let validation = keymap.validate();
+        if !validation.valid {
+            let keys: Vec<String> = validation
+                .invalid_keys
+                .iter()
+                .map(|k| format!("{}: \"{}\"", k.note, k.key))
+                .collect();
+            return Err(AppError::invalid("Invalid keys").with_context(keys.join(", ")));
+        }
+
+        let mut custom = self.custom.write().unwrap();
+        match custom.iter_mut().find(|k| k.name == keymap.name) {
+            Some(existing) => *existing = keymap,
+            None => custom.push(keymap),
+        }
+        json_store::save(&self.path, &*custom)?;
+        Ok(validation)
+    }
+
+    /// 全部自定义映射表，用于配置导出
+    pub fn snapshot(&self) -> Vec<Keymap> {
+        self.custom.read().unwrap().clone()
+    }
+
+    /// 导入自定义映射表：replace 时整体替换，否则同名覆盖、其余追加
+    pub fn import(&self, imported: Vec<Keymap>, replace: bool) -> Result<usize, AppError> {
+        for keymap in &imported {
+            if keymap.name.trim().is_empty() || self.builtin.iter().any(|k| k.name == keymap.name) {
+                return Err(AppError::invalid("Invalid keymap name").with_context(&keymap.name));
+            }
+        }
+        let count = imported.len();
+
+        let mut custom = self.custom.write().unwrap();
+        if replace {
+            *custom = imported;
+        } else {
+            for keymap in imported {
+                match custom.iter_mut().find(|k| k.name == keymap.name) {
+                    Some(existing) => *existing = keymap,
+                    None => custom.push(keymap),
+                }
+            }
+        }
+        json_store::save(&self.path, &*custom)?;
+        Ok(count)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        if self.builtin.iter().any(|k| k.name == name) {
+            return Err(AppError::invalid("Built-in keymaps cannot be deleted").with_context(name));
+        }
+        let mut custom = self.custom.write().unwrap();
+        let before = custom.len();
+        custom.retain(|k| k.name != name);
+        if custom.len() == before {
+            return Err(AppError::not_found("Keymap not found").with_context(name));
+        }
+        json_store::save(&self.path, &*custom)?;
+        Ok(())
+    }
+}
@@ -1,115 +1,865 @@
-use enigo::{Enigo, Settings};
+use crate::error::{AppError, ErrorCode};
+use crate::input_hook;
+use crate::input_service::{self, InputHandle};
+use crate::key_feedback::KeyFeedback;
+use crate::mouse_simulator::{self, MouseEvent, MouseStyle, MouseTrack};
+use crate::playback_report::{PlaybackReport, ReportBuilder};
+use crate::state::{PlaybackControl, StartWait, WaitOutcome};
+use crate::timeline_store::{Timeline, TimelineReader};
+use enigo::Direction;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
-use uni_input::SmartKeyboard;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uni_input::keyboard::resolve_key_combo;
+use uni_input::{KeyCombo, NativeKey};
 
+/// 前端传入的时间和时长都以秒为单位
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyEvent {
     pub time: f64,     // 时间（秒）
     pub key: String,   // 按键字符串，如 "a", "shift+a", "ctrl+c"
     pub duration: f64, // 按键持续时间（秒）
+    // MIDI 力度（0-127），只用于人性化抖动
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<u8>,
 }
 
-// 播放状态管理
-lazy_static::lazy_static! {
-    static ref PLAYBACK_HANDLE: Arc<Mutex<Option<thread::JoinHandle<()>>>> = Arc::new(Mutex::new(None));
-    static ref SHOULD_STOP: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+/// 按键方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyPlaybackMode {
+    #[default]
+    Tap, // 每个事件短按一下
+    Hold, // 在 time 按下，time + duration 松开
 }
 
-/// 开始播放按键序列
-pub fn start_playback(events: Vec<KeyEvent>) -> Result<(), String> {
-    // 检查是否已有播放在进行
-    {
-        let handle = PLAYBACK_HANDLE.lock().unwrap();
-        if handle.is_some() {
-            return Err("Playback already in progress".to_string());
+/// 播放用的事件：key 是编译后的按键编号
+#[derive(Debug, Clone, Copy)]
+pub struct SourceEvent {
+    pub time: f64,
+    pub duration: f64,
+    pub key: usize,
+}
+
+/// 按时间顺序提供事件的来源：内存中的事件列表或磁盘上的时间线
+pub trait EventSource: Send {
+    fn peek(&mut self) -> Option<SourceEvent>;
+    fn advance(&mut self);
+    /// 定位到第一个时间不早于 position 的事件
+    fn seek(&mut self, position: f64);
+    fn remaining(&self) -> usize;
+}
+
+// 内存中的事件列表，每个事件单独编译，按键编号即事件下标
+struct MemorySource {
+    events: Vec<KeyEvent>,
+    next: usize,
+}
+
+impl EventSource for MemorySource {
+    fn peek(&mut self) -> Option<SourceEvent> {
+        self.events.get(self.next).map(|e| SourceEvent {
+            time: e.time,
+            duration: e.duration,
+            key: self.next,
+        })
+    }
+
+    fn advance(&mut self) {
+        self.next = (self.next + 1).min(self.events.len());
+    }
+
+    fn seek(&mut self, position: f64) {
+        self.next = self.events.partition_point(|e| e.time < position);
+    }
+
+    fn remaining(&self) -> usize {
+        self.events.len() - self.next
+    }
+}
+
+// 按住模式下待松开的按键，按时间排成小顶堆
+struct PendingRelease {
+    time: f64,
+    key: usize,
+}
+
+impl PartialEq for PendingRelease {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PendingRelease {}
+
+impl PartialOrd for PendingRelease {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingRelease {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // 反转比较，BinaryHeap 顶部是最早的松开
+        other.time.total_cmp(&self.time)
+    }
+}
+
+/// 预编译的按键：开始播放前把每个按键字符串解析一次，物理按键分配槽位，
+/// 播放时只按槽位发送，不再解析字符串或查表
+struct CompiledKeys {
+    keys: Vec<NativeKey>,   // 槽位对应的物理按键
+    key_names: Vec<String>, // 槽位的按键字符串，取组合键中对应的部分（如 "shift+a" 中的 "shift"）
+    names: Vec<String>,     // 每个按键编号的按键字符串
+    combos: Vec<KeyCombo>,  // 每个按键编号的组合键
+    slots: Vec<Vec<usize>>, // 每个按键编号用到的槽位，修饰键在前、主键在最后
+}
+
+impl CompiledKeys {
+    // 一次性检查所有按键，错误信息列出全部无法解析的按键
+    fn compile<'k>(keys: impl ExactSizeIterator<Item = &'k str>) -> Result<Self, AppError> {
+        let mut compiled = Self {
+            keys: Vec::new(),
+            key_names: Vec::new(),
+            names: Vec::with_capacity(keys.len()),
+            combos: Vec::with_capacity(keys.len()),
+            slots: Vec::with_capacity(keys.len()),
+        };
+        let mut errors: Vec<String> = Vec::new();
+
+        for key in keys {
+            match resolve_key_combo(key) {
+                Ok(combo) => {
+                    // 组合键按 "+" 分隔，各部分依次对应修饰键和主键
+                    let slots = combo
+                        .modifiers
+                        .iter()
+                        .chain([&combo.main])
+                        .zip(key.split('+'))
+                        .map(|(native, name)| compiled.slot(*native, name))
+                        .collect();
+                    compiled.slots.push(slots);
+                    compiled.names.push(key.to_string());
+                    compiled.combos.push(combo);
+                }
+                Err(e) => {
+                    let message = format!("\"{}\" ({})", key, e);
+                    if !errors.contains(&message) {
+                        errors.push(message);
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::invalid("Invalid keys").with_context(errors.join(", ")));
         }
+        Ok(compiled)
     }
 
-    // 重置停止标志
-    {
-        let mut should_stop = SHOULD_STOP.lock().unwrap();
-        *should_stop = false;
+    fn slot(&mut self, key: NativeKey, name: &str) -> usize {
+        match self.keys.iter().position(|k| *k == key) {
+            Some(slot) => slot,
+            None => {
+                self.keys.push(key);
+                self.key_names.push(name.to_string());
+                self.keys.len() - 1
+            }
+        }
     }
 
-    // 在新线程中执行播放
-    let handle = thread::spawn(move || {
-        // 创建 Enigo 实例
-        let mut enigo = match Enigo::new(&Settings::default()) {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Failed to create Enigo instance: {:?}", e);
-                return;
+    // 发送一个槽位的按键
+    fn send(&self, input: &InputHandle, slot: usize, direction: Direction) -> Result<(), String> {
+        input.native_key(self.keys[slot], &self.key_names[slot], direction)
+    }
+
+    fn modifiers(&self, index: usize) -> &[usize] {
+        let slots = &self.slots[index];
+        &slots[..slots.len() - 1]
+    }
+}
+
+/// 播放用的键盘：按引用计数记录正在按住的物理按键（多个事件可能共用同一按键，如 shift）。
+/// 被丢弃时松开所有仍按住的键，因此停止、出错或播放线程 panic 都不会留下卡住的按键
+struct Keyboard {
+    input: InputHandle,
+    keys: CompiledKeys,
+    counts: Vec<usize>,
+    // 正在由 tap_combo 发送的按键编号，中途出错或 panic 时需要补发松开
+    tapping: Option<usize>,
+    // 通知前端屏幕键盘的按下和松开
+    feedback: Option<KeyFeedback>,
+}
+
+impl Keyboard {
+    fn new(input: InputHandle, keys: CompiledKeys, feedback: Option<KeyFeedback>) -> Self {
+        Self {
+            counts: vec![0; keys.keys.len()],
+            input,
+            keys,
+            tapping: None,
+            feedback,
+        }
+    }
+
+    fn notify(&self, key: usize, down: bool) {
+        if let Some(feedback) = &self.feedback {
+            let name = &self.keys.names[key];
+            if down {
+                feedback.down(name);
+            } else {
+                feedback.up(name);
+            }
+        }
+    }
+
+    fn press(&mut self, key: usize) -> Result<(), String> {
+        self.notify(key, true);
+        let slots = &self.keys.slots[key];
+        for (i, &slot) in slots.iter().enumerate() {
+            self.counts[slot] += 1;
+            if self.counts[slot] == 1 {
+                self.keys.send(&self.input, slot, Direction::Press)?;
+            } else if i == slots.len() - 1 {
+                // 同一个键还按着时先松开再按下，保证游戏能收到新的一次按键
+                self.keys.send(&self.input, slot, Direction::Release)?;
+                self.keys.send(&self.input, slot, Direction::Press)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, key: usize) -> Result<(), String> {
+        self.notify(key, false);
+        for &slot in self.keys.slots[key].iter().rev() {
+            if self.counts[slot] == 0 {
+                continue;
+            }
+            self.counts[slot] -= 1;
+            if self.counts[slot] == 0 {
+                self.keys.send(&self.input, slot, Direction::Release)?;
             }
+        }
+        Ok(())
+    }
+
+    /// 短按一个组合键，失败时松开该组合键的所有按键
+    fn tap(&mut self, key: usize) -> Result<(), String> {
+        self.tapping = Some(key);
+        self.notify(key, true);
+        let result = self
+            .input
+            .tap_combo(&self.keys.combos[key], &self.keys.names[key]);
+        if result.is_err() {
+            self.release_tapping();
+        }
+        self.notify(key, false);
+        self.tapping = None;
+        result
+    }
+
+    // 修饰键只按一次，所有主键一起按下再一起松开
+    fn press_together(&mut self, batch: &[usize]) -> Result<(), String> {
+        let mut result = Ok(());
+        for &key in batch {
+            result = result.and(self.press(key));
+        }
+        std::thread::sleep(TAP_HOLD);
+        for &key in batch.iter().rev() {
+            result = result.and(self.release(key));
+        }
+        result
+    }
+
+    // tap_combo 不记录按下状态，中断时把组合键里没被其他事件按住的键都松开
+    fn release_tapping(&mut self) {
+        let Some(key) = self.tapping.take() else {
+            return;
         };
+        for &slot in self.keys.slots[key].iter().rev() {
+            if self.counts[slot] == 0 {
+                self.release_slot(slot);
+            }
+        }
+    }
 
-        let start_time = std::time::Instant::now();
+    fn release_slot(&self, slot: usize) {
+        if let Err(e) = self.keys.send(&self.input, slot, Direction::Release) {
+            log::warn!("Failed to release key {}: {}", self.keys.key_names[slot], e);
+        }
+    }
 
-        for event in events {
-            // 检查是否需要停止
-            {
-                let should_stop = SHOULD_STOP.lock().unwrap();
-                if *should_stop {
-                    break;
+    /// 松开所有仍按住的键
+    fn release_all(&mut self) {
+        if let Some(feedback) = &self.feedback {
+            feedback.release_all();
+        }
+        for slot in 0..self.counts.len() {
+            if self.counts[slot] > 0 {
+                self.counts[slot] = 0;
+                self.release_slot(slot);
+            }
+        }
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        input_hook::begin_injection();
+        self.release_tapping();
+        self.release_all();
+        input_hook::end_injection();
+    }
+}
+
+// 按下时间相差在此范围内的按键视为同一个和弦
+const CHORD_WINDOW_SECS: f64 = 0.005;
+// 短按时按住的时长
+const TAP_HOLD: Duration = Duration::from_millis(20);
+
+// 修饰键不同的按键不能同时按下，按修饰键分批
+fn modifier_batches(keys: &CompiledKeys, chord: &[SourceEvent]) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    for event in chord {
+        let modifiers = keys.modifiers(event.key);
+        match batches
+            .iter_mut()
+            .find(|batch| keys.modifiers(batch[0]) == modifiers)
+        {
+            Some(batch) => batch.push(event.key),
+            None => batches.push(vec![event.key]),
+        }
+    }
+    batches
+}
+
+/// A–B 循环区间（秒），区间内的事件反复播放直到停止
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// 人性化抖动：每个事件的时间和按住时长随机偏移，让演奏不那么机械
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Humanize {
+    pub timing_ms: f64, // 按下时间偏移上限，±timing_ms
+    pub hold_ms: f64,   // 按住时长偏移上限，±hold_ms
+    // 按力度缩放：弱音偏移更大（最多 1.5 倍），强音更稳（最少 0.5 倍）
+    pub velocity_scaled: bool,
+}
+
+/// 按键播放选项
+#[derive(Debug, Clone, Default)]
+pub struct KeyPlaybackOptions {
+    pub mode: KeyPlaybackMode,
+    pub loop_region: Option<LoopRegion>,
+    pub humanize: Option<Humanize>,
+    // 空跑：照常调度，但通过该句柄记录操作而不发送系统输入
+    pub dry_run: Option<InputHandle>,
+    // 同步播放的鼠标事件的移动和点击方式
+    pub mouse_style: MouseStyle,
+    // 把按下和松开通知前端的屏幕键盘
+    pub key_feedback: Option<KeyFeedback>,
+    // 事件需要修正时拒绝播放，而不是自动修正
+    pub strict_events: bool,
+}
+
+// 播放线程持有的状态，一次播放可能包含多遍（循环播放）
+struct Session<'a> {
+    control: &'a PlaybackControl,
+    keyboard: Keyboard,
+    // 同步播放时与按键共用时钟的鼠标事件，只播按键时为空
+    mouse: MouseTrack,
+    report: ReportBuilder,
+    start_time: Instant,
+}
+
+impl Session<'_> {
+    // 跳转到 position，按键和鼠标事件一起定位
+    fn seek(&mut self, source: &mut dyn EventSource, position: f64) {
+        let before = source.remaining() + self.mouse.remaining();
+        source.seek(position);
+        self.mouse.seek(position);
+        let remaining = source.remaining() + self.mouse.remaining();
+        self.report.requeue(remaining.saturating_sub(before));
+        self.control.set_remaining(remaining);
+    }
+
+    // 下一个鼠标事件是否不晚于下一个按键动作，同一时刻先点击鼠标
+    fn mouse_due(&self, next_key: Option<f64>) -> bool {
+        match (self.mouse.peek_time(), next_key) {
+            (Some(mouse), Some(key)) => mouse <= key,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    fn fire_mouse(&mut self) {
+        self.mouse.fire(
+            &self.keyboard.input,
+            self.control,
+            self.start_time,
+            &mut self.report,
+        );
+        self.control.advance(1);
+    }
+
+    // 从 from 位置播放到末尾，返回 false 表示被停止
+    fn tap_pass(&mut self, source: &mut dyn EventSource, from: f64) -> bool {
+        let control = self.control;
+        let mut chord = Vec::new();
+        source.seek(from);
+        self.mouse.seek(from);
+        loop {
+            let next = source.peek();
+            let mouse_first = self.mouse_due(next.map(|e| e.time));
+            let time = match (mouse_first, next) {
+                (true, _) => self.mouse.peek_time().unwrap_or_default(),
+                (false, Some(event)) => event.time,
+                (false, None) => break,
+            };
+
+            // 等待到事件时间（期间可暂停、停止或跳转）
+            match control.wait_until(Duration::from_secs_f64(time), &mut self.start_time) {
+                WaitOutcome::Ready => {}
+                WaitOutcome::Seek(position) => {
+                    self.seek(source, position);
+                    continue;
                 }
+                WaitOutcome::Stop => return false,
             }
+            if mouse_first {
+                self.fire_mouse();
+                continue;
+            }
+            let Some(first) = next else {
+                break;
+            };
 
-            // 等待到事件时间
-            let target_time = Duration::from_secs_f64(event.time);
-            let elapsed = start_time.elapsed();
+            // 按下时间几乎相同的连续事件作为和弦一起按
+            chord.clear();
+            while let Some(event) = source.peek() {
+                if event.time - first.time > CHORD_WINDOW_SECS {
+                    break;
+                }
+                chord.push(event);
+                source.advance();
+            }
 
-            if target_time > elapsed {
-                let wait_time = target_time - elapsed;
-                thread::sleep(wait_time);
+            input_hook::begin_injection();
+            let fired_at = self.control.song_time(self.start_time);
+            for batch in modifier_batches(&self.keyboard.keys, &chord) {
+                let result = if batch.len() == 1 {
+                    // 模拟按键 (调用 uni-input 的 SmartKeyboard trait)
+                    self.keyboard.tap(batch[0])
+                } else {
+                    self.keyboard.press_together(&batch)
+                };
+                if let Err(e) = &result {
+                    log::warn!("Failed to simulate keypress: {}", e);
+                }
+                for event in chord.iter().filter(|e| batch.contains(&e.key)) {
+                    self.report.record(event.time, fired_at, result.is_ok());
+                }
+                control.advance(batch.len());
             }
+            input_hook::end_injection();
+        }
+        true
+    }
 
-            // 再次检查是否需要停止
-            {
-                let should_stop = SHOULD_STOP.lock().unwrap();
-                if *should_stop {
+    fn hold_pass(&mut self, source: &mut dyn EventSource, from: f64) -> bool {
+        let control = self.control;
+        // 只记录已按下事件的松开，按下时间早于起始或跳转位置的事件不再处理
+        let mut releases: BinaryHeap<PendingRelease> = BinaryHeap::new();
+        let mut completed = true;
+
+        source.seek(from);
+        self.mouse.seek(from);
+        loop {
+            // 同一时刻先松开再按下
+            let press = source.peek();
+            let release_first = match (&press, releases.peek()) {
+                (Some(p), Some(r)) => r.time <= p.time,
+                (_, release) => release.is_some(),
+            };
+            let key_time = if release_first {
+                releases.peek().map(|r| r.time)
+            } else {
+                press.map(|p| p.time)
+            };
+            let mouse_first = self.mouse_due(key_time);
+            let time = match (mouse_first, key_time) {
+                (true, _) => self.mouse.peek_time().unwrap_or_default(),
+                (false, Some(time)) => time,
+                (false, None) => break,
+            };
+
+            match control.wait_until(Duration::from_secs_f64(time), &mut self.start_time) {
+                WaitOutcome::Ready => {}
+                WaitOutcome::Seek(position) => {
+                    input_hook::begin_injection();
+                    self.keyboard.release_all();
+                    input_hook::end_injection();
+                    releases.clear();
+                    self.seek(source, position);
+                    continue;
+                }
+                WaitOutcome::Stop => {
+                    completed = false;
                     break;
                 }
             }
+            if mouse_first {
+                self.fire_mouse();
+                continue;
+            }
 
-            // 模拟按键 (调用 uni-input 的 SmartKeyboard trait)
-            if let Err(e) = enigo.simulate_keypress_smart(&event.key) {
-                eprintln!("Failed to simulate keypress: {}", e);
+            input_hook::begin_injection();
+            let fired_at = self.control.song_time(self.start_time);
+            if release_first {
+                let release = releases.pop().unwrap();
+                if let Err(e) = self.keyboard.release(release.key) {
+                    log::warn!("Failed to simulate key hold: {}", e);
+                }
+            } else if let Some(event) = press {
+                source.advance();
+                let result = self.keyboard.press(event.key);
+                if let Err(e) = &result {
+                    log::warn!("Failed to simulate key hold: {}", e);
+                }
+                releases.push(PendingRelease {
+                    time: event.time + event.duration.max(0.0),
+                    key: event.key,
+                });
+                self.report.record(event.time, fired_at, result.is_ok());
+                control.advance(1);
             }
+            input_hook::end_injection();
         }
 
-        // 播放完成，清理句柄
-        let mut handle = PLAYBACK_HANDLE.lock().unwrap();
-        *handle = None;
-    });
+        // 每遍结束（包括循环边界）都松开所有按键
+        self.keyboard.release_all();
+        completed
+    }
+}
+
+// 错误信息中最多列出的事件数
+const MAX_LISTED_EVENTS: usize = 5;
+/// 事件时间、时长、循环区间和倒计时的上限（秒），超出时换算成 Duration 会溢出
+pub const MAX_EVENT_SECS: f64 = 86_400.0;
+
+fn in_range(secs: f64) -> bool {
+    secs.is_finite() && secs.abs() <= MAX_EVENT_SECS
+}
+
+/// 开始播放前对事件做的修正
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventFixes {
+    pub negative_times: usize,     // 时间为负，改为 0
+    pub negative_durations: usize, // 时长为负，改为 0
+    pub reordered: bool,           // 没有按时间排序，已重新排序
+}
 
-    // 保存线程句柄
-    {
-        let mut playback_handle = PLAYBACK_HANDLE.lock().unwrap();
-        *playback_handle = Some(handle);
+impl EventFixes {
+    pub fn is_empty(&self) -> bool {
+        self.negative_times == 0 && self.negative_durations == 0 && !self.reordered
     }
 
-    Ok(())
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.negative_times > 0 {
+            parts.push(format!("{} negative times", self.negative_times));
+        }
+        if self.negative_durations > 0 {
+            parts.push(format!("{} negative durations", self.negative_durations));
+        }
+        if self.reordered {
+            parts.push("events out of order".to_string());
+        }
+        parts.join(", ")
+    }
 }
 
-/// 停止播放
-pub fn stop_playback() -> Result<(), String> {
-    // 设置停止标志
-    {
-        let mut should_stop = SHOULD_STOP.lock().unwrap();
-        *should_stop = true;
+/// 检查并规整播放事件：时间或时长不是有限数或超出 MAX_EVENT_SECS 时返回 InvalidEvents 错误；
+/// 负的时间和时长改为 0，乱序时按时间稳定排序。strict 为 true 时需要修正也返回错误
+pub fn normalize_events(events: &mut [KeyEvent], strict: bool) -> Result<EventFixes, AppError> {
+    let broken: Vec<String> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !in_range(e.time) || !in_range(e.duration))
+        .map(|(i, e)| format!("#{} (time {}, duration {})", i, e.time, e.duration))
+        .collect();
+    if !broken.is_empty() {
+        let mut context = broken[..broken.len().min(MAX_LISTED_EVENTS)].join(", ");
+        if broken.len() > MAX_LISTED_EVENTS {
+            context.push_str(&format!(" and {} more", broken.len() - MAX_LISTED_EVENTS));
+        }
+        return Err(AppError::new(
+            ErrorCode::InvalidEvents,
+            "Events have non-finite or out-of-range times or durations",
+        )
+        .with_context(context));
     }
 
-    // 等待线程结束（最多等待1秒）
-    let handle = {
-        let mut playback_handle = PLAYBACK_HANDLE.lock().unwrap();
-        playback_handle.take()
+    let fixes = EventFixes {
+        negative_times: events.iter().filter(|e| e.time < 0.0).count(),
+        negative_durations: events.iter().filter(|e| e.duration < 0.0).count(),
+        reordered: events
+            .windows(2)
+            .any(|pair| pair[1].time.max(0.0) < pair[0].time.max(0.0)),
     };
+    if fixes.is_empty() {
+        return Ok(fixes);
+    }
+    if strict {
+        return Err(
+            AppError::new(ErrorCode::InvalidEvents, "Events need normalization")
+                .with_context(fixes.describe()),
+        );
+    }
+    for event in events.iter_mut() {
+        event.time = event.time.max(0.0);
+        event.duration = event.duration.max(0.0);
+    }
+    if fixes.reordered {
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+    Ok(fixes)
+}
+
+/// 最后一个按键松开的时间，短按至少按住 TAP_HOLD
+pub fn song_end(events: &[KeyEvent]) -> f64 {
+    events
+        .iter()
+        .map(|e| e.time + e.duration.max(TAP_HOLD.as_secs_f64()))
+        .fold(0.0, f64::max)
+}
+
+// 给每个事件加上随机偏移，偏移后重新按时间排序
+fn humanize(events: &mut [KeyEvent], settings: Humanize) {
+    let timing = settings.timing_ms.max(0.0) / 1000.0;
+    let hold = settings.hold_ms.max(0.0) / 1000.0;
+    if timing == 0.0 && hold == 0.0 {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    for event in events.iter_mut() {
+        let scale = match event.velocity {
+            Some(velocity) if settings.velocity_scaled => 1.5 - velocity.min(127) as f64 / 127.0,
+            _ => 1.0,
+        };
+        event.time = (event.time + rng.gen_range(-timing..=timing) * scale).max(0.0);
+        if event.duration > 0.0 {
+            event.duration = (event.duration + rng.gen_range(-hold..=hold) * scale).max(0.0);
+        }
+    }
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
+
+fn validate_region(region: LoopRegion) -> Result<(), AppError> {
+    if region.start >= 0.0 && region.end > region.start && region.end <= MAX_EVENT_SECS {
+        Ok(())
+    } else {
+        Err(AppError::invalid(format!(
+            "Invalid loop region: {} - {}",
+            region.start, region.end
+        )))
+    }
+}
+
+// 只保留循环区间内按下的事件，按住时长截到区间结束
+fn clip_to_region(events: &mut Vec<KeyEvent>, region: LoopRegion) {
+    events.retain(|e| e.time >= region.start && e.time < region.end);
+    for event in events.iter_mut() {
+        event.duration = event.duration.min(region.end - event.time);
+    }
+}
+
+/// 开始播放按键序列
+pub fn start_playback<F>(
+    control: &Arc<PlaybackControl>,
+    mut events: Vec<KeyEvent>,
+    options: KeyPlaybackOptions,
+    wait: StartWait,
+    on_finish: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(PlaybackReport) + Send + 'static,
+{
+    if let Some(region) = options.loop_region {
+        validate_region(region)?;
+        clip_to_region(&mut events, region);
+        if events.is_empty() {
+            return Err(AppError::invalid("No events in loop region"));
+        }
+    }
+
+    if let Some(settings) = options.humanize {
+        humanize(&mut events, settings);
+    }
 
-    if let Some(handle) = handle {
-        let _ = handle.join();
+    // 在启动线程前解析所有按键，失败时直接返回错误
+    let keys = CompiledKeys::compile(events.iter().map(|e| e.key.as_str()))?;
+    let source = MemorySource { events, next: 0 };
+    let mouse = MouseTrack::new(Vec::new());
+    run(
+        control,
+        "keyboard",
+        keys,
+        Box::new(source),
+        mouse,
+        options,
+        wait,
+        on_finish,
+    )
+}
+
+/// 在同一个线程、同一个时钟下播放按键和鼠标事件，两者不会互相漂移
+pub fn start_combined_playback<F>(
+    control: &Arc<PlaybackControl>,
+    mut events: Vec<KeyEvent>,
+    mut mouse_events: Vec<MouseEvent>,
+    options: KeyPlaybackOptions,
+    wait: StartWait,
+    on_finish: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(PlaybackReport) + Send + 'static,
+{
+    mouse_simulator::check_events(&mouse_events)?;
+    if let Some(region) = options.loop_region {
+        validate_region(region)?;
+        clip_to_region(&mut events, region);
+        mouse_events.retain(|e| e.time >= region.start && e.time < region.end);
+    }
+    if events.is_empty() && mouse_events.is_empty() {
+        return Err(AppError::invalid("No events to play"));
     }
+    if let Some(settings) = options.humanize {
+        humanize(&mut events, settings);
+    }
+
+    let keys = CompiledKeys::compile(events.iter().map(|e| e.key.as_str()))?;
+    let source = MemorySource { events, next: 0 };
+    let mouse = MouseTrack::new(mouse_events).with_style(options.mouse_style.clone());
+    run(
+        control,
+        "combined",
+        keys,
+        Box::new(source),
+        mouse,
+        options,
+        wait,
+        on_finish,
+    )
+}
+
+/// 流式播放磁盘上的时间线，不支持循环区间
+pub fn start_timeline_playback<F>(
+    control: &Arc<PlaybackControl>,
+    timeline: &Timeline,
+    mode: KeyPlaybackMode,
+    wait: StartWait,
+    on_finish: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(PlaybackReport) + Send + 'static,
+{
+    if timeline.events == 0 {
+        return Err(AppError::invalid("Timeline is empty"));
+    }
+    let keys = CompiledKeys::compile(timeline.keys.iter().map(String::as_str))?;
+    let source = TimelineReader::open(timeline)?;
+    let options = KeyPlaybackOptions {
+        mode,
+        ..Default::default()
+    };
+    let mouse = MouseTrack::new(Vec::new());
+    run(
+        control,
+        "keyboard",
+        keys,
+        Box::new(source),
+        mouse,
+        options,
+        wait,
+        on_finish,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run<F>(
+    control: &Arc<PlaybackControl>,
+    kind: &'static str,
+    keys: CompiledKeys,
+    mut source: Box<dyn EventSource>,
+    mouse: MouseTrack,
+    options: KeyPlaybackOptions,
+    wait: StartWait,
+    on_finish: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(PlaybackReport) + Send + 'static,
+{
+    let input = match options.dry_run.clone() {
+        Some(input) => input,
+        None => input_service::handle()?,
+    };
+    let keyboard = Keyboard::new(input, keys, options.key_feedback.clone());
+
+    control.start(wait, move |control| {
+        let total = source.remaining() + mouse.remaining();
+        let mut session = Session {
+            control,
+            keyboard,
+            mouse,
+            report: ReportBuilder::new(kind, total),
+            start_time: control.clock_start(total),
+        };
+
+        let mut from = 0.0;
+        if let Some(region) = options.loop_region {
+            control.reanchor(&mut session.start_time, region.start);
+            from = region.start;
+        }
+
+        let completed = loop {
+            let finished = match options.mode {
+                KeyPlaybackMode::Tap => session.tap_pass(source.as_mut(), from),
+                KeyPlaybackMode::Hold => session.hold_pass(source.as_mut(), from),
+            };
+            if !finished {
+                break false;
+            }
+            let Some(region) = options.loop_region else {
+                break true;
+            };
+
+            // 等到区间结束再回到起点
+            let end = Duration::from_secs_f64(region.end);
+            match control.wait_until(end, &mut session.start_time) {
+                WaitOutcome::Ready => {
+                    control.reanchor(&mut session.start_time, region.start);
+                    from = region.start;
+                }
+                WaitOutcome::Seek(position) => from = position,
+                WaitOutcome::Stop => break false,
+            }
+            session.seek(source.as_mut(), from);
+        };
 
-    Ok(())
+        // 先松开所有按键再报告结束，句柄由 PlaybackControl 清理
+        let Session {
+            keyboard, report, ..
+        } = session;
+        drop(keyboard);
+        on_finish(report.finish(completed));
+    })
 }
@@ -1,97 +1,1671 @@
+mod accompaniment;
+mod after_playback;
+mod arrange;
+mod chord;
+mod config_bundle;
+mod cpu_usage;
+mod diagnostics;
+mod emergency_stop;
+mod error;
+mod focus_guard;
+mod frame_sync;
+mod game_preset;
+mod ghosting;
+mod gpx;
+mod guitar_pro;
+mod hum;
+mod input_backend;
+mod input_hook;
+mod input_interrupt;
+mod input_service;
+mod json_store;
+mod key_detect;
+mod key_feedback;
+mod keymap;
 mod keypress_simulator;
+mod library;
+mod lilypond;
+mod link_sync;
+mod logging;
+mod lyrics;
+mod media_controls;
 mod midi_analyzer;
+mod midi_clock;
 mod mouse_simulator;
+mod mouse_traces;
+mod musicxml;
+mod omr;
+mod playback_report;
+mod playlist;
+mod profile;
+mod score_file;
+mod score_import;
+mod self_test;
+mod session_stats;
+mod sleep_inhibit;
+mod start_trigger;
+mod state;
+mod target_watcher;
+mod thread_priority;
+mod timeline_store;
+mod timer_resolution;
+mod url_import;
+mod warning;
+mod xml_tree;
 
-use std::sync::Mutex;
-use uni_window::WindowInfo;
+use error::{AppError, ErrorCode};
+use profile::{ActivationMode, ActivationSettings, GameProfile};
+use state::AppState;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+use uni_window::{ChildWindowInfo, DisplayMode, Rect, WindowInfo};
+use warning::Warning;
 
-// Define Global Locked Window State
-lazy_static::lazy_static! {
-    static ref LOCKED_WINDOW: Mutex<Option<WindowInfo>> = Mutex::new(None);
+#[tauri::command]
+fn get_windows() -> Result<Vec<WindowInfo>, AppError> {
+    uni_window::enumerate_windows().map_err(AppError::window)
+}
+
+#[tauri::command]
+fn get_window_at_point(x: i32, y: i32) -> Result<Option<WindowInfo>, AppError> {
+    uni_window::window_at_point(x, y).map_err(AppError::window)
+}
+
+/// 点击游戏窗口来选择要锁定的窗口
+#[tauri::command]
+async fn pick_window() -> Result<Option<WindowInfo>, AppError> {
+    let (x, y) = mouse_simulator::pick_coordinate().await?;
+    get_window_at_point(x, y)
+}
+
+/// 查询窗口显示模式，未指定窗口时使用锁定的窗口
+#[tauri::command]
+fn get_window_display_mode(
+    state: State<'_, AppState>,
+    window_id: Option<u32>,
+) -> Result<DisplayMode, AppError> {
+    let window = match window_id {
+        Some(id) => uni_window::find_window(id)
+            .map_err(AppError::window)?
+            .ok_or_else(|| AppError::not_found("Window not found").with_context(id.to_string()))?,
+        None => refresh_locked_window(&state)?,
+    };
+    uni_window::display_mode(&window).map_err(AppError::window)
+}
+
+/// 设置目标窗口置顶，未指定窗口时使用锁定的窗口
+#[tauri::command]
+fn set_window_topmost(
+    state: State<'_, AppState>,
+    window_id: Option<u32>,
+    topmost: bool,
+) -> Result<(), AppError> {
+    let id = match window_id {
+        Some(id) => id,
+        None => refresh_locked_window(&state)?.id,
+    };
+    uni_window::set_window_topmost(id, topmost).map_err(AppError::window)
 }
 
+/// 设置目标窗口不透明度，未指定窗口时使用锁定的窗口
 #[tauri::command]
-fn get_windows() -> Result<Vec<WindowInfo>, String> {
-    uni_window::enumerate_windows().map_err(|e| e.to_string())
+fn set_window_opacity(
+    state: State<'_, AppState>,
+    window_id: Option<u32>,
+    opacity: f32,
+) -> Result<(), AppError> {
+    let id = match window_id {
+        Some(id) => id,
+        None => refresh_locked_window(&state)?.id,
+    };
+    uni_window::set_window_opacity(id, opacity).map_err(AppError::window)
 }
 
 #[tauri::command]
-fn lock_window(window: WindowInfo) {
-    let mut locked = LOCKED_WINDOW.lock().unwrap();
-    *locked = Some(window);
+fn lock_window(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    window: WindowInfo,
+) -> Result<WindowInfo, AppError> {
+    // 前端传入的信息可能已过期，以重新枚举得到的窗口为准
+    let current = uni_window::find_window(window.id)
+        .map_err(AppError::window)?
+        .ok_or_else(|| window_lost("Window no longer exists", &window.title))?;
+    if window.pid != 0 && current.pid != window.pid {
+        return Err(window_lost(
+            "Window now belongs to a different process",
+            &window.title,
+        ));
+    }
+
+    {
+        let mut lock = state.lock.write().unwrap();
+        // 重复锁定同一窗口时保留区域和子窗口，它们只对原窗口有效
+        if lock.window.as_ref().map(|w| w.id) != Some(current.id) {
+            lock.region = None;
+            lock.child = None;
+        }
+        lock.window = Some(current.clone());
+    }
+    log::info!("Locked window: {} (pid {})", current.title, current.pid);
+
+    // 目标进程退出后自动解除锁定
+    target_watcher::start(app, current.clone());
+    Ok(current)
 }
 
 #[tauri::command]
-fn unlock_window() {
-    let mut locked = LOCKED_WINDOW.lock().unwrap();
-    *locked = None;
+fn unlock_window(state: State<'_, AppState>) {
+    target_watcher::stop();
+    state.clear_lock();
 }
 
 #[tauri::command]
-fn get_locked_window() -> Option<WindowInfo> {
-    let locked = LOCKED_WINDOW.lock().unwrap();
-    locked.clone()
+fn get_locked_window(state: State<'_, AppState>) -> Option<WindowInfo> {
+    state.locked_window()
 }
 
-fn try_activate_locked_window() -> Result<(), String> {
-    let locked = LOCKED_WINDOW.lock().unwrap();
-    if let Some(ref window) = *locked {
-        #[cfg(target_os = "windows")]
-        uni_window::activate_window(window.id).map_err(|e| e.to_string())?;
-        
-        #[cfg(target_os = "macos")]
-        uni_window::activate_window_by_pid(window.pid).map_err(|e| e.to_string())?;
-        
+// 获取锁定窗口的最新信息（位置和大小可能已变化）
+fn refresh_locked_window(state: &AppState) -> Result<WindowInfo, AppError> {
+    let locked = state
+        .locked_window()
+        .ok_or_else(|| AppError::not_found("No window locked"))?;
+    uni_window::find_window(locked.id)
+        .map_err(AppError::window)?
+        .ok_or_else(|| window_lost("Locked window no longer exists", &locked.title))
+}
+
+// 锁定的窗口已关闭或无法激活，前端据此提示重新锁定
+fn window_lost(message: &str, title: &str) -> AppError {
+    AppError::new(ErrorCode::WindowLost, message).with_context(title)
+}
+
+#[tauri::command]
+fn lock_region(state: State<'_, AppState>, region: Rect) -> Result<(), AppError> {
+    set_lock_region(&state, region)
+}
+
+fn set_lock_region(state: &AppState, region: Rect) -> Result<(), AppError> {
+    let window = refresh_locked_window(state)?;
+    if !region.fits_within(window.width, window.height) {
+        return Err(AppError::invalid("Region is outside of the locked window"));
+    }
+    let mut lock = state.lock.write().unwrap();
+    lock.region = Some(region);
+    lock.child = None;
+    Ok(())
+}
+
+/// 在锁定窗口上框选区域，返回相对窗口的坐标
+#[tauri::command]
+async fn pick_lock_region(state: State<'_, AppState>) -> Result<Rect, AppError> {
+    let window = refresh_locked_window(&state)?;
+    let picked = mouse_simulator::pick_region().await?;
+    let region = Rect {
+        x: picked.x - window.x,
+        y: picked.y - window.y,
+        ..picked
+    };
+    set_lock_region(&state, region)?;
+    Ok(region)
+}
+
+#[tauri::command]
+fn clear_lock_region(state: State<'_, AppState>) {
+    let mut lock = state.lock.write().unwrap();
+    lock.region = None;
+    lock.child = None;
+}
+
+/// 枚举子窗口，未指定父窗口时使用锁定的窗口
+#[tauri::command]
+fn get_child_windows(
+    state: State<'_, AppState>,
+    parent_id: Option<u32>,
+) -> Result<Vec<ChildWindowInfo>, AppError> {
+    let parent_id = match parent_id {
+        Some(id) => id,
+        None => refresh_locked_window(&state)?.id,
+    };
+    uni_window::enumerate_child_windows(parent_id).map_err(AppError::window)
+}
+
+/// 锁定子窗口：子窗口区域作为锁定区域，子窗口本身作为输入目标
+#[tauri::command]
+fn lock_child_window(state: State<'_, AppState>, child: ChildWindowInfo) -> Result<(), AppError> {
+    let window = refresh_locked_window(&state)?;
+    if child.parent_id != window.id {
+        return Err(AppError::invalid(
+            "Child window does not belong to the locked window",
+        ));
+    }
+    set_lock_region(&state, child.rect)?;
+    state.lock.write().unwrap().child = Some(child);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_locked_child_window(state: State<'_, AppState>) -> Option<ChildWindowInfo> {
+    state.lock.read().unwrap().child.clone()
+}
+
+#[tauri::command]
+fn get_lock_region(state: State<'_, AppState>) -> Option<Rect> {
+    state.lock.read().unwrap().region
+}
+
+/// 检查锁定窗口的状态，返回需要提示用户的警告
+#[tauri::command]
+fn check_locked_window(state: State<'_, AppState>) -> Result<Vec<Warning>, AppError> {
+    let locked = match state.locked_window() {
+        Some(w) => w,
+        None => return Ok(Vec::new()),
+    };
+    let current = refresh_locked_window(&state)?;
+    let mut warnings = Vec::new();
+
+    if current.width != locked.width || current.height != locked.height {
+        warnings.push(Warning::WindowResized {
+            from_width: locked.width,
+            from_height: locked.height,
+            to_width: current.width,
+            to_height: current.height,
+        });
+    }
+    if let Some(region) = state.lock.read().unwrap().region {
+        if !region.fits_within(current.width, current.height) {
+            warnings.push(Warning::RegionOutsideWindow { region });
+        }
+    }
+    if current.is_minimized {
+        warnings.push(Warning::WindowMinimized);
+    }
+    if uni_window::display_mode(&current).ok() == Some(DisplayMode::ExclusiveFullscreen) {
+        warnings.push(Warning::ExclusiveFullscreen);
+    }
+    Ok(warnings)
+}
+
+fn try_activate_locked_window(
+    state: &AppState,
+    settings: &ActivationSettings,
+) -> Result<(), AppError> {
+    if settings.mode == ActivationMode::Never {
+        return Ok(());
+    }
+
+    if let Some(ref window) = state.locked_window() {
+        // 窗口已在前台时无需再次激活
+        if settings.mode == ActivationMode::IfUnfocused
+            && uni_window::is_window_foreground(window).unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        uni_window::activate_window_info(window).map_err(|e| {
+            window_lost(&format!("Failed to activate window: {}", e), &window.title)
+        })?;
+
         // Wait a bit for window to actually activate
-        std::thread::sleep(std::time::Duration::from_millis(500));
+        std::thread::sleep(std::time::Duration::from_millis(settings.wait_ms));
+
+        if settings.verify && !uni_window::is_window_foreground(window).map_err(AppError::window)? {
+            return Err(window_lost("Failed to activate window", &window.title));
+        }
     }
     Ok(())
 }
 
 #[tauri::command]
+fn get_profiles(state: State<'_, AppState>) -> Vec<GameProfile> {
+    state.profiles.list_profiles()
+}
+
+#[tauri::command]
+fn save_profile(state: State<'_, AppState>, profile: GameProfile) -> Result<(), AppError> {
+    state.profiles.save_profile(profile).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn delete_profile(state: State<'_, AppState>, name: &str) -> Result<(), AppError> {
+    state.profiles.delete_profile(name).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn set_active_profile(state: State<'_, AppState>, name: &str) -> Result<(), AppError> {
+    state
+        .profiles
+        .set_active_profile(name)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_active_profile(state: State<'_, AppState>) -> GameProfile {
+    state.profiles.active_profile()
+}
+
+/// 列出内置和自定义的音符映射表
+#[tauri::command]
+fn list_keymaps(state: State<'_, AppState>) -> Vec<keymap::KeymapSummary> {
+    state.keymaps.list()
+}
+
+#[tauri::command]
+fn get_keymap(state: State<'_, AppState>, name: &str) -> Result<keymap::Keymap, AppError> {
+    state.keymaps.get(name)
+}
+
+/// 保存自定义映射表，返回检查结果（共用按键的音符）
+#[tauri::command]
+fn save_keymap(
+    state: State<'_, AppState>,
+    keymap: keymap::Keymap,
+) -> Result<keymap::KeymapValidation, AppError> {
+    state.keymaps.save(keymap)
+}
+
+#[tauri::command]
+fn delete_keymap(state: State<'_, AppState>, name: &str) -> Result<(), AppError> {
+    state.keymaps.delete(name)
+}
+
+/// 内置的游戏预设（映射表和音域）
+#[tauri::command]
+fn get_game_presets() -> Vec<game_preset::GamePreset> {
+    game_preset::presets()
+}
+
+/// 切换到预设对应的档案并设置映射表，返回预设的音域和黑键处理方式
+#[tauri::command]
+fn apply_game_preset(
+    state: State<'_, AppState>,
+    id: &str,
+) -> Result<game_preset::GamePreset, AppError> {
+    game_preset::apply(&state, id)
+}
+
+/// 检查映射表中的按键能否解析，不保存
+#[tauri::command]
+fn validate_keymap(keymap: keymap::Keymap) -> keymap::KeymapValidation {
+    keymap.validate()
+}
+
+// 参数直接对应前端 invoke 的字段
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 fn parse_midi(
+    state: State<'_, AppState>,
     file_path: &str,
     min_note: u8,
     max_note: u8,
-    black_key_mode: &str,
+    black_key_mode: midi_analyzer::BlackKeyMode,
     trim_long_notes: bool,
-) -> Result<midi_analyzer::MidiAnalysis, String> {
-    midi_analyzer::analyze_midi_file(
-        file_path,
+    note_naming: Option<midi_analyzer::NoteNaming>,
+    track_shifts: Option<HashMap<usize, midi_analyzer::TrackShift>>,
+    fold_mode: Option<arrange::FoldMode>,
+    reduce_harmony: Option<bool>,
+    groove: Option<arrange::GrooveTransfer>,
+    release_early: Option<arrange::ReleaseEarly>,
+    import_track: Option<usize>,
+    max_gap_secs: Option<f64>,
+    auto_trim: Option<arrange::AutoTrim>,
+    velocity: Option<arrange::VelocityCompression>,
+    channel_delays_ms: Option<HashMap<u8, f64>>,
+    transpose: Option<i32>,
+    octave_shift: Option<i32>,
+    tracks: Option<Vec<usize>>,
+    channels: Option<Vec<u8>>,
+    exclude_channels: Option<Vec<u8>>,
+    keep_percussion: Option<bool>,
+    long_notes: Option<arrange::LongNoteTrim>,
+    min_release_gap_ms: Option<f64>,
+    merge_duplicates: Option<arrange::NoteMerge>,
+    simplify: Option<arrange::Simplify>,
+    extract_melody: Option<arrange::MelodyLine>,
+    quantize: Option<arrange::Quantize>,
+    tempo: Option<midi_analyzer::TempoOverride>,
+    auto_fit_to_c_major: Option<bool>,
+    include_controls: Option<bool>,
+) -> Result<midi_analyzer::MidiAnalysis, AppError> {
+    let options = midi_analyzer::AnalyzeOptions {
         min_note,
         max_note,
         black_key_mode,
         trim_long_notes,
+        long_notes,
+        naming: note_naming.unwrap_or_default(),
+        track_shifts: track_shifts.unwrap_or_default(),
+        tracks,
+        channels,
+        exclude_channels: exclude_channels.unwrap_or_default(),
+        keep_percussion: keep_percussion.unwrap_or(false),
+        transpose: transpose.unwrap_or(0),
+        tempo,
+        auto_fit_to_c_major: auto_fit_to_c_major.unwrap_or(false),
+        octave_shift: octave_shift.unwrap_or(0),
+        fold_mode: fold_mode.unwrap_or_default(),
+        extract_melody,
+        reduce_harmony: reduce_harmony.unwrap_or(false),
+        simplify,
+        quantize,
+        groove,
+        release_early,
+        import_track,
+        max_gap_secs,
+        auto_trim,
+        velocity,
+        merge_duplicates,
+        min_release_gap_ms,
+        channel_delays_ms: channel_delays_ms.unwrap_or_default(),
+        include_controls: include_controls.unwrap_or(false),
+    };
+    let mut analysis = midi_analyzer::analyze_midi_file(file_path, &options)?;
+    state.library.record_metadata(file_path, &analysis.metadata);
+    state
+        .library
+        .apply_track_labels(file_path, &mut analysis.tracks);
+    Ok(analysis)
+}
+
+/// 给音轨起名和设置颜色，之后分析同一文件时随音轨信息返回
+#[tauri::command]
+fn set_track_label(
+    state: State<'_, AppState>,
+    file_path: &str,
+    track: usize,
+    label: midi_analyzer::TrackLabel,
+) -> Result<(), AppError> {
+    state
+        .library
+        .set_track_label(file_path, track, label)
+        .map_err(AppError::from)
+}
+
+/// 保存可分享的 .autoscore 文件，内容为按键序列或嵌入的乐曲文件（二选一）
+// 参数直接对应前端 invoke 的字段
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn save_score(
+    state: State<'_, AppState>,
+    path: &str,
+    metadata: score_file::ScoreMetadata,
+    profile: Option<String>,
+    keymap: Option<String>,
+    options: Option<midi_analyzer::AnalyzeOptions>,
+    events: Option<Vec<keypress_simulator::KeyEvent>>,
+    midi_path: Option<String>,
+) -> Result<(), AppError> {
+    let source = match (events, midi_path) {
+        (Some(events), None) => score_file::ScoreSource::Events(events),
+        (None, Some(midi_path)) => score_file::ScoreSource::MidiFile(midi_path),
+        _ => return Err(AppError::invalid("Provide either events or a MIDI file")),
+    };
+    score_file::save(
+        Path::new(path),
+        metadata,
+        profile,
+        keymap,
+        options,
+        source,
+        &state,
     )
+    .map_err(AppError::from)
+}
+
+/// 打开 .autoscore 文件，校验和不符或引用的档案、键位表不存在时返回错误
+#[tauri::command]
+fn open_score(state: State<'_, AppState>, path: &str) -> Result<score_file::OpenedScore, AppError> {
+    score_file::open(Path::new(path), &state).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_song_info(
+    state: State<'_, AppState>,
+    file_path: &str,
+) -> Result<library::SongEntry, AppError> {
+    state.library.get_entry(file_path).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn set_song_tags(
+    state: State<'_, AppState>,
+    file_path: &str,
+    tags: Vec<String>,
+) -> Result<library::SongEntry, AppError> {
+    state
+        .library
+        .set_tags(file_path, tags)
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-fn start_playback(events: Vec<keypress_simulator::KeyEvent>) -> Result<(), String> {
-    try_activate_locked_window()?;
-    keypress_simulator::start_playback(events)
+fn set_song_rating(
+    state: State<'_, AppState>,
+    file_path: &str,
+    rating: Option<u8>,
+) -> Result<library::SongEntry, AppError> {
+    state
+        .library
+        .set_rating(file_path, rating)
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-fn stop_playback() -> Result<(), String> {
-    keypress_simulator::stop_playback()
+fn search_songs_by_tag(state: State<'_, AppState>, tag: &str) -> Vec<library::SongEntry> {
+    state.library.search_by_tag(tag)
 }
 
 #[tauri::command]
-fn start_mouse_playback(events: Vec<mouse_simulator::MouseEvent>) -> Result<(), String> {
-    try_activate_locked_window()?;
-    mouse_simulator::start_mouse_playback(events)
+fn get_song_tags(state: State<'_, AppState>) -> Vec<String> {
+    state.library.list_tags()
 }
 
 #[tauri::command]
-fn stop_mouse_playback() -> Result<(), String> {
-    mouse_simulator::stop_mouse_playback()
+fn get_recent_songs(state: State<'_, AppState>, limit: Option<usize>) -> Vec<library::SongEntry> {
+    state.library.recent_songs(limit)
 }
 
 #[tauri::command]
-async fn pick_mouse_coordinate() -> Result<(i32, i32), String> {
+fn get_last_played_song(state: State<'_, AppState>) -> Option<library::SongEntry> {
+    state.library.recent_songs(Some(1)).into_iter().next()
+}
+
+#[tauri::command]
+fn get_favorites(state: State<'_, AppState>) -> Vec<library::SongEntry> {
+    state.library.favorites()
+}
+
+#[tauri::command]
+fn get_favorite_slot(state: State<'_, AppState>, slot: usize) -> Option<library::SongEntry> {
+    state.library.favorite_at(slot)
+}
+
+#[tauri::command]
+fn add_favorite(
+    state: State<'_, AppState>,
+    file_path: &str,
+    slot: Option<usize>,
+) -> Result<Vec<library::SongEntry>, AppError> {
+    state
+        .library
+        .add_favorite(file_path, slot)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn remove_favorite(
+    state: State<'_, AppState>,
+    file_path: &str,
+) -> Result<Vec<library::SongEntry>, AppError> {
+    state
+        .library
+        .remove_favorite(file_path)
+        .map_err(AppError::from)
+}
+
+/// 播放开始后按档案设置启动焦点守护和用户输入中断监视
+fn start_playback_monitors(app: AppHandle, state: &AppState, profile: &GameProfile) {
+    if let Some(window) = state.locked_window() {
+        focus_guard::start(app.clone(), window, profile.focus_guard.clone());
+    }
+    input_interrupt::start(app.clone(), profile.input_interrupt.clone());
+    session_stats::start(app);
+}
+
+#[derive(Clone, serde::Serialize)]
+struct CountdownTick {
+    kind: &'static str,
+    remaining: u32,
+}
+
+/// 播放前的倒计时，每秒通知前端一次
+fn with_lead_in(
+    app: &AppHandle,
+    wait: state::StartWait,
+    kind: &'static str,
+    secs: Option<f64>,
+) -> Result<state::StartWait, AppError> {
+    let Some(secs) = secs.filter(|s| *s != 0.0) else {
+        return Ok(wait);
+    };
+    if !(0.0..=keypress_simulator::MAX_EVENT_SECS).contains(&secs) {
+        return Err(AppError::invalid(format!("Invalid lead-in: {}", secs)));
+    }
+    let app = app.clone();
+    Ok(wait.with_lead_in(secs, move |remaining| {
+        let _ = app.emit("playback://countdown", CountdownTick { kind, remaining });
+    }))
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ScheduledStartEvent {
+    kind: &'static str,
+    late_ms: f64, // 实际开始比计划晚的毫秒数
+}
+
+#[derive(Clone, serde::Serialize)]
+struct StartTriggerEvent {
+    kind: &'static str,
+    waited_ms: f64,
+}
+
+/// 播放开始前的等待：开始条件、定时开始和倒计时
+#[derive(Default)]
+struct PlaybackStart {
+    at: Option<Instant>,
+    lead_in_secs: Option<f64>,
+    when: Option<start_trigger::Trigger>,
+}
+
+impl PlaybackStart {
+    /// 按 Unix 时间戳（毫秒）或延迟秒数定时开始，两者最多给出一个
+    fn scheduled(
+        start_at_ms: Option<u64>,
+        delay_secs: Option<f64>,
+        lead_in_secs: Option<f64>,
+    ) -> Result<Self, AppError> {
+        let delay = match (start_at_ms, delay_secs) {
+            (Some(_), Some(_)) => {
+                return Err(AppError::invalid(
+                    "Provide either a start time or a delay, not both",
+                ))
+            }
+            (Some(ms), None) => Some(
+                UNIX_EPOCH
+                    .checked_add(Duration::from_millis(ms))
+                    .ok_or_else(|| AppError::invalid("Scheduled start time is too far away"))?
+                    .duration_since(SystemTime::now())
+                    .map_err(|_| AppError::invalid("Scheduled start time is in the past"))?,
+            ),
+            (None, Some(secs)) if !(0.0..=keypress_simulator::MAX_EVENT_SECS).contains(&secs) => {
+                return Err(AppError::invalid(format!("Invalid start delay: {}", secs)))
+            }
+            (None, Some(secs)) => Some(Duration::from_secs_f64(secs)),
+            (None, None) => None,
+        };
+        // 定时开始与倒计时一样最多等 MAX_EVENT_SECS
+        let at = match delay {
+            Some(delay) if delay.as_secs_f64() > keypress_simulator::MAX_EVENT_SECS => {
+                return Err(AppError::invalid("Scheduled start time is too far away"))
+            }
+            Some(delay) => Some(
+                Instant::now()
+                    .checked_add(delay)
+                    .ok_or_else(|| AppError::invalid("Scheduled start time is too far away"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            at,
+            lead_in_secs,
+            when: None,
+        })
+    }
+
+    /// 等到开始条件满足再开始，不能和定时开始同时使用
+    fn when(
+        mut self,
+        state: &AppState,
+        condition: Option<start_trigger::StartCondition>,
+    ) -> Result<Self, AppError> {
+        let Some(condition) = condition else {
+            return Ok(self);
+        };
+        if self.at.is_some() {
+            return Err(AppError::invalid(
+                "Provide either a start condition or a scheduled start, not both",
+            ));
+        }
+        let window = state.locked_window();
+        self.when = Some(start_trigger::Trigger::compile(condition, window.as_ref())?);
+        Ok(self)
+    }
+}
+
+// 整理成随 start 一起传入的等待，只对这一次播放有效
+fn playback_wait(
+    app: &AppHandle,
+    kind: &'static str,
+    start: PlaybackStart,
+) -> Result<state::StartWait, AppError> {
+    let mut wait = with_lead_in(app, state::StartWait::default(), kind, start.lead_in_secs)?;
+    if let Some(trigger) = start.when {
+        wait = with_start_trigger(app, wait, kind, trigger);
+    }
+    if let Some(at) = start.at {
+        let app = app.clone();
+        wait = wait.with_scheduled_start(at, move |late| {
+            let late_ms = late.as_secs_f64() * 1000.0;
+            log::info!(
+                "Scheduled {} playback started ({:.2}ms late)",
+                kind,
+                late_ms
+            );
+            let _ = app.emit(
+                "playback://scheduled-start",
+                ScheduledStartEvent { kind, late_ms },
+            );
+        });
+    }
+    Ok(wait)
+}
+
+/// 画面满足开始条件时通知前端并开始；超时放弃时发出 "playback://start-trigger-timeout"
+fn with_start_trigger(
+    app: &AppHandle,
+    wait: state::StartWait,
+    kind: &'static str,
+    trigger: start_trigger::Trigger,
+) -> state::StartWait {
+    let app = app.clone();
+    let since = Instant::now();
+    wait.with_gate(trigger.poll_interval, move || {
+        let waited = since.elapsed();
+        match trigger.is_met() {
+            Ok(true) => {
+                let waited_ms = waited.as_secs_f64() * 1000.0;
+                log::info!("Start condition met after {:.0}ms", waited_ms);
+                let _ = app.emit(
+                    "playback://start-triggered",
+                    StartTriggerEvent { kind, waited_ms },
+                );
+                return state::GateCheck::Open;
+            }
+            Ok(false) => {}
+            // 截屏偶尔失败（如切换全屏时），继续等待
+            Err(e) => log::warn!("Failed to check start condition: {}", e),
+        }
+        if trigger.timeout.is_some_and(|timeout| waited >= timeout) {
+            log::warn!("Start condition not met, {} playback abandoned", kind);
+            let _ = app.emit("playback://start-trigger-timeout", kind);
+            return state::GateCheck::Abandon;
+        }
+        state::GateCheck::Wait
+    })
+}
+
+/// 播放结束时保存报告并通知前端
+fn on_playback_finished(
+    app: AppHandle,
+) -> impl FnOnce(playback_report::PlaybackReport) + Send + 'static {
+    move |report| {
+        log::info!(
+            "{} playback finished: {}/{} played, {} failed, completed: {}, jitter avg {:.2}ms p95 {:.2}ms",
+            report.kind,
+            report.played,
+            report.total_events,
+            report.failed,
+            report.completed,
+            report.avg_jitter_ms,
+            report.p95_jitter_ms
+        );
+        let state = app.state::<AppState>();
+        *state.last_report.lock().unwrap() = Some(report.clone());
+        // 队列中的曲子由队列结束时统一执行
+        if !state.queue.is_running() {
+            after_playback::on_finished(&app, report.completed);
+        }
+        let _ = app.emit("playback://report", report);
+    }
+}
+
+// 记录正在播放的曲目；播放统计失败不影响播放本身
+fn note_song_started(state: &AppState, file_path: Option<&str>) {
+    *state.now_playing.lock().unwrap() = session_stats::NowPlaying::from_file(file_path);
+    if let Some(path) = file_path {
+        if let Err(e) = state.library.record_play(path) {
+            log::warn!("Failed to record play: {}", e);
+        }
+    }
+}
+
+/// 按当前档案的键盘矩阵限制检查按键序列，revoice 开启时返回去掉冲突后的序列
+#[tauri::command]
+fn check_key_ghosting(
+    state: State<'_, AppState>,
+    events: Vec<keypress_simulator::KeyEvent>,
+) -> ghosting::GhostingCheck {
+    let settings = state.profiles.active_profile().keyboard_matrix;
+    ghosting::check_events(events, &settings)
+}
+
+// 参数直接对应前端 invoke 的字段
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn start_playback(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    events: Vec<keypress_simulator::KeyEvent>,
+    file_path: Option<String>,
+    mode: Option<keypress_simulator::KeyPlaybackMode>,
+    loop_start: Option<f64>,
+    loop_end: Option<f64>,
+    lead_in_secs: Option<f64>,
+    humanize: Option<keypress_simulator::Humanize>,
+    start_at_ms: Option<u64>,
+    start_delay_secs: Option<f64>,
+    dry_run: Option<bool>,
+    accompaniment: Option<accompaniment::AccompanimentLoop>,
+    start_when: Option<start_trigger::StartCondition>,
+    key_feedback: Option<bool>,
+    strict_events: Option<bool>,
+) -> Result<(), AppError> {
+    let start = PlaybackStart::scheduled(start_at_ms, start_delay_secs, lead_in_secs)?
+        .when(&state, start_when)?;
+    let dry_run = dry_run.unwrap_or(false);
+    // 伴奏在主旋律开始前检查，出错时都不播放
+    let accompaniment = match accompaniment {
+        Some(layer) => {
+            let pattern = accompaniment::Pattern::compile(layer)?;
+            let input = if dry_run {
+                dry_run_input(&app)
+            } else {
+                input_service::handle()?
+            };
+            Some((pattern, input))
+        }
+        None => None,
+    };
+    // 只给出一端时，另一端取乐曲开头或结尾
+    let loop_region = if loop_start.is_some() || loop_end.is_some() {
+        Some(keypress_simulator::LoopRegion {
+            start: loop_start.unwrap_or(0.0),
+            end: loop_end.unwrap_or_else(|| keypress_simulator::song_end(&events)),
+        })
+    } else {
+        None
+    };
+    let options = keypress_simulator::KeyPlaybackOptions {
+        mode: mode.unwrap_or_default(),
+        loop_region,
+        humanize,
+        dry_run: dry_run.then(|| dry_run_input(&app)),
+        mouse_style: mouse_simulator::MouseStyle::default(),
+        key_feedback: key_feedback
+            .unwrap_or(false)
+            .then(|| key_feedback::start(app.clone())),
+        strict_events: strict_events.unwrap_or(false),
+    };
+    start_key_playback(
+        &app,
+        &state,
+        events,
+        file_path.as_deref(),
+        options,
+        start,
+        on_playback_finished(app.clone()),
+    )?;
+    if let Some((pattern, input)) = accompaniment {
+        state
+            .accompaniment
+            .start(state.keyboard.clone(), input, pattern);
+    }
+    Ok(())
+}
+
+/// 按映射表把原始 MIDI 事件转换为按键后播放，未指定映射表时使用当前档案的映射表。
+/// black_key_mode 为 shift_modifier 时黑键用 shift + 下方白键发送，其余参数同 start_playback
+// 参数直接对应前端 invoke 的字段
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn start_midi_playback(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    events: Vec<midi_analyzer::MidiEvent>,
+    keymap: Option<String>,
+    file_path: Option<String>,
+    mode: Option<keypress_simulator::KeyPlaybackMode>,
+    loop_start: Option<f64>,
+    loop_end: Option<f64>,
+    lead_in_secs: Option<f64>,
+    humanize: Option<keypress_simulator::Humanize>,
+    start_at_ms: Option<u64>,
+    start_delay_secs: Option<f64>,
+    dry_run: Option<bool>,
+    accompaniment: Option<accompaniment::AccompanimentLoop>,
+    black_key_mode: Option<midi_analyzer::BlackKeyMode>,
+    start_when: Option<start_trigger::StartCondition>,
+    key_feedback: Option<bool>,
+    strict_events: Option<bool>,
+) -> Result<(), AppError> {
+    let keymap = keymap
+        .or_else(|| state.profiles.active_profile().keymap)
+        .ok_or_else(|| AppError::invalid("No keymap selected"))?;
+    let shift_black_keys = black_key_mode == Some(midi_analyzer::BlackKeyMode::ShiftModifier);
+    let mapped = state
+        .keymaps
+        .get(&keymap)?
+        .map_events(&events, shift_black_keys);
+    if mapped.unmapped > 0 {
+        log::warn!("{} notes have no key in keymap {}", mapped.unmapped, keymap);
+    }
+    if mapped.events.is_empty() {
+        return Err(AppError::invalid("No notes map to keys").with_context(&keymap));
+    }
+    start_playback(
+        app,
+        state,
+        mapped.events,
+        file_path,
+        mode,
+        loop_start,
+        loop_end,
+        lead_in_secs,
+        humanize,
+        start_at_ms,
+        start_delay_secs,
+        dry_run,
+        accompaniment,
+        start_when,
+        key_feedback,
+        strict_events,
+    )
+}
+
+/// 等游戏画面满足开始条件（如演奏界面出现）时开始播放，其余参数同 start_playback
+// 参数直接对应前端 invoke 的字段
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn start_playback_when(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    condition: start_trigger::StartCondition,
+    events: Vec<keypress_simulator::KeyEvent>,
+    file_path: Option<String>,
+    mode: Option<keypress_simulator::KeyPlaybackMode>,
+    lead_in_secs: Option<f64>,
+    humanize: Option<keypress_simulator::Humanize>,
+    dry_run: Option<bool>,
+    accompaniment: Option<accompaniment::AccompanimentLoop>,
+    key_feedback: Option<bool>,
+    strict_events: Option<bool>,
+) -> Result<(), AppError> {
+    start_playback(
+        app,
+        state,
+        events,
+        file_path,
+        mode,
+        None,
+        None,
+        lead_in_secs,
+        humanize,
+        None,
+        None,
+        dry_run,
+        accompaniment,
+        Some(condition),
+        key_feedback,
+        strict_events,
+    )
+}
+
+/// 立即读取开始条件中各探针的颜色，用于设置时检查条件是否正确
+#[tauri::command]
+fn check_start_condition(
+    state: State<'_, AppState>,
+    condition: start_trigger::StartCondition,
+) -> Result<Vec<start_trigger::ProbeReading>, AppError> {
+    let window = state.locked_window();
+    start_trigger::Trigger::compile(condition, window.as_ref())?.read()
+}
+
+#[derive(Clone, serde::Serialize)]
+struct DryRunInput {
+    position_secs: f64, // 实际触发时的播放位置
+    #[serde(flatten)]
+    action: input_service::InputAction,
+}
+
+// 空跑时把每个本该发送的输入记入日志并通过 "playback://dry-run" 通知前端
+fn dry_run_input(app: &AppHandle) -> input_service::InputHandle {
+    let app = app.clone();
+    input_service::dry_run(move |action| {
+        let position_secs = app.state::<AppState>().keyboard.status().position_secs;
+        log::info!("Dry run at {:.3}s: {:?}", position_secs, action);
+        let _ = app.emit(
+            "playback://dry-run",
+            DryRunInput {
+                position_secs,
+                action,
+            },
+        );
+    })
+}
+
+// 按键盘矩阵检查会串键的按键，开启 revoice 时直接修正序列
+fn warn_ghosting(events: &mut Vec<keypress_simulator::KeyEvent>, profile: &GameProfile) {
+    let ghosted = ghosting::check(events, &profile.keyboard_matrix);
+    if ghosted > 0 {
+        log::warn!(
+            "{} key presses would ghost on the keyboard matrix (revoiced: {})",
+            ghosted,
+            profile.keyboard_matrix.revoice
+        );
+    }
+}
+
+// 检查并规整前端传入的按键事件，做了修正时通过 "playback://events-fixed" 通知前端
+fn check_key_events(
+    app: &AppHandle,
+    events: &mut [keypress_simulator::KeyEvent],
+    strict: bool,
+) -> Result<(), AppError> {
+    let fixes = keypress_simulator::normalize_events(events, strict)?;
+    if !fixes.is_empty() {
+        log::warn!("Normalized key events before playback: {:?}", fixes);
+        let _ = app.emit("playback://events-fixed", fixes);
+    }
+    Ok(())
+}
+
+// 按当前档案预处理按键序列并开始播放，单曲播放和队列共用
+fn start_key_playback(
+    app: &AppHandle,
+    state: &AppState,
+    mut events: Vec<keypress_simulator::KeyEvent>,
+    file_path: Option<&str>,
+    options: keypress_simulator::KeyPlaybackOptions,
+    start: PlaybackStart,
+    on_finish: impl FnOnce(playback_report::PlaybackReport) + Send + 'static,
+) -> Result<(), AppError> {
+    check_key_events(app, &mut events, options.strict_events)?;
+    let profile = state.profiles.active_profile();
+    // 先按帧对齐，对齐后同时按下的键可能变多
+    if let Some(frame_ms) = profile.frame_sync_ms {
+        frame_sync::align_key_events(&mut events, frame_ms);
+    }
+    warn_ghosting(&mut events, &profile);
+    // 空跑不碰游戏窗口
+    let dry_run = options.dry_run.is_some();
+    if !dry_run {
+        try_activate_locked_window(state, &profile.activation)?;
+    }
+    state.keyboard.apply_profile(&profile);
+    let wait = playback_wait(app, "keyboard", start)?;
+    keypress_simulator::start_playback(&state.keyboard, events, options, wait, on_finish)?;
+    if dry_run {
+        session_stats::start(app.clone());
+    } else {
+        start_playback_monitors(app.clone(), state, &profile);
+    }
+    note_song_started(state, file_path);
+    Ok(())
+}
+
+fn play_queued_song(
+    app: &AppHandle,
+    song: &playlist::QueueSong,
+    on_finish: Box<dyn FnOnce(playback_report::PlaybackReport) + Send>,
+) -> Result<(), String> {
+    let save_report = on_playback_finished(app.clone());
+    start_key_playback(
+        app,
+        &app.state::<AppState>(),
+        song.events.clone(),
+        song.file_path.as_deref(),
+        keypress_simulator::KeyPlaybackOptions {
+            mode: song.mode,
+            ..Default::default()
+        },
+        PlaybackStart {
+            lead_in_secs: song.lead_in_secs,
+            ..Default::default()
+        },
+        move |report| {
+            save_report(report.clone());
+            on_finish(report);
+        },
+    )
+    .map_err(String::from)
+}
+
+/// 依次播放多首曲子，曲间的切换通过 "queue://transition" 事件通知。
+/// 不传曲子时播放已添加到队列的曲子
+#[tauri::command]
+fn start_queue(
+    app: AppHandle,
+    songs: Option<Vec<playlist::QueueSong>>,
+    settings: Option<playlist::QueueSettings>,
+) -> Result<(), AppError> {
+    playlist::start(&app, songs, settings, play_queued_song).map_err(AppError::from)
+}
+
+/// 添加曲子到队尾，返回队列长度
+#[tauri::command]
+fn enqueue_songs(state: State<'_, AppState>, songs: Vec<playlist::QueueSong>) -> usize {
+    state.queue.enqueue(songs)
+}
+
+#[tauri::command]
+fn get_queue(state: State<'_, AppState>) -> playlist::QueueSnapshot {
+    state.queue.snapshot()
+}
+
+/// 清除还没播放的曲子
+#[tauri::command]
+fn clear_queue(state: State<'_, AppState>) {
+    state.queue.clear();
+}
+
+/// 打乱还没播放的曲子
+#[tauri::command]
+fn shuffle_queue(state: State<'_, AppState>) {
+    state.queue.shuffle();
+}
+
+/// 修改曲间间隔、倒计时和自动切换，从下一首开始生效
+#[tauri::command]
+fn set_queue_settings(
+    state: State<'_, AppState>,
+    settings: playlist::QueueSettings,
+) -> Result<(), AppError> {
+    state.queue.set_settings(settings).map_err(AppError::from)
+}
+
+/// 停止当前曲子并开始下一首
+#[tauri::command]
+fn skip_queued_song(state: State<'_, AppState>) {
+    state.skip_queued_song();
+}
+
+/// 停止队列和正在播放的曲子
+#[tauri::command]
+fn stop_queue(state: State<'_, AppState>) {
+    state.queue.stop();
+    state.keyboard.stop();
+}
+
+/// 当前曲子播完后停止队列
+#[tauri::command]
+fn set_queue_stop_after_current(state: State<'_, AppState>, enabled: bool) {
+    state.queue.set_stop_after_current(enabled);
+}
+
+/// 设置播放或队列结束后执行的动作（宏、聊天消息、退出程序）和队列的曲数上限
+#[tauri::command]
+fn set_after_playback(
+    state: State<'_, AppState>,
+    settings: after_playback::AfterPlayback,
+) -> Result<(), AppError> {
+    settings.validate()?;
+    *state.after_playback.lock().unwrap() = settings;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_after_playback(state: State<'_, AppState>) -> after_playback::AfterPlayback {
+    state.after_playback.lock().unwrap().clone()
+}
+
+/// 新建磁盘时间线，超长的按键序列可分块追加后流式播放
+#[tauri::command]
+fn create_timeline(state: State<'_, AppState>) -> Result<u64, AppError> {
+    state.timelines.create().map_err(AppError::from)
+}
+
+/// 向时间线追加一块按时间排序的事件，返回总事件数
+#[tauri::command]
+fn append_timeline(
+    state: State<'_, AppState>,
+    handle: u64,
+    events: Vec<keypress_simulator::KeyEvent>,
+) -> Result<usize, AppError> {
+    state
+        .timelines
+        .append(handle, &events)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn delete_timeline(state: State<'_, AppState>, handle: u64) -> Result<(), AppError> {
+    state.timelines.remove(handle).map_err(AppError::from)
+}
+
+/// 流式播放时间线。事件不全部载入内存，因此不做帧对齐、键盘矩阵检查和循环区间
+#[tauri::command]
+fn start_timeline_playback(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    handle: u64,
+    file_path: Option<String>,
+    mode: Option<keypress_simulator::KeyPlaybackMode>,
+    lead_in_secs: Option<f64>,
+) -> Result<(), AppError> {
+    let timeline = state.timelines.get(handle)?;
+    let profile = state.profiles.active_profile();
+    try_activate_locked_window(&state, &profile.activation)?;
+    state.keyboard.apply_profile(&profile);
+    let wait = with_lead_in(&app, state::StartWait::default(), "keyboard", lead_in_secs)?;
+    keypress_simulator::start_timeline_playback(
+        &state.keyboard,
+        &timeline,
+        mode.unwrap_or_default(),
+        wait,
+        on_playback_finished(app.clone()),
+    )?;
+    start_playback_monitors(app, &state, &profile);
+    note_song_started(&state, file_path.as_deref());
+    Ok(())
+}
+
+/// 当前曲目和播放进度，与 "session://stats" 事件内容相同
+#[tauri::command]
+fn get_session_stats(state: State<'_, AppState>) -> session_stats::SessionStats {
+    session_stats::snapshot(&state)
+}
+
+/// 查询键盘和鼠标播放的进度
+#[tauri::command]
+fn get_playback_status(state: State<'_, AppState>) -> state::PlaybackStatusReport {
+    state.playback_status()
+}
+
+#[tauri::command]
+fn get_last_playback_report(state: State<'_, AppState>) -> Option<playback_report::PlaybackReport> {
+    state.last_report.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn stop_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    if !state.keyboard.stop() {
+        return Err(stop_timed_out("keyboard"));
+    }
+    Ok(())
+}
+
+// 播放线程没有在时限内结束，已被放弃，播放状态已重置
+fn stop_timed_out(kind: &str) -> AppError {
+    AppError::new(
+        ErrorCode::Timeout,
+        "Playback did not stop in time and was abandoned",
+    )
+    .with_context(kind)
+}
+
+/// 停止循环伴奏，主旋律继续播放
+#[tauri::command]
+fn stop_accompaniment(state: State<'_, AppState>) -> Result<(), AppError> {
+    if !state.accompaniment.is_running() {
+        return Err(AppError::not_playing().with_context("accompaniment"));
+    }
+    state.accompaniment.stop();
+    Ok(())
+}
+
+/// 可用的 MIDI 输出端口
+#[tauri::command]
+fn list_midi_outputs() -> Result<Vec<String>, AppError> {
+    midi_clock::list_outputs()
+}
+
+/// 在 MIDI 输出端口上发送跟随当前键盘播放的时钟，播放结束时自动停止
+#[tauri::command]
+fn start_midi_clock(
+    state: State<'_, AppState>,
+    sync: midi_clock::ClockSync,
+) -> Result<(), AppError> {
+    if !state.keyboard.is_playing() {
+        return Err(AppError::not_playing());
+    }
+    state.midi_clock.start(state.keyboard.clone(), sync)
+}
+
+#[tauri::command]
+fn stop_midi_clock(state: State<'_, AppState>) -> Result<(), AppError> {
+    if !state.midi_clock.is_running() {
+        return Err(AppError::not_playing().with_context("midi clock"));
+    }
+    state.midi_clock.stop();
+    Ok(())
+}
+
+/// 通过 Ableton Link 同步当前键盘播放：跟随会话的速度和相位，或把乐曲速度发布到会话
+#[tauri::command]
+fn start_link_sync(state: State<'_, AppState>, sync: link_sync::LinkSync) -> Result<(), AppError> {
+    if !state.keyboard.is_playing() {
+        return Err(AppError::not_playing());
+    }
+    state.tempo_link.start(state.keyboard.clone(), sync)
+}
+
+/// 停止同步并离开 Link 会话
+#[tauri::command]
+fn stop_link_sync(state: State<'_, AppState>) {
+    state.tempo_link.disable();
+}
+
+#[tauri::command]
+fn get_link_status(state: State<'_, AppState>) -> link_sync::LinkStatus {
+    state.tempo_link.status()
+}
+
+/// 跳转到按键序列的指定位置（秒）
+#[tauri::command]
+fn seek_playback(state: State<'_, AppState>, seconds: f64) -> Result<(), AppError> {
+    state.keyboard.seek(seconds)
+}
+
+#[tauri::command]
+fn get_emergency_stop(state: State<'_, AppState>) -> emergency_stop::EmergencyStopSettings {
+    state.emergency_stop.settings()
+}
+
+/// 修改紧急停止热键，立即生效
+#[tauri::command]
+fn set_emergency_stop(
+    app: AppHandle,
+    settings: emergency_stop::EmergencyStopSettings,
+) -> Result<(), AppError> {
+    emergency_stop::update(&app, settings).map_err(AppError::from)
+}
+
+/// 导出全部配置（档案、映射表、热键）到一个文件
+#[tauri::command]
+fn export_config(app: AppHandle, path: &str) -> Result<(), AppError> {
+    config_bundle::export(&app, Path::new(path)).map_err(AppError::from)
+}
+
+/// 导入配置文件；replace 为 true 时替换全部档案和映射表，否则按名称合并
+#[tauri::command]
+fn import_config(
+    app: AppHandle,
+    path: &str,
+    replace: Option<bool>,
+) -> Result<config_bundle::ImportSummary, AppError> {
+    config_bundle::import(&app, Path::new(path), replace.unwrap_or(false)).map_err(AppError::from)
+}
+
+/// 取消还在等待定时开始的键盘播放
+#[tauri::command]
+fn cancel_scheduled_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    if !state.keyboard.is_waiting_for_start() {
+        return Err(AppError::new(
+            ErrorCode::NotPlaying,
+            "No scheduled playback",
+        ));
+    }
+    state.keyboard.stop();
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.keyboard.pause()
+}
+
+#[tauri::command]
+fn resume_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.keyboard.resume();
+    Ok(())
+}
+
+#[tauri::command]
+fn start_mouse_playback(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut events: Vec<mouse_simulator::MouseEvent>,
+    file_path: Option<String>,
+    relative: Option<bool>,
+    lead_in_secs: Option<f64>,
+) -> Result<(), AppError> {
+    if relative.unwrap_or(false) {
+        to_screen_coordinates(&state, &mut events)?;
+    }
+    mouse_simulator::resolve_monitor_points(&mut events)?;
+
+    let profile = state.profiles.active_profile();
+    if let Some(frame_ms) = profile.frame_sync_ms {
+        frame_sync::align_mouse_events(&mut events, frame_ms);
+    }
+    try_activate_locked_window(&state, &profile.activation)?;
+    state.mouse.apply_profile(&profile);
+    let wait = with_lead_in(&app, state::StartWait::default(), "mouse", lead_in_secs)?;
+    mouse_simulator::start_mouse_playback(
+        &state.mouse,
+        events,
+        mouse_style(&state, &profile),
+        wait,
+        on_playback_finished(app.clone()),
+    )?;
+    start_playback_monitors(app, &state, &profile);
+    note_song_started(&state, file_path.as_deref());
+    Ok(())
+}
+
+// 按档案设置鼠标回放的轨迹和点击节奏
+fn mouse_style(state: &AppState, profile: &GameProfile) -> mouse_simulator::MouseStyle {
+    mouse_simulator::MouseStyle {
+        traces: if profile.human_mouse_traces {
+            state.mouse_traces.playback_set()
+        } else {
+            Vec::new()
+        },
+        timing: profile.mouse_humanize.timing(),
+    }
+}
+
+/// 真人鼠标轨迹库中的轨迹（内置示例和录制的）
+#[tauri::command]
+fn list_mouse_traces(state: State<'_, AppState>) -> Vec<mouse_traces::TraceSummary> {
+    state.mouse_traces.list()
+}
+
+#[tauri::command]
+fn delete_mouse_trace(state: State<'_, AppState>, name: &str) -> Result<(), AppError> {
+    state.mouse_traces.delete(name)
+}
+
+/// 开始录制鼠标轨迹：正常移动并点击，每段移动后点击保存为一条轨迹
+#[tauri::command]
+fn start_mouse_trace_recording(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.mouse_traces.start_recording()
+}
+
+#[tauri::command]
+fn stop_mouse_trace_recording(
+    state: State<'_, AppState>,
+    name_prefix: Option<String>,
+) -> Result<Vec<mouse_traces::TraceSummary>, AppError> {
+    state
+        .mouse_traces
+        .stop_recording(name_prefix.as_deref().unwrap_or("trace"))
+}
+
+// 坐标相对锁定窗口（或锁定区域）时，按窗口当前位置换算为屏幕坐标
+fn to_screen_coordinates(
+    state: &AppState,
+    events: &mut [mouse_simulator::MouseEvent],
+) -> Result<(), AppError> {
+    let window = refresh_locked_window(state)?;
+    let region = state.lock.read().unwrap().region;
+    let (origin_x, origin_y) = match region {
+        Some(r) => (window.x + r.x, window.y + r.y),
+        None => (window.x, window.y),
+    };
+    // 按显示器记录的坐标已是绝对位置
+    for event in events.iter_mut().filter(|e| e.monitor_id.is_none()) {
+        event.x += origin_x;
+        event.y += origin_y;
+        for point in &mut event.waypoints {
+            point.x += origin_x;
+            point.y += origin_y;
+        }
+    }
+    Ok(())
+}
+
+/// 同一线程、同一时钟播放一起录制的按键和鼠标事件，由键盘播放的控制命令暂停、停止和跳转
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn start_combined_playback(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut key_events: Vec<keypress_simulator::KeyEvent>,
+    mut mouse_events: Vec<mouse_simulator::MouseEvent>,
+    file_path: Option<String>,
+    mode: Option<keypress_simulator::KeyPlaybackMode>,
+    relative: Option<bool>,
+    lead_in_secs: Option<f64>,
+    key_feedback: Option<bool>,
+    strict_events: Option<bool>,
+) -> Result<(), AppError> {
+    check_key_events(&app, &mut key_events, strict_events.unwrap_or(false))?;
+    if relative.unwrap_or(false) {
+        to_screen_coordinates(&state, &mut mouse_events)?;
+    }
+    mouse_simulator::resolve_monitor_points(&mut mouse_events)?;
+    if state.mouse.is_playing() {
+        return Err(AppError::busy().with_context("mouse"));
+    }
+
+    let profile = state.profiles.active_profile();
+    if let Some(frame_ms) = profile.frame_sync_ms {
+        frame_sync::align_key_events(&mut key_events, frame_ms);
+        frame_sync::align_mouse_events(&mut mouse_events, frame_ms);
+    }
+    warn_ghosting(&mut key_events, &profile);
+    try_activate_locked_window(&state, &profile.activation)?;
+    state.keyboard.apply_profile(&profile);
+    let wait = with_lead_in(&app, state::StartWait::default(), "combined", lead_in_secs)?;
+    keypress_simulator::start_combined_playback(
+        &state.keyboard,
+        key_events,
+        mouse_events,
+        keypress_simulator::KeyPlaybackOptions {
+            mode: mode.unwrap_or_default(),
+            mouse_style: mouse_style(&state, &profile),
+            key_feedback: key_feedback
+                .unwrap_or(false)
+                .then(|| key_feedback::start(app.clone())),
+            ..Default::default()
+        },
+        wait,
+        on_playback_finished(app.clone()),
+    )?;
+    start_playback_monitors(app, &state, &profile);
+    note_song_started(&state, file_path.as_deref());
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_mouse_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    if !state.mouse.stop() {
+        return Err(stop_timed_out("mouse"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_mouse_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.mouse.pause()
+}
+
+#[tauri::command]
+fn resume_mouse_playback(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.mouse.resume();
+    Ok(())
+}
+
+#[tauri::command]
+async fn pick_mouse_coordinate() -> Result<(i32, i32), AppError> {
     mouse_simulator::pick_coordinate().await
 }
 
+/// 选择鼠标坐标并按所在显示器记录，混合缩放的多显示器上回放时不会错位
+#[tauri::command]
+async fn pick_monitor_coordinate() -> Result<uni_window::MonitorPoint, AppError> {
+    mouse_simulator::pick_monitor_coordinate().await
+}
+
+/// 启动诊断：权限、输入后端、窗口枚举、全局快捷键、计时器精度和显示缩放
+#[tauri::command]
+async fn run_diagnostics(app: AppHandle) -> diagnostics::DiagnosticsReport {
+    diagnostics::run(&app)
+}
+
+#[tauri::command]
+fn set_log_level(level: logging::LogLevel) {
+    logging::set_level(level);
+}
+
+#[tauri::command]
+fn get_log_level() -> logging::LogLevel {
+    logging::level()
+}
+
+/// 导出最近的日志用于问题报告，返回导出文件的路径
+#[tauri::command]
+fn export_logs(app: AppHandle, target_path: Option<String>) -> Result<String, AppError> {
+    let log_dir = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| AppError::io(e.to_string()))?;
+    let path = logging::export(&log_dir, target_path.map(Into::into))?;
+    Ok(path.display().to_string())
+}
+
+/// 从麦克风录制哼唱或口哨，识别成旋律并保存为 MIDI 文件，返回文件路径
+#[tauri::command]
+async fn record_melody(app: AppHandle, seconds: Option<f64>) -> Result<String, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(e.to_string()))?;
+    let path = hum::record_melody(&data_dir, seconds)?;
+    Ok(path.display().to_string())
+}
+
+/// 识别乐谱图片或 PDF（实验性，需要安装 Audiveris），返回生成的 MIDI 文件和识别质量报告
+#[tauri::command]
+async fn import_sheet_image(
+    app: AppHandle,
+    file_path: String,
+    audiveris_path: Option<String>,
+) -> Result<omr::SheetImport, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(e.to_string()))?;
+    omr::import_sheet_image(&data_dir, &file_path, audiveris_path.as_deref())
+        .map_err(AppError::from)
+}
+
+/// 从网址下载乐曲或 .autoscore 文件并分析，下载进度通过 "import://progress" 事件通知
+#[tauri::command]
+async fn import_from_url(
+    app: AppHandle,
+    url: String,
+    sha256: Option<String>,
+    options: Option<midi_analyzer::AnalyzeOptions>,
+) -> Result<url_import::UrlImport, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(e.to_string()))?;
+    url_import::import_from_url(
+        &app,
+        &data_dir,
+        &url,
+        sha256.as_deref(),
+        &options.unwrap_or_default(),
+    )
+    .map_err(AppError::from)
+}
+
+/// 检查输入后端能否初始化（权限、显示服务等）
+#[tauri::command]
+fn probe_input_backend() -> input_backend::InputBackendStatus {
+    input_backend::probe()
+}
+
+#[tauri::command]
+async fn self_test(state: State<'_, AppState>) -> Result<self_test::SelfTestReport, AppError> {
+    self_test::run(&state).map_err(AppError::from)
+}
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -108,18 +1682,119 @@ pub fn run() {
         .plugin(tauri_plugin_window_state::Builder::default().build()) // Add this line
         .plugin(tauri_plugin_dialog::init()) // Add this line
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .setup(|app| {
+            logging::set_level(logging::LogLevel::Info);
+            let state = AppState::new(app.path().app_config_dir()?, app.path().app_data_dir()?);
+            app.manage(state);
+            emergency_stop::install(app.handle());
+            media_controls::install(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             parse_midi,
+            save_score,
+            open_score,
+            check_key_ghosting,
             start_playback,
+            start_midi_playback,
+            start_playback_when,
+            check_start_condition,
+            create_timeline,
+            append_timeline,
+            delete_timeline,
+            start_timeline_playback,
+            start_queue,
+            enqueue_songs,
+            get_queue,
+            clear_queue,
+            shuffle_queue,
+            set_queue_settings,
+            skip_queued_song,
+            stop_queue,
+            set_queue_stop_after_current,
+            set_after_playback,
+            get_after_playback,
             stop_playback,
+            cancel_scheduled_playback,
+            get_playback_status,
+            get_session_stats,
+            get_last_playback_report,
+            seek_playback,
+            stop_accompaniment,
+            list_midi_outputs,
+            start_midi_clock,
+            stop_midi_clock,
+            start_link_sync,
+            stop_link_sync,
+            get_link_status,
+            get_emergency_stop,
+            set_emergency_stop,
+            export_config,
+            import_config,
+            pause_playback,
+            resume_playback,
             start_mouse_playback,
+            start_combined_playback,
             stop_mouse_playback,
+            pause_mouse_playback,
+            resume_mouse_playback,
             pick_mouse_coordinate,
+            pick_monitor_coordinate,
+            list_mouse_traces,
+            delete_mouse_trace,
+            start_mouse_trace_recording,
+            stop_mouse_trace_recording,
+            probe_input_backend,
+            run_diagnostics,
+            set_log_level,
+            get_log_level,
+            export_logs,
+            record_melody,
+            import_sheet_image,
+            import_from_url,
+            self_test,
             get_windows,
+            get_window_at_point,
+            pick_window,
+            get_window_display_mode,
+            set_window_topmost,
+            set_window_opacity,
             lock_window,
             unlock_window,
-            get_locked_window
+            get_locked_window,
+            lock_region,
+            pick_lock_region,
+            clear_lock_region,
+            get_lock_region,
+            get_child_windows,
+            lock_child_window,
+            get_locked_child_window,
+            check_locked_window,
+            get_profiles,
+            save_profile,
+            delete_profile,
+            set_active_profile,
+            get_active_profile,
+            list_keymaps,
+            get_keymap,
+            save_keymap,
+            delete_keymap,
+            validate_keymap,
+            get_game_presets,
+            apply_game_preset,
+            get_song_info,
+            set_song_tags,
+            set_song_rating,
+            set_track_label,
+            search_songs_by_tag,
+            get_song_tags,
+            get_recent_songs,
+            get_last_played_song,
+            get_favorites,
+            get_favorite_slot,
+            add_favorite,
+            remove_favorite
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
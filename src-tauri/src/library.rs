@@ -0,0 +1,309 @@
+use crate::json_store;
+use crate::midi_analyzer::{self, SongMetadata, TrackInfo, TrackLabel};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LIBRARY_FILE_NAME: &str = "library.json";
+const MAX_RATING: u8 = 5;
+const DEFAULT_RECENT_LIMIT: usize = 10;
+
+/// 曲库中的一首歌，以文件路径为键
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongEntry {
+    pub file_path: String,
+    pub metadata: SongMetadata,
+    pub tags: Vec<String>,
+    pub rating: Option<u8>,
+    pub play_count: u32,
+    pub last_played: Option<u64>, // Unix 时间戳（秒）
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct LibraryStore {
+    songs: HashMap<String, SongEntry>,
+    favorites: Vec<String>, // 收藏列表，顺序即快捷槽位
+    // 文件内容哈希 -> 音轨 id -> 标签，文件改名或移动后仍然有效
+    track_labels: HashMap<String, HashMap<usize, TrackLabel>>,
+}
+
+/// 曲库，保存在数据目录的 library.json 中
+pub struct Library {
+    store: RwLock<LibraryStore>,
+    path: PathBuf,
+}
+
+// 取出条目，不存在时从文件读取元数据并创建
+fn entry_mut<'a>(
+    store: &'a mut LibraryStore,
+    file_path: &str,
+) -> Result<&'a mut SongEntry, String> {
+    if !store.songs.contains_key(file_path) {
+        let metadata = midi_analyzer::read_song_metadata(file_path)?;
+        store.songs.insert(
+            file_path.to_string(),
+            SongEntry {
+                file_path: file_path.to_string(),
+                metadata,
+                ..Default::default()
+            },
+        );
+    }
+    Ok(store.songs.get_mut(file_path).unwrap())
+}
+
+/// 内容的 SHA-256（十六进制）
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn file_hash(file_path: &str) -> Result<String, String> {
+    let bytes = fs::read(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(content_hash(&bytes))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Library {
+    /// 从数据目录加载曲库
+    pub fn load(data_dir: PathBuf) -> Self {
+        let path = data_dir.join(LIBRARY_FILE_NAME);
+
+        let store = match json_store::load::<LibraryStore>(&path) {
+            Ok(Some(store)) => store,
+            Ok(None) => LibraryStore::default(),
+            Err(e) => {
+                log::error!("Failed to load library: {}", e);
+                LibraryStore::default()
+            }
+        };
+
+        Self {
+            store: RwLock::new(store),
+            path,
+        }
+    }
+
+    fn persist(&self, store: &LibraryStore) -> Result<(), String> {
+        json_store::save(&self.path, store)
+    }
+
+    /// 解析歌曲后更新曲库中的元数据
+    pub fn record_metadata(&self, file_path: &str, metadata: &SongMetadata) {
+        let mut store = self.store.write().unwrap();
+        let entry = store
+            .songs
+            .entry(file_path.to_string())
+            .or_insert_with(|| SongEntry {
+                file_path: file_path.to_string(),
+                ..Default::default()
+            });
+        entry.metadata = metadata.clone();
+
+        if let Err(e) = self.persist(&store) {
+            log::error!("Failed to save library: {}", e);
+        }
+    }
+
+    pub fn get_entry(&self, file_path: &str) -> Result<SongEntry, String> {
+        let mut store = self.store.write().unwrap();
+        let entry = entry_mut(&mut store, file_path)?.clone();
+        self.persist(&store)?;
+        Ok(entry)
+    }
+
+    /// 设置标签（去除空白和重复项）
+    pub fn set_tags(&self, file_path: &str, tags: Vec<String>) -> Result<SongEntry, String> {
+        let mut store = self.store.write().unwrap();
+        let entry = entry_mut(&mut store, file_path)?;
+
+        let mut normalized: Vec<String> = Vec::new();
+        for tag in tags {
+            let tag = tag.trim().to_string();
+            if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                normalized.push(tag);
+            }
+        }
+        entry.tags = normalized;
+
+        let entry = entry.clone();
+        self.persist(&store)?;
+        Ok(entry)
+    }
+
+    /// 设置评分（0-5），None 表示清除评分
+    pub fn set_rating(&self, file_path: &str, rating: Option<u8>) -> Result<SongEntry, String> {
+        if rating.is_some_and(|r| r > MAX_RATING) {
+            return Err(format!("Rating must be between 0 and {}", MAX_RATING));
+        }
+
+        let mut store = self.store.write().unwrap();
+        let entry = entry_mut(&mut store, file_path)?;
+        entry.rating = rating;
+
+        let entry = entry.clone();
+        self.persist(&store)?;
+        Ok(entry)
+    }
+
+    /// 按标签搜索（不区分大小写），结果按曲名排序
+    pub fn search_by_tag(&self, tag: &str) -> Vec<SongEntry> {
+        let tag = tag.trim();
+        let store = self.store.read().unwrap();
+        let mut result: Vec<SongEntry> = store
+            .songs
+            .values()
+            .filter(|s| s.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.metadata.title.cmp(&b.metadata.title));
+        result
+    }
+
+    /// 曲库中出现过的全部标签
+    pub fn list_tags(&self) -> Vec<String> {
+        let store = self.store.read().unwrap();
+        let tags: BTreeSet<String> = store
+            .songs
+            .values()
+            .flat_map(|s| s.tags.iter().cloned())
+            .collect();
+        tags.into_iter().collect()
+    }
+
+    /// 保存音轨的名称和颜色，两者都为空时删除
+    pub fn set_track_label(
+        &self,
+        file_path: &str,
+        track: usize,
+        label: TrackLabel,
+    ) -> Result<(), String> {
+        let name = label
+            .name
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+        let color = label.color.map(|c| c.trim().to_ascii_lowercase());
+        if let Some(color) = &color {
+            let valid = color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+            if !valid {
+                return Err(format!("Invalid color: {}", color));
+            }
+        }
+        let hash = file_hash(file_path)?;
+
+        let mut store = self.store.write().unwrap();
+        let labels = store.track_labels.entry(hash.clone()).or_default();
+        if name.is_none() && color.is_none() {
+            labels.remove(&track);
+            if labels.is_empty() {
+                store.track_labels.remove(&hash);
+            }
+        } else {
+            labels.insert(track, TrackLabel { name, color });
+        }
+        self.persist(&store)
+    }
+
+    /// 把保存的音轨标签填入分析结果，读取失败时保持原样
+    pub fn apply_track_labels(&self, file_path: &str, tracks: &mut [TrackInfo]) {
+        let store = self.store.read().unwrap();
+        if store.track_labels.is_empty() {
+            return;
+        }
+        let labels = match file_hash(file_path) {
+            Ok(hash) => store.track_labels.get(&hash),
+            Err(e) => {
+                log::warn!("Failed to load track labels: {}", e);
+                None
+            }
+        };
+        if let Some(labels) = labels {
+            for track in tracks {
+                track.label = labels.get(&track.id).cloned();
+            }
+        }
+    }
+
+    /// 记录一次播放
+    pub fn record_play(&self, file_path: &str) -> Result<(), String> {
+        let mut store = self.store.write().unwrap();
+        let entry = entry_mut(&mut store, file_path)?;
+        entry.play_count += 1;
+        entry.last_played = Some(now_secs());
+        self.persist(&store)
+    }
+
+    /// 最近播放的歌曲，按播放时间倒序
+    pub fn recent_songs(&self, limit: Option<usize>) -> Vec<SongEntry> {
+        let store = self.store.read().unwrap();
+        let mut result: Vec<SongEntry> = store
+            .songs
+            .values()
+            .filter(|s| s.last_played.is_some())
+            .cloned()
+            .collect();
+        result.sort_by_key(|s| std::cmp::Reverse(s.last_played));
+        result.truncate(limit.unwrap_or(DEFAULT_RECENT_LIMIT));
+        result
+    }
+
+    /// 收藏列表（按槽位顺序）
+    pub fn favorites(&self) -> Vec<SongEntry> {
+        let store = self.store.read().unwrap();
+        store
+            .favorites
+            .iter()
+            .filter_map(|path| store.songs.get(path).cloned())
+            .collect()
+    }
+
+    /// 按槽位（从 1 开始）获取收藏的歌曲
+    pub fn favorite_at(&self, slot: usize) -> Option<SongEntry> {
+        self.favorites().into_iter().nth(slot.checked_sub(1)?)
+    }
+
+    /// 添加收藏，可指定槽位（从 1 开始），已收藏时移动到新槽位
+    pub fn add_favorite(
+        &self,
+        file_path: &str,
+        slot: Option<usize>,
+    ) -> Result<Vec<SongEntry>, String> {
+        {
+            let mut store = self.store.write().unwrap();
+            entry_mut(&mut store, file_path)?;
+
+            store.favorites.retain(|p| p != file_path);
+            let index = slot
+                .map(|s| s.saturating_sub(1).min(store.favorites.len()))
+                .unwrap_or(store.favorites.len());
+            store.favorites.insert(index, file_path.to_string());
+            self.persist(&store)?;
+        }
+        Ok(self.favorites())
+    }
+
+    pub fn remove_favorite(&self, file_path: &str) -> Result<Vec<SongEntry>, String> {
+        {
+            let mut store = self.store.write().unwrap();
+            store.favorites.retain(|p| p != file_path);
+            self.persist(&store)?;
+        }
+        Ok(self.favorites())
+    }
+}
@@ -0,0 +1,562 @@
+use crate::score_import::{ImportedNote, ImportedSong, ImportedTrack, TICKS_PER_BEAT};
+
+// 支持的 LilyPond 子集：\relative 或绝对音高的旋律、\chordmode/\chords 和弦、
+// 和弦 <...>、连音线 ~、附点、休止符、\time、\tempo、\repeat 展开以及 \header 中的曲名和作曲者。
+// 每个音乐块作为一个音轨，都从乐曲开头开始。
+
+const VELOCITY: u8 = 90;
+// 不带八度符号的 c 为小字组 c（MIDI 48）
+const BASE_OCTAVE: i32 = 3;
+// 没有指定起始音高的 \relative 按 f 处理
+const DEFAULT_RELATIVE_STEP: i32 = BASE_OCTAVE * 7 + 3;
+// c d e f g a b 的自然音半音数
+const NATURAL_SEMITONES: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+// \repeat 最多展开的遍数，避免文件中过大的次数让导入卡住
+const MAX_REPEATS: usize = 64;
+
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(pos) = rest.find('%') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(block) = rest.strip_prefix("%{") {
+            rest = block.find("%}").map_or("", |end| &block[end + 2..]);
+        } else {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn tokenize(source: &str) -> Vec<String> {
+    let source = strip_comments(source);
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = source.chars().peekable();
+
+    let flush = |current: &mut String, tokens: &mut Vec<String>| {
+        if !current.is_empty() {
+            tokens.push(std::mem::take(current));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            // 字符串保留开头的引号，便于和普通单词区分
+            '"' => {
+                flush(&mut current, &mut tokens);
+                let mut text = String::from('"');
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                tokens.push(text);
+            }
+            '{' | '}' | '|' | '~' | '=' => {
+                flush(&mut current, &mut tokens);
+                tokens.push(c.to_string());
+            }
+            '<' | '>' => {
+                flush(&mut current, &mut tokens);
+                if chars.peek() == Some(&c) {
+                    chars.next();
+                    tokens.push(format!("{}{}", c, c));
+                } else {
+                    tokens.push(c.to_string());
+                }
+            }
+            c if c.is_whitespace() => flush(&mut current, &mut tokens),
+            c => current.push(c),
+        }
+    }
+    flush(&mut current, &mut tokens);
+    tokens
+}
+
+/// 解析出的音高：绝对音级（c 为 0 的全音阶序号）和半音变化
+struct Pitch {
+    step: i32,
+    accidental: i32,
+}
+
+impl Pitch {
+    fn key(&self) -> u8 {
+        let octave = self.step.div_euclid(7);
+        let semitone = NATURAL_SEMITONES[self.step.rem_euclid(7) as usize];
+        ((octave + 1) * 12 + semitone + self.accidental).clamp(0, 127) as u8
+    }
+}
+
+// 解析音名部分，返回 (音级 0-6, 半音变化, 八度符号数, 剩余文本)
+fn parse_pitch_name(text: &str) -> Option<(i32, i32, i32, &str)> {
+    let letter = text.chars().next()?;
+    let letter_step = match letter {
+        'c' => 0,
+        'd' => 1,
+        'e' => 2,
+        'f' => 3,
+        'g' => 4,
+        'a' => 5,
+        'b' => 6,
+        _ => return None,
+    };
+
+    let mut rest = &text[1..];
+    let mut accidental = 0;
+    // 荷兰语音名：is 为升，es 为降，a、e 的降号简写为 s（eses、ases 为重降）
+    if (letter == 'a' || letter == 'e') && rest.starts_with('s') {
+        accidental -= 1;
+        rest = &rest[1..];
+    }
+    loop {
+        if let Some(r) = rest.strip_prefix("is") {
+            accidental += 1;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("es") {
+            accidental -= 1;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+
+    let mut marks = 0;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\'' => marks += 1,
+            ',' => marks -= 1,
+            '!' | '?' => {}
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    Some((letter_step, accidental, marks, rest))
+}
+
+// 时值：数字为几分音符，后跟附点
+fn parse_duration(text: &str) -> Option<u32> {
+    let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let value: u32 = digits.parse().ok()?;
+    if !value.is_power_of_two() || value > 128 {
+        return None;
+    }
+
+    let base = TICKS_PER_BEAT as u32 * 4 / value;
+    let dots = text[digits.len()..]
+        .chars()
+        .take_while(|&c| c == '.')
+        .count();
+    let mut length = base;
+    let mut add = base;
+    for _ in 0..dots {
+        add /= 2;
+        length += add;
+    }
+    Some(length)
+}
+
+fn chord_intervals(modifier: &str) -> &'static [i32] {
+    match modifier {
+        "m" | "min" => &[0, 3, 7],
+        "7" | "dom7" => &[0, 4, 7, 10],
+        "maj7" | "maj" => &[0, 4, 7, 11],
+        "m7" | "min7" => &[0, 3, 7, 10],
+        "dim" => &[0, 3, 6],
+        "dim7" => &[0, 3, 6, 9],
+        "aug" => &[0, 4, 8],
+        "sus2" => &[0, 2, 7],
+        "sus4" | "sus" => &[0, 5, 7],
+        "m7.5-" => &[0, 3, 6, 10],
+        _ => &[0, 4, 7],
+    }
+}
+
+#[derive(Default)]
+struct Voice {
+    chord_mode: bool,
+    relative: Option<i32>, // 相对模式下的参考音级
+    tick: u32,
+    duration: u32,
+    notes: Vec<ImportedNote>,
+    last: Vec<usize>, // 上一个音或和弦中的音符，用于连音线
+    tie: bool,
+}
+
+impl Voice {
+    fn new(chord_mode: bool, relative: Option<i32>) -> Self {
+        Self {
+            chord_mode,
+            relative,
+            duration: TICKS_PER_BEAT as u32,
+            ..Default::default()
+        }
+    }
+
+    // 相对模式下取离参考音最近（不超过四度）的八度
+    fn resolve(&mut self, letter_step: i32, accidental: i32, marks: i32) -> Pitch {
+        let step = match self.relative {
+            Some(reference) => {
+                let mut diff = letter_step - reference.rem_euclid(7);
+                if diff > 3 {
+                    diff -= 7;
+                } else if diff < -3 {
+                    diff += 7;
+                }
+                let step = reference + diff + marks * 7;
+                self.relative = Some(step);
+                step
+            }
+            None => BASE_OCTAVE * 7 + letter_step + marks * 7,
+        };
+        Pitch { step, accidental }
+    }
+
+    fn set_duration(&mut self, text: &str) {
+        if let Some(length) = parse_duration(text) {
+            self.duration = length;
+        }
+    }
+
+    fn rest(&mut self) {
+        self.last.clear();
+        self.tie = false;
+        self.tick += self.duration;
+    }
+
+    fn play(&mut self, keys: &[u8]) {
+        let mut current = Vec::with_capacity(keys.len());
+        for &key in keys {
+            // 连音线连接的同音延长上一个音符
+            let tied = self.tie.then(|| {
+                self.last.iter().copied().find(|&i| {
+                    let note = &self.notes[i];
+                    note.key == key && note.tick + note.length == self.tick
+                })
+            });
+            match tied.flatten() {
+                Some(i) => {
+                    self.notes[i].length += self.duration;
+                    current.push(i);
+                }
+                None => {
+                    current.push(self.notes.len());
+                    self.notes.push(ImportedNote {
+                        tick: self.tick,
+                        length: self.duration,
+                        key,
+                        velocity: VELOCITY,
+                    });
+                }
+            }
+        }
+        self.last = current;
+        self.tie = false;
+        self.tick += self.duration;
+    }
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    song: ImportedSong,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn skip_block(&mut self) {
+        if self.peek() != Some("{") {
+            return;
+        }
+        let mut depth = 0;
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_header(&mut self) {
+        if self.peek() != Some("{") {
+            return;
+        }
+        self.next();
+        while let Some(token) = self.next() {
+            if token == "}" {
+                return;
+            }
+            if self.peek() == Some("=") {
+                self.next();
+                let value = self.next().unwrap_or_default();
+                let value = value.trim_start_matches('"').trim().to_string();
+                match token.as_str() {
+                    "title" => self.song.title = Some(value),
+                    "composer" => self.song.composer = Some(value),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn parse_top(&mut self) {
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "\\header" => self.parse_header(),
+                "\\layout" | "\\paper" | "\\midi" | "\\with" => self.skip_block(),
+                "\\version" | "\\include" | "\\language" => {
+                    self.next();
+                }
+                "\\relative" => {
+                    let reference = self.parse_relative_start();
+                    self.parse_track(Voice::new(false, Some(reference)));
+                }
+                "\\chordmode" | "\\chords" => self.parse_track(Voice::new(true, None)),
+                "{" => {
+                    self.pos -= 1;
+                    self.parse_track(Voice::new(false, None));
+                }
+                // \score 和 \new 只是容器，继续扫描其中的音乐块
+                "\\new" | "\\context" => {
+                    self.next();
+                    if self.peek() == Some("=") {
+                        self.next();
+                        self.next();
+                    }
+                }
+                "\\score" | "\\book" if self.peek() == Some("{") => {
+                    self.next();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_relative_start(&mut self) -> i32 {
+        match self.peek().and_then(parse_pitch_name) {
+            Some((letter_step, _, marks, _)) => {
+                self.next();
+                BASE_OCTAVE * 7 + letter_step + marks * 7
+            }
+            None => DEFAULT_RELATIVE_STEP,
+        }
+    }
+
+    fn parse_track(&mut self, mut voice: Voice) {
+        if self.peek() != Some("{") {
+            return;
+        }
+        self.parse_sequence(&mut voice);
+        if voice.notes.is_empty() {
+            return;
+        }
+
+        let index = self.song.tracks.len();
+        self.song.tracks.push(ImportedTrack {
+            name: if voice.chord_mode {
+                "Chords".to_string()
+            } else {
+                format!("Voice {}", index + 1)
+            },
+            channel: index.min(15) as u8,
+            notes: voice.notes,
+        });
+    }
+
+    fn parse_sequence(&mut self, voice: &mut Voice) {
+        // 跳过开头的 {
+        self.next();
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "}" => return,
+                "{" => {
+                    self.pos -= 1;
+                    self.parse_sequence(voice);
+                }
+                "~" => voice.tie = true,
+                "<" => self.parse_chord(voice),
+                "\\time" => self.parse_time(voice.tick),
+                "\\tempo" => self.parse_tempo(voice.tick),
+                "\\key" => {
+                    self.next();
+                    self.next();
+                }
+                "\\clef" | "\\bar" | "\\partial" | "\\set" => {
+                    self.next();
+                }
+                "\\relative" => voice.relative = Some(self.parse_relative_start()),
+                "\\repeat" => self.parse_repeat(voice),
+                t if t.starts_with('\\') || t.starts_with('"') => {}
+                t if voice.chord_mode => self.parse_chord_symbol(voice, t),
+                t => self.parse_note(voice, t),
+            }
+        }
+    }
+
+    // \repeat volta|unfold N { ... } 展开为 N 遍
+    fn parse_repeat(&mut self, voice: &mut Voice) {
+        self.next();
+        let mut times: usize = self.next().and_then(|t| t.parse().ok()).unwrap_or(1);
+        if times > MAX_REPEATS {
+            log::warn!("Repeat count {} clamped to {}", times, MAX_REPEATS);
+            times = MAX_REPEATS;
+        }
+        if self.peek() != Some("{") {
+            return;
+        }
+        let start = self.pos;
+        for _ in 0..times.max(1) {
+            self.pos = start;
+            self.parse_sequence(voice);
+        }
+    }
+
+    fn parse_time(&mut self, tick: u32) {
+        let Some(signature) = self.next() else {
+            return;
+        };
+        if let Some((num, den)) = signature.split_once('/') {
+            if let (Ok(num), Ok(den)) = (num.parse(), den.parse()) {
+                self.song.time_signatures.push((tick, num, den));
+            }
+        }
+    }
+
+    // \tempo "Allegro" 4 = 120，速度换算为每分钟四分音符数
+    fn parse_tempo(&mut self, tick: u32) {
+        if self.peek().is_some_and(|t| t.starts_with('"')) {
+            self.next();
+        }
+        let Some(unit) = self.peek().and_then(parse_duration) else {
+            return;
+        };
+        self.next();
+        if self.peek() != Some("=") {
+            return;
+        }
+        self.next();
+        let bpm: Option<f64> = self
+            .next()
+            .and_then(|t| t.split('-').next().and_then(|b| b.parse().ok()));
+        if let Some(bpm) = bpm {
+            let quarter_bpm = bpm * unit as f64 / TICKS_PER_BEAT as f64;
+            self.song.tempo_changes.push((tick, quarter_bpm));
+        }
+    }
+
+    fn parse_note(&mut self, voice: &mut Voice, token: &str) {
+        // 去掉附在音符后的演奏记号和力度，如 c4-. 或 c4\p
+        let end = token
+            .find(['\\', '-', '_', '^', '('])
+            .unwrap_or(token.len());
+        let token = &token[..end];
+        if token.is_empty() {
+            return;
+        }
+
+        if let Some(rest) = token
+            .strip_prefix('r')
+            .or_else(|| token.strip_prefix('s'))
+            .or_else(|| token.strip_prefix('R'))
+        {
+            voice.set_duration(rest);
+            voice.rest();
+            return;
+        }
+
+        if let Some((letter_step, accidental, marks, rest)) = parse_pitch_name(token) {
+            let key = voice.resolve(letter_step, accidental, marks).key();
+            voice.set_duration(rest);
+            voice.play(&[key]);
+        }
+    }
+
+    // <c e g>4：和弦内的音依次相对前一个音，和弦之后以第一个音为参考
+    fn parse_chord(&mut self, voice: &mut Voice) {
+        let mut keys = Vec::new();
+        let mut first_step = None;
+        while let Some(token) = self.next() {
+            if token == ">" {
+                break;
+            }
+            if let Some((letter_step, accidental, marks, _)) = parse_pitch_name(&token) {
+                let pitch = voice.resolve(letter_step, accidental, marks);
+                first_step.get_or_insert(pitch.step);
+                keys.push(pitch.key());
+            }
+        }
+        if voice.relative.is_some() {
+            voice.relative = first_step.or(voice.relative);
+        }
+
+        if self
+            .peek()
+            .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let duration = self.next().unwrap_or_default();
+            voice.set_duration(&duration);
+        }
+        if !keys.is_empty() {
+            voice.play(&keys);
+        }
+    }
+
+    // 和弦模式：c1:m7、g:7、f2/a（低音转位忽略）
+    fn parse_chord_symbol(&mut self, voice: &mut Voice, token: &str) {
+        let token = token.split('/').next().unwrap_or_default();
+        let (head, modifier) = token.split_once(':').unwrap_or((token, ""));
+
+        if let Some(rest) = head.strip_prefix('r').or_else(|| head.strip_prefix('s')) {
+            voice.set_duration(rest);
+            voice.rest();
+            return;
+        }
+
+        if let Some((letter_step, accidental, marks, rest)) = parse_pitch_name(head) {
+            let root = Pitch {
+                step: BASE_OCTAVE * 7 + letter_step + marks * 7,
+                accidental,
+            }
+            .key();
+            let keys: Vec<u8> = chord_intervals(modifier)
+                .iter()
+                .map(|i| (root as i32 + i).clamp(0, 127) as u8)
+                .collect();
+            voice.set_duration(rest);
+            voice.play(&keys);
+        }
+    }
+}
+
+/// 解析 LilyPond 源文件
+pub fn parse(source: &str) -> Result<ImportedSong, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source),
+        pos: 0,
+        song: ImportedSong::default(),
+    };
+    parser.parse_top();
+
+    if parser.song.tracks.is_empty() {
+        return Err("No music found in LilyPond file".to_string());
+    }
+    Ok(parser.song)
+}
@@ -0,0 +1,229 @@
+use crate::error::AppError;
+use crate::state::PlaybackControl;
+use rusty_link::{AblLink, SessionState};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Ableton Link 速度同步：与局域网内的其他 Link 应用（DAW、鼓机、一起演奏的乐手）共享速度和节拍相位。
+// 跟随时按会话速度调整键盘播放的速率，并微调速率让乐曲的拍对齐会话的拍；
+// 主导时把乐曲的速度和当前拍位置发布到会话中。乐曲的第一拍对齐会话的小节开头。
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+// 相位差每拍对应的速率微调比例，以及微调的上限
+const PHASE_GAIN: f64 = 0.1;
+const MAX_NUDGE: f64 = 0.05;
+// 相位差超过这么多拍时直接跳转对齐，不再慢慢追赶
+const SNAP_BEATS: f64 = 0.25;
+// 播放位置变化与经过的时间相差这么多秒时视为跳转
+const SEEK_TOLERANCE_SECS: f64 = 0.1;
+
+fn default_quantum() -> f64 {
+    4.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkRole {
+    Follow, // 播放跟随会话的速度和相位
+    Lead,   // 会话跟随播放
+}
+
+/// 同步设置
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkSync {
+    pub role: LinkRole,
+    pub song_bpm: f64, // 乐曲的原速
+    #[serde(default = "default_quantum")]
+    pub quantum: f64, // 每小节的拍数，按小节对齐相位
+}
+
+/// Link 的当前状态
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStatus {
+    pub enabled: bool,
+    pub peers: u64,
+    pub tempo: f64,
+    pub role: Option<LinkRole>, // 正在同步时的角色
+}
+
+type Running = (Arc<AtomicBool>, thread::JoinHandle<()>, LinkRole);
+
+/// Link 实例和同步线程。实例在第一次同步时创建，之后保持启用，
+/// 下一首不用重新发现其他设备
+#[derive(Default)]
+pub struct TempoLink {
+    link: Mutex<Option<Arc<AblLink>>>,
+    running: Mutex<Option<Running>>,
+}
+
+impl TempoLink {
+    fn link(&self, bpm: f64) -> Arc<AblLink> {
+        let mut link = self.link.lock().unwrap();
+        let link = link.get_or_insert_with(|| {
+            let link = AblLink::new(bpm);
+            link.enable_start_stop_sync(true);
+            Arc::new(link)
+        });
+        link.enable(true);
+        Arc::clone(link)
+    }
+
+    /// 跟随 playback 的播放同步，播放结束时同步线程随之结束
+    pub fn start(&self, playback: Arc<PlaybackControl>, sync: LinkSync) -> Result<(), AppError> {
+        if !sync.song_bpm.is_finite() || sync.song_bpm <= 0.0 {
+            return Err(AppError::invalid(format!(
+                "Invalid song tempo: {}",
+                sync.song_bpm
+            )));
+        }
+        if !sync.quantum.is_finite() || sync.quantum <= 0.0 {
+            return Err(AppError::invalid(format!(
+                "Invalid Link quantum: {}",
+                sync.quantum
+            )));
+        }
+        self.stop();
+        let link = self.link(sync.song_bpm);
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let role = sync.role;
+        log::info!("Link sync started as {:?} at {} BPM", role, sync.song_bpm);
+        let handle = thread::spawn(move || {
+            match sync.role {
+                LinkRole::Follow => follow(&link, &playback, &sync, &flag),
+                LinkRole::Lead => lead(&link, &playback, &sync, &flag),
+            }
+            // 跟随结束后恢复原速
+            let _ = playback.set_rate(None);
+        });
+        *self.running.lock().unwrap() = Some((stop, handle, role));
+        Ok(())
+    }
+
+    /// 停止同步，播放恢复原速
+    pub fn stop(&self) {
+        let running = self.running.lock().unwrap().take();
+        if let Some((stop, handle, _)) = running {
+            stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+    }
+
+    /// 停止同步并离开会话
+    pub fn disable(&self) {
+        self.stop();
+        if let Some(link) = self.link.lock().unwrap().as_ref() {
+            link.enable(false);
+        }
+    }
+
+    pub fn status(&self) -> LinkStatus {
+        let role = self
+            .running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(_, handle, _)| !handle.is_finished())
+            .map(|(_, _, role)| *role);
+        let link = self.link.lock().unwrap();
+        let Some(link) = link.as_ref().filter(|link| link.is_enabled()) else {
+            return LinkStatus {
+                enabled: false,
+                peers: 0,
+                tempo: 0.0,
+                role,
+            };
+        };
+        let mut session = SessionState::new();
+        link.capture_app_session_state(&mut session);
+        LinkStatus {
+            enabled: true,
+            peers: link.num_peers(),
+            tempo: session.tempo(),
+            role,
+        }
+    }
+}
+
+// 会话的拍位置减去乐曲的拍位置，折到 (-quantum/2, quantum/2]
+fn phase_error(link_beat: f64, song_beat: f64, quantum: f64) -> f64 {
+    let diff = (link_beat - song_beat).rem_euclid(quantum);
+    if diff > quantum / 2.0 {
+        diff - quantum
+    } else {
+        diff
+    }
+}
+
+fn follow(link: &AblLink, playback: &PlaybackControl, sync: &LinkSync, stop: &AtomicBool) {
+    let mut session = SessionState::new();
+    // 播放线程可能还在倒计时，is_playing 此时已为 true
+    while !stop.load(Ordering::SeqCst) && playback.is_playing() {
+        link.capture_app_session_state(&mut session);
+        let now = link.clock_micros();
+        let base = session.tempo() / sync.song_bpm;
+
+        let mut rate = base;
+        if let Some(position) = playback.position().filter(|_| !playback.is_paused()) {
+            let song_beat = position * sync.song_bpm / 60.0;
+            let error = phase_error(
+                session.beat_at_time(now, sync.quantum),
+                song_beat,
+                sync.quantum,
+            );
+            let target = position + error * 60.0 / sync.song_bpm;
+            if error.abs() > SNAP_BEATS && target >= 0.0 {
+                let _ = playback.seek(target);
+            } else {
+                rate = base * (1.0 + (error * PHASE_GAIN).clamp(-MAX_NUDGE, MAX_NUDGE));
+            }
+        }
+        if let Err(e) = playback.set_rate(Some(rate)) {
+            log::warn!("Link tempo ignored: {}", e);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn lead(link: &AblLink, playback: &PlaybackControl, sync: &LinkSync, stop: &AtomicBool) {
+    let mut session = SessionState::new();
+    // 上次发布时的 (播放位置, Link 时钟)，None 表示会话处于停止状态
+    let mut published: Option<(f64, i64)> = None;
+    while !stop.load(Ordering::SeqCst) && playback.is_playing() {
+        let now = link.clock_micros();
+        let position = playback.position().filter(|_| !playback.is_paused());
+        let jumped = match (position, published) {
+            (Some(position), Some((last, at))) => {
+                let expected = last + (now - at) as f64 / 1_000_000.0;
+                (position - expected).abs() > SEEK_TOLERANCE_SECS
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if jumped || (position.is_none() && published.is_some()) {
+            link.capture_app_session_state(&mut session);
+            session.set_tempo(sync.song_bpm, now);
+            match position {
+                Some(position) => {
+                    let beat = position * sync.song_bpm / 60.0;
+                    session.force_beat_at_time(beat, now.max(0) as u64, sync.quantum);
+                    session.set_is_playing(true, now.max(0) as u64);
+                }
+                None => session.set_is_playing(false, now.max(0) as u64),
+            }
+            link.commit_app_session_state(&session);
+        }
+        published = position.map(|position| (position, now));
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    if published.is_some() {
+        link.capture_app_session_state(&mut session);
+        session.set_is_playing(false, link.clock_micros().max(0) as u64);
+        link.commit_app_session_state(&session);
+    }
+}
@@ -0,0 +1,98 @@
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 每个日志文件最多导出末尾这么多字节，避免报告过大
+const MAX_EXPORT_BYTES_PER_FILE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// 运行时调整日志级别（Rust 侧和前端通过插件写入的日志都受影响）
+pub fn set_level(level: LogLevel) {
+    log::set_max_level(level.into());
+    log::info!("Log level set to {:?}", level);
+}
+
+pub fn level() -> LogLevel {
+    match log::max_level() {
+        LevelFilter::Off | LevelFilter::Error => LogLevel::Error,
+        LevelFilter::Warn => LogLevel::Warn,
+        LevelFilter::Info => LogLevel::Info,
+        LevelFilter::Debug => LogLevel::Debug,
+        LevelFilter::Trace => LogLevel::Trace,
+    }
+}
+
+// 读取文件末尾最多 max_bytes 字节
+fn read_tail(path: &Path, max_bytes: u64) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let start = bytes.len().saturating_sub(max_bytes as usize);
+    Ok(String::from_utf8_lossy(&bytes[start..]).into_owned())
+}
+
+/// 把日志目录中的日志合并为一个文件，用于提交问题报告
+/// 未指定目标路径时保存在日志目录中，返回导出文件的路径
+pub fn export(log_dir: &Path, target: Option<PathBuf>) -> Result<PathBuf, String> {
+    let mut log_files: Vec<(SystemTime, PathBuf)> = fs::read_dir(log_dir)
+        .map_err(|e| format!("Failed to read log directory: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH);
+            (modified, path)
+        })
+        .collect();
+    log_files.sort();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut report = format!(
+        "OpenGamesAutoPlay {}\nOS: {} {}\nExported at: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        now
+    );
+    for (_, path) in &log_files {
+        report.push_str(&format!("\n===== {} =====\n", path.display()));
+        report.push_str(&read_tail(path, MAX_EXPORT_BYTES_PER_FILE)?);
+    }
+
+    let target = target.unwrap_or_else(|| log_dir.join(format!("bug-report-{}.txt", now)));
+    fs::write(&target, report)
+        .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    log::info!(
+        "Exported {} log files to {}",
+        log_files.len(),
+        target.display()
+    );
+    Ok(target)
+}
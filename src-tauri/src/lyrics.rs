@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+// 歌词提取：标准 MIDI 用歌词事件，每个事件一个音节，换行写作音节末尾的回车或换行符；
+// 卡拉 OK 文件（.kar）把音节写在文本事件中，"/" 开头表示换行、"\" 开头表示换段，
+// "@" 开头的是曲名、语言等信息。文件中有歌词事件时只用歌词事件，否则只在文本事件像卡拉 OK 时使用。
+
+// 没有 "@" 信息时，文本事件至少要有这么多个才当作歌词，避免把普通的注释当成歌词
+const MIN_KARAOKE_TEXTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricSource {
+    Lyric, // 歌词元事件
+    Text,  // 文本元事件
+}
+
+/// 解析时收集的原始事件
+pub struct RawLyric<'a> {
+    pub tick: u32,
+    pub track: usize,
+    pub source: LyricSource,
+    pub bytes: &'a [u8],
+}
+
+/// 一个带时间的歌词音节，音节中的空格原样保留，前端直接拼接即可
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lyric {
+    pub time: f64,
+    pub track: usize,
+    pub text: String,
+    pub new_line: bool,      // 从这个音节开始新的一行
+    pub new_paragraph: bool, // 从这个音节开始新的一段
+}
+
+/// 按时间整理歌词音节
+pub fn assemble(mut raw: Vec<RawLyric>, tick_to_seconds: &dyn Fn(u32) -> f64) -> Vec<Lyric> {
+    let source = if raw.iter().any(|r| r.source == LyricSource::Lyric) {
+        LyricSource::Lyric
+    } else {
+        LyricSource::Text
+    };
+    raw.retain(|r| r.source == source);
+    if source == LyricSource::Text
+        && raw.len() < MIN_KARAOKE_TEXTS
+        && !raw.iter().any(|r| r.bytes.starts_with(b"@"))
+    {
+        return Vec::new();
+    }
+    raw.sort_by_key(|r| (r.tick, r.track));
+
+    let mut lyrics = Vec::new();
+    // 上一个音节以换行结尾，下一个音节开始新的一行
+    let (mut line_pending, mut paragraph_pending) = (true, false);
+    for r in raw {
+        let text = String::from_utf8_lossy(r.bytes);
+        let mut text: &str = &text;
+        if source == LyricSource::Text {
+            if text.starts_with('@') {
+                continue;
+            }
+            if let Some(rest) = text.strip_prefix('\\') {
+                (line_pending, paragraph_pending) = (true, true);
+                text = rest;
+            } else if let Some(rest) = text.strip_prefix('/') {
+                line_pending = true;
+                text = rest;
+            }
+        }
+        // 换行符写在音节前面时换行从这个音节开始，写在后面时从下一个音节开始
+        let body = text.trim_start_matches(['\r', '\n']);
+        if body.len() < text.len() {
+            line_pending = true;
+        }
+        let syllable = body.trim_end_matches(['\r', '\n']);
+        if !syllable.is_empty() {
+            lyrics.push(Lyric {
+                time: tick_to_seconds(r.tick),
+                track: r.track,
+                text: syllable.to_string(),
+                new_line: line_pending,
+                new_paragraph: paragraph_pending,
+            });
+            (line_pending, paragraph_pending) = (false, false);
+        }
+        if syllable.len() < body.len() {
+            line_pending = true;
+        }
+    }
+    lyrics
+}
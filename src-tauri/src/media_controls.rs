@@ -0,0 +1,140 @@
+use crate::session_stats::SessionStats;
+use crate::state::AppState;
+use souvlaki::{
+    MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
+};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// 把正在播放的曲目同步到系统媒体控制（Windows SMTC、macOS 正在播放、Linux MPRIS），
+// 键盘媒体键和系统媒体面板的播放/暂停/停止可以直接控制播放。
+
+const DISPLAY_NAME: &str = "OpenGamesAutoPlay";
+const DBUS_NAME: &str = "opengamesautoplay";
+
+enum Update {
+    Playing {
+        title: String,
+        paused: bool,
+        position: Duration,
+    },
+    Stopped,
+}
+
+// 系统媒体控制对象不一定能跨线程使用，由专用线程持有，这里只保存发送端
+static UPDATES: Mutex<Option<Sender<Update>>> = Mutex::new(None);
+
+/// 注册系统媒体控制，失败时只记录日志
+pub fn install(app: &AppHandle) {
+    // Windows 的 SMTC 需要关联主窗口
+    #[cfg(windows)]
+    let hwnd = app
+        .get_webview_window("main")
+        .and_then(|window| window.hwnd().ok())
+        .map(|hwnd| hwnd.0 as usize);
+    #[cfg(not(windows))]
+    let hwnd: Option<usize> = None;
+
+    let (sender, receiver) = mpsc::channel();
+    *UPDATES.lock().unwrap() = Some(sender);
+
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut controls = match create(app, hwnd) {
+            Ok(controls) => controls,
+            Err(e) => {
+                log::warn!("Failed to register system media controls: {}", e);
+                UPDATES.lock().unwrap().take();
+                return;
+            }
+        };
+        for update in receiver {
+            let result = match update {
+                Update::Playing {
+                    title,
+                    paused,
+                    position,
+                } => {
+                    let progress = Some(MediaPosition(position));
+                    let playback = if paused {
+                        MediaPlayback::Paused { progress }
+                    } else {
+                        MediaPlayback::Playing { progress }
+                    };
+                    controls
+                        .set_metadata(MediaMetadata {
+                            title: Some(&title),
+                            ..Default::default()
+                        })
+                        .and_then(|_| controls.set_playback(playback))
+                }
+                Update::Stopped => controls.set_playback(MediaPlayback::Stopped),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to update system media controls: {:?}", e);
+            }
+        }
+    });
+}
+
+fn create(app: AppHandle, hwnd: Option<usize>) -> Result<MediaControls, String> {
+    let config = PlatformConfig {
+        dbus_name: DBUS_NAME,
+        display_name: DISPLAY_NAME,
+        hwnd: hwnd.map(|h| h as *mut std::ffi::c_void),
+    };
+    let mut controls = MediaControls::new(config).map_err(|e| format!("{:?}", e))?;
+    controls
+        .attach(move |event| handle_event(&app, event))
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(controls)
+}
+
+fn handle_event(app: &AppHandle, event: MediaControlEvent) {
+    let state = app.state::<AppState>();
+    match event {
+        MediaControlEvent::Play => state.resume_all(),
+        MediaControlEvent::Pause => state.pause_all(),
+        MediaControlEvent::Toggle if state.is_any_paused() => state.resume_all(),
+        MediaControlEvent::Toggle => state.pause_all(),
+        MediaControlEvent::Stop => state.stop_all(),
+        MediaControlEvent::Next => state.skip_queued_song(),
+        _ => {}
+    }
+}
+
+fn send(update: Update) {
+    if let Some(sender) = UPDATES.lock().unwrap().as_ref() {
+        let _ = sender.send(update);
+    }
+}
+
+/// 用最新的播放统计更新系统媒体面板
+pub fn update(stats: &SessionStats) {
+    let status = if stats.keyboard.running {
+        &stats.keyboard
+    } else {
+        &stats.mouse
+    };
+    if !status.running {
+        send(Update::Stopped);
+        return;
+    }
+    send(Update::Playing {
+        title: stats
+            .now_playing
+            .title
+            .clone()
+            .unwrap_or_else(|| DISPLAY_NAME.to_string()),
+        paused: status.paused,
+        position: Duration::from_secs_f64(status.position_secs),
+    });
+}
+
+/// 播放全部结束
+pub fn clear() {
+    send(Update::Stopped);
+}
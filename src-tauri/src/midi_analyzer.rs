@@ -1,3 +1,14 @@
+use crate::arrange::{
+    self, AutoTrim, FoldMode, GrooveTransfer, LongNoteTrim, MelodyLine, NoteMerge, Quantize,
+    ReleaseEarly, Simplify, TrimmedRange, VelocityCompression,
+};
+use crate::chord::{self, ChordLabel};
+use crate::error::AppError;
+use crate::guitar_pro;
+use crate::key_detect::{self, DetectedKey};
+use crate::lilypond;
+use crate::lyrics::{self, Lyric, LyricSource, RawLyric};
+use crate::warning::Warning;
 use midly::{MidiMessage, Smf, TrackEventKind};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -5,9 +16,12 @@ use std::fs;
 use std::path::Path;
 
 // Black and white key pitch classes (matching Python implementation)
-const BLACK_PCS: [u8; 5] = [1, 3, 6, 8, 10]; // C#, D#, F#, G#, A#
+pub const BLACK_PCS: [u8; 5] = [1, 3, 6, 8, 10]; // C#, D#, F#, G#, A#
 const WHITE_PCS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11]; // C, D, E, F, G, A, B
 
+// General MIDI 的第 10 通道固定为打击乐，音高表示鼓的种类而不是音高
+const PERCUSSION_CHANNEL: u8 = 9;
+
 /// Find the nearest white key pitch class for a given pitch class
 /// Uses "nearest" strategy: finds the white key with minimum absolute distance
 fn nearest_white_pc(pc: u8) -> u8 {
@@ -49,6 +63,24 @@ pub struct MidiEvent {
     pub end: f64,
 }
 
+/// 控制事件：弯音、控制器（CC）和通道触后，与音符分开返回，不参与音符的转换
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ControlEvent {
+    pub time: f64,
+    pub track: usize,
+    pub channel: u8,
+    #[serde(flatten)]
+    pub kind: ControlKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlKind {
+    PitchBend { value: f64 },                 // -1.0 到 1.0，0 为不弯音
+    Controller { controller: u8, value: u8 }, // 如 1 为调制轮、64 为延音踏板
+    ChannelPressure { value: u8 },
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AnalysisResult {
     pub min_note: Option<u8>,
@@ -58,6 +90,10 @@ pub struct AnalysisResult {
     pub min_note_name: String,
     pub max_note_name: String,
     pub total_over_limit_count: usize,
+    #[serde(default)]
+    pub key: Option<DetectedKey>, // 原曲的调性（移调前）
+    #[serde(default)]
+    pub auto_transpose: Option<i32>, // 自动移调到 C 大调时选择的半音数
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -66,8 +102,10 @@ pub struct TrackAnalysis {
     pub min_note: Option<u8>,
     pub max_note_name: String,
     pub min_note_name: String,
-    pub max_note_group: String,
+    pub max_note_group: String, // 按所选命名方式生成，前端本地化时使用 max_group
     pub min_note_group: String,
+    pub max_group: Option<NoteGroupInfo>,
+    pub min_group: Option<NoteGroupInfo>,
     pub upper_over_limit: usize,
     pub lower_over_limit: usize,
     pub is_max_over_limit: bool,
@@ -84,6 +122,146 @@ pub struct TrackInfo {
     pub name: String,
     pub note_count: usize,
     pub analysis: TrackAnalysis,
+    pub label: Option<TrackLabel>, // 用户保存的名称和颜色
+    #[serde(default)]
+    pub percussion: bool, // 音符都在第 10 通道（打击乐）
+}
+
+/// 用户给音轨起的名称和颜色，按文件内容保存在曲库中
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TrackLabel {
+    pub name: Option<String>,
+    pub color: Option<String>, // "#rrggbb"
+}
+
+/// 黑键（半音）的处理方式，游戏不支持半音时需要转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlackKeyMode {
+    #[default]
+    SupportBlackKey, // 原样保留
+    AutoSharp,     // 移到最近的白键
+    Skip,          // 去掉黑键音符
+    RoundUp,       // 升到上方的白键
+    RoundDown,     // 降到下方的白键
+    ShiftModifier, // 保留黑键，按键时用 shift + 下方白键的按键
+}
+
+impl BlackKeyMode {
+    /// 转换后的音高，去掉时返回 None
+    pub fn apply(self, note: u8) -> Option<u8> {
+        let pc = note % 12;
+        if !BLACK_PCS.contains(&pc) {
+            return Some(note);
+        }
+        match self {
+            Self::SupportBlackKey | Self::ShiftModifier => Some(note),
+            // 保持八度，只换音名
+            Self::AutoSharp => Some(note - pc + nearest_white_pc(pc)),
+            Self::Skip => None,
+            Self::RoundUp => Some(note + 1),
+            Self::RoundDown => Some(note - 1),
+        }
+    }
+}
+
+/// 单个音轨的移调（半音）、转位（八度）和延后（毫秒，负数为提前）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct TrackShift {
+    pub transpose: i32,
+    pub octave: i32,
+    pub delay_ms: f64,
+}
+
+impl TrackShift {
+    pub fn semitones(&self) -> i32 {
+        self.transpose + self.octave * 12
+    }
+
+    /// 移动后的音高，限制在 MIDI 范围内
+    pub fn apply(&self, note: u8) -> u8 {
+        (note as i32 + self.semitones()).clamp(0, 127) as u8
+    }
+}
+
+/// 改写速度表：按倍数整体变速，或固定为一个速度（忽略文件中的变速）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TempoOverride {
+    Scale(f64), // 0.5 为半速，2.0 为两倍速
+    Bpm(f64),
+}
+
+impl TempoOverride {
+    fn validate(self) -> Result<Self, AppError> {
+        let (Self::Scale(value) | Self::Bpm(value)) = self;
+        if !value.is_finite() || value <= 0.0 {
+            return Err(AppError::invalid(format!(
+                "Invalid tempo override: {:?}",
+                self
+            )));
+        }
+        Ok(self)
+    }
+
+    // 改写 (tick, 每拍微秒数) 速度表
+    fn apply(self, tempo_changes: &mut Vec<(u32, u32)>) {
+        match self {
+            Self::Scale(factor) => {
+                for (_, tempo) in tempo_changes.iter_mut() {
+                    *tempo = (*tempo as f64 / factor).round().max(1.0) as u32;
+                }
+            }
+            Self::Bpm(bpm) => {
+                *tempo_changes = vec![(0, (60_000_000.0 / bpm).round().max(1.0) as u32)];
+            }
+        }
+    }
+}
+
+/// 解析 MIDI 时的转换选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyzeOptions {
+    pub min_note: u8,
+    pub max_note: u8,
+    pub black_key_mode: BlackKeyMode,
+    pub trim_long_notes: bool,
+    pub long_notes: Option<LongNoteTrim>, // 长音的截短和重复按下，设置后忽略 trim_long_notes
+    pub naming: NoteNaming,
+    pub track_shifts: HashMap<usize, TrackShift>,
+    pub tracks: Option<Vec<usize>>, // 只保留这些音轨的音符（独奏、静音），不设置时保留全部
+    pub channels: Option<Vec<u8>>,  // 只保留这些通道（0-15）的音符，不设置时保留全部
+    pub exclude_channels: Vec<u8>,  // 去掉这些通道的音符
+    pub keep_percussion: bool,      // 未指定通道时保留第 10 通道的打击乐，默认去掉
+    pub transpose: i32,             // 整首移调（半音），与音轨移调相加
+    pub tempo: Option<TempoOverride>, // 换算时间前改写速度表
+    pub auto_fit_to_c_major: bool,  // 在移调之后再移动到黑键最少的调
+    pub octave_shift: i32,          // 整首转位（八度）
+    pub channel_delays_ms: HashMap<u8, f64>, // 按通道延后的毫秒数，与音轨延后相加
+    pub fold_mode: FoldMode,
+    pub extract_melody: Option<MelodyLine>, // 只保留最高（或最低）的一条旋律线
+    pub reduce_harmony: bool,
+    pub simplify: Option<Simplify>, // 去掉装饰音、限制复音数
+    pub quantize: Option<Quantize>, // 按拍线量化按下时间，在律动套用之前进行
+    pub groove: Option<GrooveTransfer>,
+    pub release_early: Option<ReleaseEarly>,
+    pub import_track: Option<usize>, // Guitar Pro 文件中要导入的音轨
+    pub max_gap_secs: Option<f64>,   // 超过该时长的静默缩短到该时长
+    pub auto_trim: Option<AutoTrim>,
+    pub velocity: Option<VelocityCompression>,
+    pub merge_duplicates: Option<NoteMerge>, // 合并多个音轨中同时出现的同一个音
+    pub min_release_gap_ms: Option<f64>,     // 同一个音再次按下前至少松开这么久
+    pub include_controls: bool, // 返回弯音和控制器事件，事件很多时会增大结果，默认不返回
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SongMetadata {
+    pub title: String,
+    pub composer: Option<String>,
+    pub copyright: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -91,6 +269,101 @@ pub struct MidiAnalysis {
     pub events: Vec<MidiEvent>,
     pub analysis: AnalysisResult,
     pub tracks: Vec<TrackInfo>,
+    pub metadata: SongMetadata,
+    pub warnings: Vec<Warning>,
+    pub chords: Vec<ChordLabel>,
+    pub beats: Vec<BeatMark>,
+    // 自动裁剪掉的时间段（原时间轴），不裁剪时为空
+    #[serde(default)]
+    pub trimmed: Vec<TrimmedRange>,
+    // 弯音和控制器事件，按时间排序，与拍线一样跟随裁剪、压缩静默和延后
+    #[serde(default)]
+    pub controls: Vec<ControlEvent>,
+    // 歌词音节，按时间排序，包括未选中音轨中的歌词
+    #[serde(default)]
+    pub lyrics: Vec<Lyric>,
+}
+
+/// 拍线，beat 为 1 时即小节线
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BeatMark {
+    pub time: f64,
+    pub bar: u32,  // 从 1 开始
+    pub beat: u32, // 小节内从 1 开始
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+/// 音名和音组的命名方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteNaming {
+    #[default]
+    Chinese, // 简谱数字 + 音名 + 八度符号，如 "1c¹"、"小字一组"
+    Scientific, // 科学音高记号，如 "C4"、"Octave 4"
+    Helmholtz,  // 亥姆霍兹记号，如 "c'"、"one-line octave"
+}
+
+pub(crate) const SHARP_NAMES: [&str; 12] = [
+    "c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b",
+];
+
+impl NoteNaming {
+    pub fn note_name(self, note: u8) -> String {
+        let name = SHARP_NAMES[(note % 12) as usize];
+        // MIDI 60 为 C4
+        let octave = note as i32 / 12 - 1;
+
+        match self {
+            NoteNaming::Chinese => get_note_name(note),
+            NoteNaming::Scientific => format!("{}{}", name.to_uppercase(), octave),
+            NoteNaming::Helmholtz => helmholtz_name(name, octave),
+        }
+    }
+
+    pub fn group_name(self, note: u8) -> String {
+        let group = NoteGroup::from_note(note);
+        match (self, group) {
+            (NoteNaming::Chinese, _) => get_note_group(note),
+            (_, NoteGroup::Unknown) => "Unknown".to_string(),
+            (NoteNaming::Scientific, _) => {
+                let octave = note as i32 / 12 - 1;
+                let range = match group {
+                    NoteGroup::SubContra => "A0-B0".to_string(),
+                    NoteGroup::FiveLine => "C8".to_string(),
+                    _ => format!("C{}-B{}", octave, octave),
+                };
+                format!("Octave {} ({})", octave, range)
+            }
+            (NoteNaming::Helmholtz, _) => {
+                let (name, range) = match group {
+                    NoteGroup::SubContra => ("sub-contra octave", "A,,-B,,"),
+                    NoteGroup::Contra => ("contra octave", "C,-B,"),
+                    NoteGroup::Great => ("great octave", "C-B"),
+                    NoteGroup::Small => ("small octave", "c-b"),
+                    NoteGroup::OneLine => ("one-line octave", "c'-b'"),
+                    NoteGroup::TwoLine => ("two-line octave", "c''-b''"),
+                    NoteGroup::ThreeLine => ("three-line octave", "c'''-b'''"),
+                    NoteGroup::FourLine => ("four-line octave", "c''''-b''''"),
+                    _ => ("five-line octave", "c'''''"),
+                };
+                format!("{} ({})", name, range)
+            }
+        }
+    }
+}
+
+// 亥姆霍兹记号：小字组为小写无符号，往上每组加一个 '，大字组往下每组加一个 ,
+fn helmholtz_name(name: &str, octave: i32) -> String {
+    if octave >= 3 {
+        format!("{}{}", name, "'".repeat((octave - 3) as usize))
+    } else {
+        format!(
+            "{}{}",
+            name.to_uppercase(),
+            ",".repeat((2 - octave) as usize)
+        )
+    }
 }
 
 fn get_note_name(note: u8) -> String {
@@ -137,19 +410,93 @@ fn get_note_name(note: u8) -> String {
     format!("{}{}{}", solfege_num, note_name, octave_symbol)
 }
 
-fn get_note_group(note: u8) -> String {
+/// 音组（按德式八度命名），序列化为英文代码供前端本地化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteGroup {
+    SubContra, // 大字二组
+    Contra,    // 大字一组
+    Great,     // 大字组
+    Small,     // 小字组
+    OneLine,   // 小字一组
+    TwoLine,   // 小字二组
+    ThreeLine, // 小字三组
+    FourLine,  // 小字四组
+    FiveLine,  // 小字五组
+    Unknown,
+}
+
+impl NoteGroup {
     // Based on groups.ts configuration
-    match note {
-        21..=23 => "大字二组 (A₂-B₂)".to_string(),
-        24..=35 => "大字一组 (C₁-B₁)".to_string(),
-        36..=47 => "大字组 (C-B)".to_string(),
-        48..=59 => "小字组 (c-b)".to_string(),
-        60..=71 => "小字一组 (c¹-b¹)".to_string(),
-        72..=83 => "小字二组 (c²-b²)".to_string(),
-        84..=95 => "小字三组 (c³-b³)".to_string(),
-        96..=107 => "小字四组 (c⁴-b⁴)".to_string(),
-        108 => "小字五组 (c⁵)".to_string(),
-        _ => "未知".to_string(),
+    pub fn from_note(note: u8) -> Self {
+        match note {
+            21..=23 => NoteGroup::SubContra,
+            24..=35 => NoteGroup::Contra,
+            36..=47 => NoteGroup::Great,
+            48..=59 => NoteGroup::Small,
+            60..=71 => NoteGroup::OneLine,
+            72..=83 => NoteGroup::TwoLine,
+            84..=95 => NoteGroup::ThreeLine,
+            96..=107 => NoteGroup::FourLine,
+            108 => NoteGroup::FiveLine,
+            _ => NoteGroup::Unknown,
+        }
+    }
+
+    /// 音域的记谱写法，与语言无关
+    pub fn range_label(self) -> &'static str {
+        match self {
+            NoteGroup::SubContra => "A₂-B₂",
+            NoteGroup::Contra => "C₁-B₁",
+            NoteGroup::Great => "C-B",
+            NoteGroup::Small => "c-b",
+            NoteGroup::OneLine => "c¹-b¹",
+            NoteGroup::TwoLine => "c²-b²",
+            NoteGroup::ThreeLine => "c³-b³",
+            NoteGroup::FourLine => "c⁴-b⁴",
+            NoteGroup::FiveLine => "c⁵",
+            NoteGroup::Unknown => "",
+        }
+    }
+
+    pub fn name_zh(self) -> &'static str {
+        match self {
+            NoteGroup::SubContra => "大字二组",
+            NoteGroup::Contra => "大字一组",
+            NoteGroup::Great => "大字组",
+            NoteGroup::Small => "小字组",
+            NoteGroup::OneLine => "小字一组",
+            NoteGroup::TwoLine => "小字二组",
+            NoteGroup::ThreeLine => "小字三组",
+            NoteGroup::FourLine => "小字四组",
+            NoteGroup::FiveLine => "小字五组",
+            NoteGroup::Unknown => "未知",
+        }
+    }
+}
+
+/// 音组及其显示提示
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteGroupInfo {
+    pub group: NoteGroup,
+    pub range: String, // 记谱写法，如 "c¹-b¹"
+}
+
+impl NoteGroupInfo {
+    fn from_note(note: u8) -> Self {
+        let group = NoteGroup::from_note(note);
+        Self {
+            group,
+            range: group.range_label().to_string(),
+        }
+    }
+}
+
+fn get_note_group(note: u8) -> String {
+    let group = NoteGroup::from_note(note);
+    match group {
+        NoteGroup::Unknown => group.name_zh().to_string(),
+        _ => format!("{} ({})", group.name_zh(), group.range_label()),
     }
 }
 
@@ -188,29 +535,189 @@ fn optimize_transpose_suggestion(
     suggestions.first().map(|(t, o, _)| (*t, *o))
 }
 
+fn meta_text(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes).trim().to_string();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// 从 MIDI 元事件和文件名中提取曲名和作曲者
+/// 文件名形如 "作曲者 - 曲名.mid" 时优先使用文件名中的信息
+fn extract_metadata(smf: &Smf, file_path: &str) -> SongMetadata {
+    let mut track_title = None;
+    let mut copyright = None;
+    let mut composer = None;
+
+    // 曲名通常是第一个音轨的名称，作曲者常写在文本事件中
+    for (i, track) in smf.tracks.iter().enumerate() {
+        for event in track {
+            match event.kind {
+                TrackEventKind::Meta(midly::MetaMessage::TrackName(name))
+                    if i == 0 && track_title.is_none() =>
+                {
+                    track_title = meta_text(name);
+                }
+                TrackEventKind::Meta(midly::MetaMessage::Copyright(text))
+                    if copyright.is_none() =>
+                {
+                    copyright = meta_text(text);
+                }
+                TrackEventKind::Meta(midly::MetaMessage::Text(text)) if composer.is_none() => {
+                    composer = meta_text(text).and_then(|t| {
+                        let lower = t.to_ascii_lowercase();
+                        ["composer:", "composed by", "作曲"]
+                            .iter()
+                            .find_map(|prefix| lower.find(prefix).map(|pos| pos + prefix.len()))
+                            .map(|pos| {
+                                t[pos..]
+                                    .trim_start_matches([':', '：', ' '])
+                                    .trim()
+                                    .to_string()
+                            })
+                            .filter(|c| !c.is_empty())
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().trim().to_string())
+        .unwrap_or_default();
+
+    let (file_composer, file_title) = match stem.split_once(" - ") {
+        Some((c, t)) if !c.trim().is_empty() && !t.trim().is_empty() => {
+            (Some(c.trim().to_string()), t.trim().to_string())
+        }
+        _ => (None, stem.clone()),
+    };
+
+    SongMetadata {
+        title: file_composer
+            .as_ref()
+            .map(|_| file_title.clone())
+            .or(track_title)
+            .unwrap_or(file_title),
+        composer: file_composer.or(composer),
+        copyright,
+    }
+}
+
+/// 读取乐曲文件，非 MIDI 格式先转换为标准 MIDI 数据
+pub fn load_midi_bytes(path: &Path, import_track: Option<usize>) -> Result<Vec<u8>, AppError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let read_error = |e: std::io::Error| {
+        AppError::io(format!("Failed to read file: {}", e)).with_context(path.display().to_string())
+    };
+    let parse_error = |e: String| AppError::parse(e).with_context(path.display().to_string());
+
+    match extension.as_str() {
+        "ly" => {
+            let source = fs::read_to_string(path).map_err(read_error)?;
+            lilypond::parse(&source)
+                .and_then(|song| song.to_smf_bytes())
+                .map_err(parse_error)
+        }
+        "gp5" | "gpx" => {
+            let bytes = fs::read(path).map_err(read_error)?;
+            guitar_pro::import(&bytes, &extension, import_track)
+                .and_then(|song| song.to_smf_bytes())
+                .map_err(parse_error)
+        }
+        _ => fs::read(path).map_err(read_error),
+    }
+}
+
+fn parse_smf<'a>(bytes: &'a [u8], source_name: &str) -> Result<Smf<'a>, AppError> {
+    Smf::parse(bytes).map_err(|e| {
+        AppError::parse(format!("Failed to parse MIDI: {}", e)).with_context(source_name)
+    })
+}
+
+/// 只读取歌曲元数据，不做音符分析
+pub fn read_song_metadata(file_path: &str) -> Result<SongMetadata, AppError> {
+    let bytes = load_midi_bytes(Path::new(file_path), None)?;
+    let smf = parse_smf(&bytes, file_path)?;
+    Ok(extract_metadata(&smf, file_path))
+}
+
 pub fn analyze_midi_file(
     file_path: &str,
-    min_note: u8,
-    max_note: u8,
-    black_key_mode: &str,
-    trim_long_notes: bool,
-) -> Result<MidiAnalysis, String> {
+    options: &AnalyzeOptions,
+) -> Result<MidiAnalysis, AppError> {
     let path = Path::new(file_path);
     if !path.exists() {
-        return Err(format!("File not found: {}", file_path));
+        return Err(AppError::not_found("File not found").with_context(file_path));
     }
 
-    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
-    let smf = Smf::parse(&bytes).map_err(|e| format!("Failed to parse MIDI: {}", e))?;
+    let bytes = load_midi_bytes(path, options.import_track)?;
+    analyze_midi_bytes(&bytes, file_path, options)
+}
+
+/// 分析内存中的标准 MIDI 数据，source_name 用于曲名缺失时的回退
+pub fn analyze_midi_bytes(
+    bytes: &[u8],
+    source_name: &str,
+    options: &AnalyzeOptions,
+) -> Result<MidiAnalysis, AppError> {
+    let smf = parse_smf(bytes, source_name)?;
 
     let ticks_per_beat = match smf.header.timing {
         midly::Timing::Metrical(t) => t.as_int() as f64,
-        midly::Timing::Timecode(_, _) => return Err("SMPTE timing not supported yet".to_string()),
+        midly::Timing::Timecode(_, _) => {
+            return Err(AppError::parse("SMPTE timing not supported yet").with_context(source_name))
+        }
+    };
+    if ticks_per_beat == 0.0 {
+        return Err(AppError::parse("Invalid timing: 0 ticks per beat").with_context(source_name));
+    }
+
+    let tempo_override = options.tempo.map(TempoOverride::validate).transpose()?;
+    let metadata = extract_metadata(&smf, source_name);
+    let (min_note, max_note) = (options.min_note, options.max_note);
+    let black_key_mode = options.black_key_mode;
+    // 旧的开关等同于默认的长音截短
+    let long_notes = options
+        .long_notes
+        .or(options.trim_long_notes.then(LongNoteTrim::default));
+    let naming = options.naming;
+    let track_shifts = &options.track_shifts;
+    let is_selected = |track: usize| options.tracks.as_ref().is_none_or(|t| t.contains(&track));
+    // 明确列出的通道优先，包括第 10 通道
+    let channel_selected = |channel: u8| {
+        !options.exclude_channels.contains(&channel)
+            && match &options.channels {
+                Some(channels) => channels.contains(&channel),
+                None => options.keep_percussion || channel != PERCUSSION_CHANNEL,
+            }
+    };
+    // 参数名在后面会被复用为统计结果，先保存音域上下限
+    let (range_min, range_max) = (min_note, max_note);
+    // 音轨实际的音高移动：音轨自身的加上整首的
+    let pitch_shift = |track: usize| {
+        let shift = track_shifts.get(&track).copied().unwrap_or_default();
+        TrackShift {
+            transpose: shift.transpose + options.transpose,
+            octave: shift.octave + options.octave_shift,
+            ..shift
+        }
     };
 
     let mut events = Vec::new();
     let mut tracks_info = Vec::new();
     let mut tempo_changes = Vec::new(); // (tick, microseconds_per_beat)
+    let mut time_signatures = Vec::new(); // (tick, numerator, denominator_power)
+    let mut key_signature: Option<(u32, i8, bool)> = None; // 最早的调号 (tick, 升降号数, 小调)
+    let mut raw_lyrics = Vec::new();
+    let mut end_tick = 0;
 
     // First pass: collect tempo changes from all tracks (usually track 0)
     // And also track names and per-track note statistics
@@ -220,7 +727,11 @@ pub fn analyze_midi_file(
         let mut current_tick = 0;
         let mut track_name = format!("Track {}", i);
         let mut note_count = 0;
+        let mut percussion_count = 0;
         let mut notes_in_track = Vec::new();
+        // 每个音轨可以单独移调，如只把低音轨升高一个八度
+        let shift = track_shifts.get(&i).copied().unwrap_or_default();
+        let pitch = pitch_shift(i);
 
         for event in track {
             current_tick += event.delta.as_int();
@@ -229,24 +740,54 @@ pub fn analyze_midi_file(
                 TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) => {
                     tempo_changes.push((current_tick, t.as_int()));
                 }
+                TrackEventKind::Meta(midly::MetaMessage::TimeSignature(num, pow, _, _)) => {
+                    time_signatures.push((current_tick, num, pow));
+                }
+                TrackEventKind::Meta(midly::MetaMessage::KeySignature(sharps, minor))
+                    if key_signature.is_none_or(|(tick, _, _)| current_tick < tick) =>
+                {
+                    key_signature = Some((current_tick, sharps, minor));
+                }
                 TrackEventKind::Meta(midly::MetaMessage::TrackName(name)) => {
                     if let Ok(n) = String::from_utf8(name.to_vec()) {
                         track_name = n;
                     }
                 }
+                TrackEventKind::Meta(midly::MetaMessage::Lyric(bytes)) => {
+                    raw_lyrics.push(RawLyric {
+                        tick: current_tick,
+                        track: i,
+                        source: LyricSource::Lyric,
+                        bytes,
+                    });
+                }
+                TrackEventKind::Meta(midly::MetaMessage::Text(bytes)) => {
+                    raw_lyrics.push(RawLyric {
+                        tick: current_tick,
+                        track: i,
+                        source: LyricSource::Text,
+                        bytes,
+                    });
+                }
                 TrackEventKind::Midi {
+                    channel,
                     message: MidiMessage::NoteOn { key, vel },
-                    ..
                 } => {
                     if vel.as_int() > 0 {
                         note_count += 1;
-                        notes_in_track.push(key.as_int());
+                        percussion_count += (channel.as_int() == PERCUSSION_CHANNEL) as usize;
+                        // 音域统计只算会保留的通道，音符数包括全部，音轨列表中仍能看到鼓轨
+                        if channel_selected(channel.as_int()) {
+                            notes_in_track.push(pitch.apply(key.as_int()));
+                        }
                     }
                 }
                 _ => {}
             }
         }
 
+        end_tick = end_tick.max(current_tick);
+
         if note_count > 0 {
             // Calculate track analysis using provided min/max note
             let limit_min = min_note;
@@ -261,9 +802,9 @@ pub fn analyze_midi_file(
             let is_max_over_limit = max_note.map_or(false, |n| n > limit_max || n < limit_min);
             let is_min_over_limit = min_note.map_or(false, |n| n < limit_min || n > limit_max);
 
-            // 计算建议值（当前移调和转位都是0）
-            let current_transpose = 0;
-            let current_octave = 0;
+            // 计算建议值（叠加在该音轨当前的移调和转位上，整首的移调保持不变）
+            let current_transpose = shift.transpose;
+            let current_octave = shift.octave;
 
             let (suggested_max_transpose, suggested_max_octave) = if is_max_over_limit {
                 max_note
@@ -292,10 +833,12 @@ pub fn analyze_midi_file(
             let analysis = TrackAnalysis {
                 max_note,
                 min_note,
-                max_note_name: max_note.map(get_note_name).unwrap_or_default(),
-                min_note_name: min_note.map(get_note_name).unwrap_or_default(),
-                max_note_group: max_note.map(get_note_group).unwrap_or_default(),
-                min_note_group: min_note.map(get_note_group).unwrap_or_default(),
+                max_note_name: max_note.map(|n| naming.note_name(n)).unwrap_or_default(),
+                min_note_name: min_note.map(|n| naming.note_name(n)).unwrap_or_default(),
+                max_note_group: max_note.map(|n| naming.group_name(n)).unwrap_or_default(),
+                min_note_group: min_note.map(|n| naming.group_name(n)).unwrap_or_default(),
+                max_group: max_note.map(NoteGroupInfo::from_note),
+                min_group: min_note.map(NoteGroupInfo::from_note),
                 upper_over_limit,
                 lower_over_limit,
                 is_max_over_limit,
@@ -311,6 +854,8 @@ pub fn analyze_midi_file(
                 name: track_name,
                 note_count,
                 analysis,
+                label: None,
+                percussion: percussion_count == note_count,
             });
 
             track_notes.insert(i, notes_in_track);
@@ -336,6 +881,10 @@ pub fn analyze_midi_file(
     if unique_tempo_changes.is_empty() || unique_tempo_changes[0].0 > 0 {
         unique_tempo_changes.insert(0, (0, 500_000));
     }
+    // 变速在换算时间之前进行，之后的拍线、音符时间都按新的速度
+    if let Some(tempo) = tempo_override {
+        tempo.apply(&mut unique_tempo_changes);
+    }
 
     // Helper to convert ticks to seconds
     let tick_to_seconds = |tick: u32| -> f64 {
@@ -358,8 +907,19 @@ pub fn analyze_midi_file(
         time
     };
 
+    let mut beats = build_beat_grid(time_signatures, end_tick, ticks_per_beat, &tick_to_seconds);
+    let mut lyrics = lyrics::assemble(raw_lyrics, &tick_to_seconds);
+
+    let mut unclosed_count = 0;
+    let mut percussion_skipped = 0;
+    let mut controls = Vec::new();
+
     // Second pass: collect notes
+    // 速度和拍号已在第一遍收集，未选中的音轨直接跳过；音轨信息仍包含全部音轨
     for (i, track) in smf.tracks.iter().enumerate() {
+        if !is_selected(i) {
+            continue;
+        }
         let mut current_tick = 0;
         // Key: (channel, note), Value: (start_tick, velocity)
         let mut active_notes: HashMap<(u8, u8), (u32, u8)> = HashMap::new();
@@ -370,6 +930,14 @@ pub fn analyze_midi_file(
             match event.kind {
                 TrackEventKind::Midi { channel, message } => {
                     let channel = channel.as_int();
+                    if !channel_selected(channel) {
+                        if channel == PERCUSSION_CHANNEL
+                            && matches!(message, MidiMessage::NoteOn { vel, .. } if vel.as_int() > 0)
+                        {
+                            percussion_skipped += 1;
+                        }
+                        continue;
+                    }
                     match message {
                         MidiMessage::NoteOn { key, vel } => {
                             let note = key.as_int();
@@ -382,14 +950,8 @@ pub fn analyze_midi_file(
                                     active_notes.remove(&(channel, note))
                                 {
                                     let start_time = tick_to_seconds(start_tick);
-                                    let mut end_time = tick_to_seconds(current_tick);
-                                    let mut duration = end_time - start_time;
-
-                                    // 优化：如果持续时间超过1秒且启用了修剪，强制修剪为0.99秒
-                                    if trim_long_notes && duration > 1.0 {
-                                        duration = 0.99;
-                                        end_time = start_time + duration;
-                                    }
+                                    let end_time = tick_to_seconds(current_tick);
+                                    let duration = end_time - start_time;
 
                                     events.push(MidiEvent {
                                         time: start_time,
@@ -414,20 +976,47 @@ pub fn analyze_midi_file(
                                 }
                             }
                         }
+                        MidiMessage::PitchBend { bend } if options.include_controls => {
+                            controls.push(ControlEvent {
+                                time: tick_to_seconds(current_tick),
+                                track: i,
+                                channel,
+                                kind: ControlKind::PitchBend {
+                                    value: bend.as_f64(),
+                                },
+                            });
+                        }
+                        MidiMessage::Controller { controller, value }
+                            if options.include_controls =>
+                        {
+                            controls.push(ControlEvent {
+                                time: tick_to_seconds(current_tick),
+                                track: i,
+                                channel,
+                                kind: ControlKind::Controller {
+                                    controller: controller.as_int(),
+                                    value: value.as_int(),
+                                },
+                            });
+                        }
+                        MidiMessage::ChannelAftertouch { vel } if options.include_controls => {
+                            controls.push(ControlEvent {
+                                time: tick_to_seconds(current_tick),
+                                track: i,
+                                channel,
+                                kind: ControlKind::ChannelPressure {
+                                    value: vel.as_int(),
+                                },
+                            });
+                        }
                         MidiMessage::NoteOff { key, .. } => {
                             let note = key.as_int();
                             if let Some((start_tick, start_vel)) =
                                 active_notes.remove(&(channel, note))
                             {
                                 let start_time = tick_to_seconds(start_tick);
-                                let mut end_time = tick_to_seconds(current_tick);
-                                let mut duration = end_time - start_time;
-
-                                // 优化：如果持续时间超过1秒且启用了修剪，强制修剪为0.99秒
-                                if trim_long_notes && duration > 1.0 {
-                                    duration = 0.99;
-                                    end_time = start_time + duration;
-                                }
+                                let end_time = tick_to_seconds(current_tick);
+                                let duration = end_time - start_time;
 
                                 events.push(MidiEvent {
                                     time: start_time,
@@ -459,6 +1048,7 @@ pub fn analyze_midi_file(
         }
 
         // 处理该音轨中未关闭的音符（自动生成0.2秒的off事件）
+        unclosed_count += active_notes.len();
         for ((channel, note), (start_tick, start_vel)) in active_notes {
             let start_time = tick_to_seconds(start_tick);
             let duration = 0.2; // 默认给0.2秒
@@ -493,21 +1083,178 @@ pub fn analyze_midi_file(
             .partial_cmp(&b.time)
             .unwrap_or(std::cmp::Ordering::Equal)
     });
+    controls.sort_by(|a: &ControlEvent, b| a.time.total_cmp(&b.time));
+
+    // 长音按原始时值处理，之后的律动、提前松开等都基于截短或拆分后的音符
+    let trimmed_count = match long_notes {
+        Some(settings) => arrange::trim_long_notes(&mut events, settings),
+        None => 0,
+    };
+
+    // 调性按原曲识别，文件中的调号优先
+    let key = match key_signature {
+        Some((_, sharps, minor)) => Some(DetectedKey::from_signature(sharps, minor)),
+        None => DetectedKey::from_notes(&events),
+    };
 
-    // Apply black key mode conversion if enabled
-    // This matches the Python implementation in midi_analyzer.py lines 529-541
-    if black_key_mode == "auto_sharp" {
+    // 音域外的处理（折叠、黑键）都在移调之后进行
+    for event in &mut events {
+        event.note = pitch_shift(event.track).apply(event.note);
+    }
+    let auto_transpose = options.auto_fit_to_c_major.then(|| {
+        let shift = key_detect::fewest_black_keys_shift(&events);
         for event in &mut events {
-            let note = event.note;
-            let pc = note % 12;
-
-            // Check if this is a black key
-            if BLACK_PCS.contains(&pc) {
-                // Find the nearest white key pitch class
-                let new_pc = nearest_white_pc(pc);
-                // Keep the octave, only change the pitch class
-                event.note = (note - pc) + new_pc;
+            event.note = (event.note as i32 + shift).clamp(0, 127) as u8;
+        }
+        shift
+    });
+
+    if let Some(settings) = options.quantize {
+        let moved = arrange::quantize(&mut events, settings, &beats);
+        log::info!("Quantized {} notes to 1/{} grid", moved, settings.grid);
+    }
+    if let Some(groove) = options.groove {
+        arrange::apply_groove(&mut events, groove, &beats);
+    }
+
+    // 和弦按移调后、简化和折叠前的原始和声识别
+    let mut chords = chord::detect_chords(&events);
+
+    // 先提取旋律或简化和声，再把剩下的音符折叠进音域
+    let melody_count = match options.extract_melody {
+        Some(line) => arrange::extract_melody(&mut events, line),
+        None => 0,
+    };
+    let reduced_count = if options.reduce_harmony {
+        arrange::reduce_harmony(&mut events)
+    } else {
+        0
+    };
+    let simplified_count = match options.simplify {
+        Some(settings) => arrange::simplify(&mut events, settings),
+        None => 0,
+    };
+    let folded_count =
+        arrange::fold_into_range(&mut events, range_min, range_max, options.fold_mode);
+
+    if let Some(release) = options.release_early {
+        arrange::release_early(&mut events, release);
+    }
+    if let Some(settings) = options.velocity {
+        arrange::compress_velocities(&mut events, settings);
+    }
+
+    // 裁剪和压缩静默放在所有改变时值的处理之后，拍线和和弦一起对齐
+    let trim = options
+        .auto_trim
+        .and_then(|settings| arrange::auto_trim(&mut events, settings))
+        .unwrap_or_default();
+    if !trim.ranges.is_empty() {
+        beats.retain(|b| b.time >= trim.keep_from && b.time <= trim.keep_until);
+        chords.retain(|c| c.end > trim.keep_from && c.time < trim.keep_until);
+        controls.retain(|c| c.time >= trim.keep_from && c.time <= trim.keep_until);
+        lyrics.retain(|l| l.time >= trim.keep_from && l.time <= trim.keep_until);
+        for beat in beats.iter_mut() {
+            beat.time -= trim.offset;
+        }
+        for control in controls.iter_mut() {
+            control.time = (control.time - trim.offset).max(0.0);
+        }
+        for lyric in lyrics.iter_mut() {
+            lyric.time = (lyric.time - trim.offset).max(0.0);
+        }
+        for chord in chords.iter_mut() {
+            chord.time = (chord.time - trim.offset).max(0.0);
+            chord.end -= trim.offset;
+        }
+    }
+    let compression = match options.max_gap_secs {
+        Some(max_secs) => {
+            let compression = arrange::compress_gaps(&mut events, max_secs);
+            beats.retain(|b| !compression.is_removed(b.time));
+            for beat in beats.iter_mut() {
+                beat.time = compression.map_time(beat.time);
+            }
+            controls.retain(|c| !compression.is_removed(c.time));
+            for control in controls.iter_mut() {
+                control.time = compression.map_time(control.time);
+            }
+            lyrics.retain(|l| !compression.is_removed(l.time));
+            for lyric in lyrics.iter_mut() {
+                lyric.time = compression.map_time(lyric.time);
+            }
+            for chord in chords.iter_mut() {
+                chord.time = compression.map_time(chord.time);
+                chord.end = compression.map_time(chord.end);
             }
+            compression
+        }
+        None => arrange::GapCompression::default(),
+    };
+
+    // 延后放在所有时间处理之后，保证设置的偏移原样体现在播放中
+    let delay_ms = |track: usize, channel: u8| {
+        track_shifts.get(&track).map_or(0.0, |s| s.delay_ms)
+            + options
+                .channel_delays_ms
+                .get(&channel)
+                .copied()
+                .unwrap_or(0.0)
+    };
+    if events.iter().any(|e| delay_ms(e.track, e.channel) != 0.0) {
+        let lead = arrange::apply_delays(&mut events, |e| delay_ms(e.track, e.channel));
+        for control in controls.iter_mut() {
+            let delay = delay_ms(control.track, control.channel) / 1000.0;
+            if delay.is_finite() {
+                control.time += delay;
+            }
+            control.time = (control.time + lead).max(0.0);
+        }
+        controls.sort_by(|a, b| a.time.total_cmp(&b.time));
+        // 歌词没有通道，只按音轨延后
+        for lyric in lyrics.iter_mut() {
+            let delay = track_shifts.get(&lyric.track).map_or(0.0, |s| s.delay_ms) / 1000.0;
+            if delay.is_finite() {
+                lyric.time += delay;
+            }
+            lyric.time = (lyric.time + lead).max(0.0);
+        }
+        lyrics.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for beat in beats.iter_mut() {
+            beat.time += lead;
+        }
+        for chord in chords.iter_mut() {
+            chord.time += lead;
+            chord.end += lead;
+        }
+    }
+
+    // 黑键转换放在最后，之后不再改变音高；去掉的音符 note_on 和 note_off 音高相同，成对去掉
+    let before = events.len();
+    events.retain_mut(|event| match black_key_mode.apply(event.note) {
+        Some(note) => {
+            event.note = note;
+            true
+        }
+        None => false,
+    });
+    if events.len() < before {
+        log::info!("Dropped {} black key notes", (before - events.len()) / 2);
+    }
+
+    // 重复音符和按键间隔都按最终音高（即按键）判断，先合并再检查间隔
+    let merged_count = match options.merge_duplicates {
+        Some(settings) => arrange::merge_duplicates(&mut events, settings),
+        None => 0,
+    };
+    if let Some(gap_ms) = options.min_release_gap_ms {
+        let shortened = arrange::enforce_release_gap(&mut events, gap_ms / 1000.0);
+        if shortened > 0 {
+            log::info!(
+                "Shortened {} notes to keep a {}ms release gap",
+                shortened,
+                gap_ms
+            );
         }
     }
 
@@ -539,13 +1286,94 @@ pub fn analyze_midi_file(
         }
     }
 
-    // Debug: print first few events
-    if events.len() > 0 {
-        println!("First 3 events:");
-        for (i, event) in events.iter().take(3).enumerate() {
-            println!("Event {}: {:?}", i, event);
+    log::info!(
+        "Parsed {}: {} events, {} below range, {} above range",
+        source_name,
+        events.len(),
+        under_min_count,
+        over_max_count
+    );
+    for (i, event) in events.iter().take(3).enumerate() {
+        log::debug!("Event {}: {:?}", i, event);
+    }
+
+    // 按调用方给定的音域统计超限音符
+    let below: usize = tracks_info
+        .iter()
+        .filter(|t| is_selected(t.id))
+        .map(|t| t.analysis.lower_over_limit)
+        .sum();
+    let above: usize = tracks_info
+        .iter()
+        .filter(|t| is_selected(t.id))
+        .map(|t| t.analysis.upper_over_limit)
+        .sum();
+    let mut warnings = Vec::new();
+    // 折叠后已无超限音符，只提示折叠了多少
+    if folded_count > 0 {
+        warnings.push(Warning::NotesFolded {
+            count: folded_count,
+        });
+    } else {
+        if below > 0 {
+            warnings.push(Warning::NotesBelowRange {
+                count: below,
+                min_note: range_min,
+            });
+        }
+        if above > 0 {
+            warnings.push(Warning::NotesAboveRange {
+                count: above,
+                max_note: range_max,
+            });
         }
     }
+    if reduced_count > 0 {
+        warnings.push(Warning::NotesReduced {
+            count: reduced_count,
+        });
+    }
+    if trimmed_count > 0 {
+        warnings.push(Warning::LongNotesTrimmed {
+            count: trimmed_count,
+        });
+    }
+    if unclosed_count > 0 {
+        warnings.push(Warning::UnclosedNotes {
+            count: unclosed_count,
+        });
+    }
+    if melody_count > 0 {
+        warnings.push(Warning::MelodyExtracted {
+            count: melody_count,
+        });
+    }
+    if simplified_count > 0 {
+        warnings.push(Warning::NotesSimplified {
+            count: simplified_count,
+        });
+    }
+    if merged_count > 0 {
+        warnings.push(Warning::NotesMerged {
+            count: merged_count,
+        });
+    }
+    if percussion_skipped > 0 {
+        warnings.push(Warning::PercussionSkipped {
+            count: percussion_skipped,
+        });
+    }
+    if trim.removed_notes > 0 {
+        warnings.push(Warning::SectionsTrimmed {
+            count: trim.removed_notes,
+        });
+    }
+    if compression.gaps() > 0 {
+        warnings.push(Warning::SilenceCompressed {
+            gaps: compression.gaps(),
+            removed_secs: compression.removed_secs(),
+        });
+    }
 
     Ok(MidiAnalysis {
         events,
@@ -554,10 +1382,77 @@ pub fn analyze_midi_file(
             max_note,
             under_min_count,
             over_max_count,
-            min_note_name: min_note.map(get_note_name).unwrap_or_default(),
-            max_note_name: max_note.map(get_note_name).unwrap_or_default(),
+            min_note_name: min_note.map(|n| naming.note_name(n)).unwrap_or_default(),
+            max_note_name: max_note.map(|n| naming.note_name(n)).unwrap_or_default(),
             total_over_limit_count: under_min_count + over_max_count,
+            key,
+            auto_transpose,
         },
         tracks: tracks_info,
+        metadata,
+        warnings,
+        chords,
+        beats,
+        trimmed: trim.ranges,
+        controls,
+        lyrics,
     })
 }
+
+// 拍线数的上限，每拍的 tick 数很小而乐曲很长时不再继续生成
+const MAX_BEATS: usize = 100_000;
+
+// 按拍号和速度表生成到乐曲结尾的拍线，没有拍号时默认 4/4
+fn build_beat_grid(
+    mut time_signatures: Vec<(u32, u8, u8)>,
+    end_tick: u32,
+    ticks_per_beat: f64,
+    tick_to_seconds: &dyn Fn(u32) -> f64,
+) -> Vec<BeatMark> {
+    time_signatures.sort_by_key(|ts| ts.0);
+    if time_signatures.first().is_none_or(|ts| ts.0 > 0) {
+        time_signatures.insert(0, (0, 4, 2));
+    }
+
+    let mut beats = Vec::new();
+    let mut sig_index = 0;
+    let mut tick = 0.0;
+    let (mut bar, mut beat) = (1, 1);
+
+    while tick <= end_tick as f64 {
+        if beats.len() >= MAX_BEATS {
+            log::warn!("Beat grid truncated at {} beats", MAX_BEATS);
+            break;
+        }
+        // 拍号变化时从变化处开始新的小节
+        while sig_index + 1 < time_signatures.len()
+            && tick >= time_signatures[sig_index + 1].0 as f64
+        {
+            sig_index += 1;
+            tick = time_signatures[sig_index].0 as f64;
+            if beat != 1 {
+                bar += 1;
+                beat = 1;
+            }
+        }
+
+        let (_, numerator, pow) = time_signatures[sig_index];
+        let numerator = numerator.max(1);
+        let denominator = 1u8.checked_shl(pow as u32).unwrap_or(4);
+        beats.push(BeatMark {
+            time: tick_to_seconds(tick.round() as u32),
+            bar,
+            beat,
+            numerator,
+            denominator,
+        });
+
+        tick += ticks_per_beat * 4.0 / denominator as f64;
+        beat += 1;
+        if beat > numerator as u32 {
+            beat = 1;
+            bar += 1;
+        }
+    }
+    beats
+}
@@ -0,0 +1,294 @@
+use crate::error::{AppError, ErrorCode};
+use crate::state::PlaybackControl;
+use midir::{MidiOutput, MidiOutputConnection};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// MIDI 时钟同步输出：播放时在选定的 MIDI 输出端口发送时钟、开始和停止消息，
+// 让外部设备（鼓机、DAW 伴奏）跟随自动演奏的速度。时钟跟随键盘播放的位置，
+// 暂停时发送停止，继续时发送继续，跳转时先用歌曲位置指针定位。
+
+const CLIENT_NAME: &str = "OpenGamesAutoPlay";
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION: u8 = 0xF2;
+
+// 每拍 24 个时钟，歌曲位置指针以十六分音符（6 个时钟）为单位
+const CLOCKS_PER_BEAT: u64 = 24;
+const CLOCKS_PER_SIXTEENTH: u64 = 6;
+// 歌曲位置指针是 14 位
+const MAX_SONG_POSITION: u64 = 0x3FFF;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+// 播放位置比下一个时钟超前这么多时视为向前跳转
+const SEEK_TOLERANCE_SECS: f64 = 0.25;
+// 播放位置倒退超过这么多时视为向后跳转
+const REWIND_TOLERANCE_SECS: f64 = 0.05;
+
+/// 时钟输出设置：拍线时间来自解析结果（已按播放速度换算），没有拍线时按固定速度
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClockSync {
+    pub port: String,
+    #[serde(default)]
+    pub beats: Vec<f64>, // 播放时间轴上每一拍的时间（秒）
+    #[serde(default)]
+    pub bpm: Option<f64>,
+}
+
+/// 可用的 MIDI 输出端口名
+pub fn list_outputs() -> Result<Vec<String>, AppError> {
+    let output = open_output()?;
+    Ok(output
+        .ports()
+        .iter()
+        .filter_map(|port| output.port_name(port).ok())
+        .collect())
+}
+
+fn open_output() -> Result<MidiOutput, AppError> {
+    MidiOutput::new(CLIENT_NAME).map_err(|e| {
+        AppError::new(ErrorCode::Other, "MIDI output is unavailable").with_context(e.to_string())
+    })
+}
+
+fn connect(name: &str) -> Result<MidiOutputConnection, AppError> {
+    let output = open_output()?;
+    let port = output
+        .ports()
+        .into_iter()
+        .find(|port| output.port_name(port).is_ok_and(|n| n == name))
+        .ok_or_else(|| AppError::not_found("MIDI output port not found").with_context(name))?;
+    output.connect(&port, "clock").map_err(|e| {
+        AppError::new(ErrorCode::Other, "Failed to open MIDI output port")
+            .with_context(format!("{} ({})", name, e))
+    })
+}
+
+/// 检查好的拍线，第一拍之前和最后一拍之后按相邻两拍的间隔延伸
+pub struct BeatGrid {
+    beats: Vec<f64>,
+    first_interval: f64,
+    last_interval: f64,
+}
+
+impl BeatGrid {
+    pub fn compile(sync: &ClockSync) -> Result<Self, AppError> {
+        let mut beats: Vec<f64> = sync
+            .beats
+            .iter()
+            .copied()
+            .filter(|t| t.is_finite())
+            .collect();
+        beats.sort_by(f64::total_cmp);
+        beats.dedup();
+        if beats.len() < 2 {
+            let bpm = sync
+                .bpm
+                .filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+                .ok_or_else(|| AppError::invalid("MIDI clock needs beat times or a tempo"))?;
+            beats = vec![0.0, 60.0 / bpm];
+        }
+        let n = beats.len();
+        Ok(Self {
+            first_interval: beats[1] - beats[0],
+            last_interval: beats[n - 1] - beats[n - 2],
+            beats,
+        })
+    }
+
+    // 第 index 拍的时间（可以是拍线之前的负数拍）
+    fn beat_time(&self, index: i64) -> f64 {
+        let last = self.beats.len() as i64 - 1;
+        if index < 0 {
+            self.beats[0] + index as f64 * self.first_interval
+        } else if index > last {
+            self.beats[last as usize] + (index - last) as f64 * self.last_interval
+        } else {
+            self.beats[index as usize]
+        }
+    }
+
+    // 时间轴从 0 秒算起，第 0 个时钟在 0 秒，之后按所在拍的长度均分
+    fn origin(&self) -> f64 {
+        self.beat_position(0.0)
+    }
+
+    // 时间对应的拍位置（带小数）
+    fn beat_position(&self, time: f64) -> f64 {
+        let index = if time < self.beats[0] {
+            ((time - self.beats[0]) / self.first_interval).floor() as i64
+        } else if time >= self.beats[self.beats.len() - 1] {
+            let last = self.beats.len() - 1;
+            last as i64 + ((time - self.beats[last]) / self.last_interval).floor() as i64
+        } else {
+            self.beats.partition_point(|&t| t <= time) as i64 - 1
+        };
+        let (start, end) = (self.beat_time(index), self.beat_time(index + 1));
+        index as f64 + (time - start) / (end - start)
+    }
+
+    /// 第 tick 个时钟的时间
+    fn tick_time(&self, tick: u64) -> f64 {
+        let beat = self.origin() + tick as f64 / CLOCKS_PER_BEAT as f64;
+        let index = beat.floor();
+        let (start, end) = (
+            self.beat_time(index as i64),
+            self.beat_time(index as i64 + 1),
+        );
+        start + (beat - index) * (end - start)
+    }
+
+    /// 离时间最近的十六分音符上的时钟
+    fn tick_at(&self, time: f64) -> u64 {
+        let beats = (self.beat_position(time) - self.origin()).max(0.0);
+        let sixteenths = (beats * (CLOCKS_PER_BEAT / CLOCKS_PER_SIXTEENTH) as f64).round();
+        (sixteenths as u64).min(MAX_SONG_POSITION) * CLOCKS_PER_SIXTEENTH
+    }
+}
+
+/// 时钟线程的句柄，同一时间只有一个输出
+#[derive(Default)]
+pub struct MidiClock {
+    running: Mutex<Option<(Arc<AtomicBool>, thread::JoinHandle<()>)>>,
+}
+
+impl MidiClock {
+    /// 打开端口并跟随 playback 的播放位置发送时钟，播放结束时发送停止
+    pub fn start(&self, playback: Arc<PlaybackControl>, sync: ClockSync) -> Result<(), AppError> {
+        let grid = BeatGrid::compile(&sync)?;
+        self.stop();
+        let connection = connect(&sync.port)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || run(&playback, connection, &grid, &flag));
+        *self.running.lock().unwrap() = Some((stop, handle));
+        log::info!("MIDI clock started on {}", sync.port);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        let running = self.running.lock().unwrap().take();
+        if let Some((stop, handle)) = running {
+            stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(_, handle)| !handle.is_finished())
+    }
+}
+
+struct Output {
+    connection: MidiOutputConnection,
+    failed: bool, // 只记录第一次发送失败
+}
+
+impl Output {
+    fn send(&mut self, message: &[u8]) {
+        if let Err(e) = self.connection.send(message) {
+            if !self.failed {
+                log::warn!("Failed to send MIDI clock message: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+
+    // 定位到第 tick 个时钟（十六分音符上）后继续；从头开始时直接发送开始
+    fn locate(&mut self, tick: u64) {
+        if tick == 0 {
+            self.send(&[START]);
+            return;
+        }
+        let position = tick / CLOCKS_PER_SIXTEENTH;
+        self.send(&[
+            SONG_POSITION,
+            (position & 0x7F) as u8,
+            ((position >> 7) & 0x7F) as u8,
+        ]);
+        self.send(&[CONTINUE]);
+    }
+}
+
+fn run(
+    playback: &PlaybackControl,
+    connection: MidiOutputConnection,
+    grid: &BeatGrid,
+    stop: &AtomicBool,
+) {
+    let mut output = Output {
+        connection,
+        failed: false,
+    };
+    // 下一个要发送的时钟；None 表示还没有发送开始
+    let mut next: Option<u64> = None;
+    let mut paused = false;
+    let mut last_position = 0.0;
+    let mut sent = 0u64;
+
+    // 播放线程可能还在倒计时，is_playing 此时已为 true
+    while !stop.load(Ordering::SeqCst) && playback.is_playing() {
+        let Some(position) = playback.position() else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+
+        if playback.is_paused() {
+            if !paused && next.is_some() {
+                output.send(&[STOP]);
+            }
+            paused = true;
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let tick = match next {
+            Some(tick)
+                if position >= last_position - REWIND_TOLERANCE_SECS
+                    && position <= grid.tick_time(tick) + SEEK_TOLERANCE_SECS =>
+            {
+                if paused {
+                    output.send(&[CONTINUE]);
+                }
+                tick
+            }
+            // 第一次取位置或跳转后重新定位
+            previous => {
+                if previous.is_some() && !paused {
+                    output.send(&[STOP]);
+                }
+                let tick = grid.tick_at(position);
+                output.locate(tick);
+                tick
+            }
+        };
+        paused = false;
+        next = Some(tick);
+        last_position = position;
+
+        let at = grid.tick_time(tick);
+        if position < at {
+            thread::sleep(Duration::from_secs_f64(at - position).min(POLL_INTERVAL));
+            continue;
+        }
+        output.send(&[CLOCK]);
+        sent += 1;
+        next = Some(tick + 1);
+    }
+
+    if next.is_some() {
+        output.send(&[STOP]);
+    }
+    output.connection.close();
+    log::info!("MIDI clock finished: {} clocks sent", sent);
+}
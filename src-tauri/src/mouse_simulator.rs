@@ -1,9 +1,35 @@
-use enigo::{Enigo, Settings};
+use crate::error::{AppError, ErrorCode};
+use crate::input_hook;
+use crate::input_service::{self, InputHandle};
+use crate::keypress_simulator::MAX_EVENT_SECS;
+use crate::playback_report::{PlaybackReport, ReportBuilder};
+use crate::state::{PlaybackControl, StartWait, WaitOutcome};
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use uni_input::SmoothMouse;
+use std::time::{Duration, Instant};
+use uni_input::{ClickTiming, DragWaypoint, TracePoint};
+
+/// 鼠标事件的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseAction {
+    #[default]
+    Click,
+    /// 只移动不点击，停留 duration 秒（显示提示、展开悬停菜单），期间不发送下一个鼠标事件
+    Hover,
+    /// 在 (x, y) 按下，依次经过 waypoints 后松开（平移地图、画手势）
+    Drag,
+}
+
+/// 拖动的途经点，duration_ms 为从上一个点移动到这里的用时
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DragPoint {
+    pub x: i32,
+    pub y: i32,
+    pub duration_ms: f64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MouseEvent {
@@ -11,113 +37,212 @@ pub struct MouseEvent {
     pub x: i32,        // X坐标
     pub y: i32,        // Y坐标
     pub duration: f64, // 持续时间（秒）
+    // 可点击区域的半径（像素），随机偏移不会超出；不设置时按档案中的目标大小
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f64>,
+    #[serde(default)]
+    pub action: MouseAction,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waypoints: Vec<DragPoint>,
+    // 设置时坐标（包括途经点）是该显示器上的物理像素，回放前换算为全局坐标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_id: Option<u32>,
 }
 
-// 播放状态管理
-lazy_static::lazy_static! {
-    static ref MOUSE_PLAYBACK_HANDLE: Arc<Mutex<Option<thread::JoinHandle<()>>>> = Arc::new(Mutex::new(None));
-    static ref MOUSE_SHOULD_STOP: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+/// 把按显示器记录的坐标换算为全局坐标，换算后清除 monitor_id
+pub fn resolve_monitor_points(events: &mut [MouseEvent]) -> Result<(), AppError> {
+    for event in events {
+        let Some(monitor_id) = event.monitor_id.take() else {
+            continue;
+        };
+        let resolve = |x: i32, y: i32| {
+            uni_window::from_monitor_point(&uni_window::MonitorPoint { monitor_id, x, y })
+                .map_err(AppError::window)
+        };
+        (event.x, event.y) = resolve(event.x, event.y)?;
+        for point in &mut event.waypoints {
+            (point.x, point.y) = resolve(point.x, point.y)?;
+        }
+    }
+    Ok(())
 }
 
-/// 开始播放鼠标事件序列
-pub fn start_mouse_playback(events: Vec<MouseEvent>) -> Result<(), String> {
-    // 检查是否已有播放在进行
-    {
-        let handle = MOUSE_PLAYBACK_HANDLE.lock().unwrap();
-        if handle.is_some() {
-            return Err("Mouse playback already in progress".to_string());
+/// 检查事件时间和悬停时长：必须是有限数且不超过 MAX_EVENT_SECS，否则播放线程换算等待时间时会溢出
+pub fn check_events(events: &[MouseEvent]) -> Result<(), AppError> {
+    // 负的时间和时长播放时按 0 处理
+    let in_range = |secs: f64| secs.is_finite() && secs <= MAX_EVENT_SECS;
+    for (i, event) in events.iter().enumerate() {
+        if !in_range(event.time) {
+            return Err(AppError::invalid(format!(
+                "Invalid mouse event time: {} (#{})",
+                event.time, i
+            )));
+        }
+        if event.action == MouseAction::Hover && !in_range(event.duration) {
+            return Err(AppError::invalid(format!(
+                "Invalid hover duration: {} (#{})",
+                event.duration, i
+            )));
         }
     }
+    Ok(())
+}
 
-    // 重置停止标志
-    {
-        let mut should_stop = MOUSE_SHOULD_STOP.lock().unwrap();
-        *should_stop = false;
+/// 鼠标移动和点击的方式，来自档案设置
+#[derive(Debug, Clone, Default)]
+pub struct MouseStyle {
+    pub traces: Vec<Arc<[TracePoint]>>, // 每次点击前随机选一条真人轨迹，为空时用合成的贝塞尔曲线
+    pub timing: ClickTiming,
+}
+
+/// 按时间顺序发送的鼠标事件，单独播放或与按键在同一线程中调度
+pub struct MouseTrack {
+    events: Vec<MouseEvent>,
+    next: usize,
+    style: MouseStyle,
+    hover_until: f64, // 悬停结束前不发送下一个事件
+}
+
+impl MouseTrack {
+    pub fn new(events: Vec<MouseEvent>) -> Self {
+        Self {
+            events,
+            next: 0,
+            style: MouseStyle::default(),
+            hover_until: 0.0,
+        }
     }
 
-    // 在新线程中执行播放
-    let handle = thread::spawn(move || {
-        // 创建 Enigo 实例
-        let mut enigo = match Enigo::new(&Settings::default()) {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Failed to create Enigo instance: {:?}", e);
-                return;
-            }
-        };
+    pub fn with_style(mut self, style: MouseStyle) -> Self {
+        self.style = style;
+        self
+    }
 
-        let start_time = std::time::Instant::now();
+    pub fn peek_time(&self) -> Option<f64> {
+        self.events
+            .get(self.next)
+            .map(|e| e.time.max(self.hover_until))
+    }
 
-        for event in events {
-            // 检查是否需要停止
-            {
-                let should_stop = MOUSE_SHOULD_STOP.lock().unwrap();
-                if *should_stop {
-                    break;
-                }
-            }
+    /// 定位到第一个时间不早于 position 的事件
+    pub fn seek(&mut self, position: f64) {
+        self.next = self.events.partition_point(|e| e.time < position);
+        self.hover_until = 0.0;
+    }
 
-            // 等待到事件时间
-            let target_time = Duration::from_secs_f64(event.time);
-            let elapsed = start_time.elapsed();
+    pub fn remaining(&self) -> usize {
+        self.events.len() - self.next
+    }
 
-            if target_time > elapsed {
-                let wait_time = target_time - elapsed;
-                thread::sleep(wait_time);
+    /// 发送下一个事件并记录到报告
+    pub fn fire(
+        &mut self,
+        input: &InputHandle,
+        control: &PlaybackControl,
+        start_time: Instant,
+        report: &mut ReportBuilder,
+    ) {
+        let Some(scheduled) = self.peek_time() else {
+            return;
+        };
+        let event = &self.events[self.next];
+        self.next += 1;
+
+        // 模拟鼠标点击 (调用 uni-input 的 SmoothMouse trait)
+        input_hook::begin_injection();
+        let fired_at = control.song_time(start_time);
+        let timing = match event.radius {
+            Some(radius) => self.style.timing.within_radius(radius),
+            None => self.style.timing,
+        };
+        let trace = self
+            .style
+            .traces
+            .choose(&mut rand::thread_rng())
+            .map(Arc::clone);
+        let result = match (event.action, trace) {
+            (MouseAction::Hover, trace) => input.mouse_hover(event.x, event.y, trace, timing),
+            (MouseAction::Drag, _) if event.waypoints.is_empty() => {
+                Err("Drag event has no waypoints".to_string())
             }
-
-            // 再次检查是否需要停止
-            {
-                let should_stop = MOUSE_SHOULD_STOP.lock().unwrap();
-                if *should_stop {
-                    break;
-                }
+            (MouseAction::Drag, _) => {
+                let waypoints = event
+                    .waypoints
+                    .iter()
+                    .map(|p| DragWaypoint {
+                        x: p.x,
+                        y: p.y,
+                        duration_ms: p.duration_ms,
+                    })
+                    .collect();
+                input.mouse_drag(event.x, event.y, waypoints, timing)
             }
-
-            // 模拟鼠标点击 (调用 uni-input 的 SmoothMouse trait)
-            if let Err(e) = enigo.mouse_click_smooth(event.x, event.y) {
-                 eprintln!("Failed to simulate mouse click: {}", e);
+            (MouseAction::Click, Some(trace)) => {
+                input.mouse_click_trace(event.x, event.y, trace, timing)
             }
+            (MouseAction::Click, None) => input.mouse_click_smooth(event.x, event.y, timing),
+        };
+        if let Err(e) = &result {
+            log::warn!("Failed to simulate mouse {:?}: {}", event.action, e);
         }
-
-        // 播放完成，清理句柄
-        let mut handle = MOUSE_PLAYBACK_HANDLE.lock().unwrap();
-        *handle = None;
-    });
-
-    // 保存线程句柄
-    {
-        let mut playback_handle = MOUSE_PLAYBACK_HANDLE.lock().unwrap();
-        *playback_handle = Some(handle);
+        input_hook::end_injection();
+        if event.action == MouseAction::Hover {
+            self.hover_until = scheduled + event.duration.max(0.0);
+        }
+        report.record(scheduled, fired_at, result.is_ok());
     }
-
-    Ok(())
 }
 
-/// 停止鼠标播放
-pub fn stop_mouse_playback() -> Result<(), String> {
-    // 设置停止标志
-    {
-        let mut should_stop = MOUSE_SHOULD_STOP.lock().unwrap();
-        *should_stop = true;
-    }
-
-    // 等待线程结束
-    let handle = {
-        let mut playback_handle = MOUSE_PLAYBACK_HANDLE.lock().unwrap();
-        playback_handle.take()
-    };
+/// 开始播放鼠标事件序列
+pub fn start_mouse_playback<F>(
+    control: &Arc<PlaybackControl>,
+    events: Vec<MouseEvent>,
+    style: MouseStyle,
+    wait: StartWait,
+    on_finish: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(PlaybackReport) + Send + 'static,
+{
+    check_events(&events)?;
+    // 在启动线程前取得输入线程句柄，初始化失败时直接返回错误
+    let input = input_service::handle()?;
+
+    control.start(wait, move |control| {
+        let mut report = ReportBuilder::new("mouse", events.len());
+        let mut completed = true;
+        let mut start_time = control.clock_start(events.len());
+
+        let mut track = MouseTrack::new(events).with_style(style);
+        while let Some(time) = track.peek_time() {
+            // 等待到事件时间（期间可暂停、停止或跳转）
+            match control.wait_until(Duration::from_secs_f64(time), &mut start_time) {
+                WaitOutcome::Ready => {}
+                WaitOutcome::Seek(position) => {
+                    let before = track.remaining();
+                    track.seek(position);
+                    report.requeue(track.remaining().saturating_sub(before));
+                    control.set_remaining(track.remaining());
+                    continue;
+                }
+                WaitOutcome::Stop => {
+                    completed = false;
+                    break;
+                }
+            }
 
-    if let Some(handle) = handle {
-        let _ = handle.join();
-    }
+            track.fire(&input, control, start_time, &mut report);
+            control.advance(1);
+        }
 
-    Ok(())
+        // 播放完成，句柄由 PlaybackControl 清理
+        on_finish(report.finish(completed));
+    })
 }
 
 /// 选择鼠标坐标
 /// 监听全局鼠标点击事件,返回点击位置的坐标
-pub async fn pick_coordinate() -> Result<(i32, i32), String> {
+pub async fn pick_coordinate() -> Result<(i32, i32), AppError> {
     use rdev::{grab, Button, Event, EventType};
     use std::sync::mpsc::channel;
     use std::sync::{
@@ -191,7 +316,7 @@ pub async fn pick_coordinate() -> Result<(i32, i32), String> {
 
         // 使用 grab 来拦截事件
         if let Err(e) = grab(callback) {
-            eprintln!("监听鼠标事件失败: {:?}", e);
+            log::error!("监听鼠标事件失败: {:?}", e);
         }
     });
 
@@ -229,7 +354,95 @@ pub async fn pick_coordinate() -> Result<(i32, i32), String> {
                 }
             }
 
-            Err("等待鼠标点击超时(30秒)".to_string())
+            Err(AppError::new(ErrorCode::Timeout, "等待鼠标点击超时(30秒)"))
+        }
+    }
+}
+
+/// 选择鼠标坐标，返回所在显示器上的物理像素坐标
+pub async fn pick_monitor_coordinate() -> Result<uni_window::MonitorPoint, AppError> {
+    let (x, y) = pick_coordinate().await?;
+    uni_window::to_monitor_point(x, y).map_err(AppError::window)
+}
+
+/// 框选屏幕区域
+/// 按下鼠标左键拖动到对角再松开，返回屏幕坐标下的矩形
+pub async fn pick_region() -> Result<uni_window::Rect, AppError> {
+    use rdev::{grab, Button, Event, EventType};
+    use std::sync::mpsc::channel;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+    use std::time::Duration;
+
+    // 传递拖动的起点和终点
+    let (tx, rx) = channel::<((i32, i32), (i32, i32))>();
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let stop_flag_clone = stop_flag.clone();
+
+    // 最后的鼠标位置和按下时的位置
+    let last_position = Arc::new(Mutex::new((0, 0)));
+    let press_position: Arc<Mutex<Option<(i32, i32)>>> = Arc::new(Mutex::new(None));
+
+    let _listen_thread = thread::spawn(move || {
+        let callback = move |event: Event| -> Option<Event> {
+            if stop_flag_clone.load(Ordering::Relaxed) {
+                return Some(event);
+            }
+
+            match event.event_type {
+                EventType::MouseMove { x, y } => {
+                    if let Ok(mut pos) = last_position.lock() {
+                        *pos = (x as i32, y as i32);
+                    }
+                    Some(event)
+                }
+                EventType::ButtonPress(Button::Left) => {
+                    // 记录起点并拦截按下事件，避免在游戏中产生点击
+                    let pos = *last_position.lock().unwrap();
+                    *press_position.lock().unwrap() = Some(pos);
+                    None
+                }
+                EventType::ButtonRelease(Button::Left) => {
+                    let start = press_position.lock().unwrap().take();
+                    match start {
+                        Some(start) => {
+                            let end = *last_position.lock().unwrap();
+                            let _ = tx.send((start, end));
+                            stop_flag_clone.store(true, Ordering::Relaxed);
+                            None
+                        }
+                        None => Some(event),
+                    }
+                }
+                _ => Some(event),
+            }
+        };
+
+        if let Err(e) = grab(callback) {
+            log::error!("监听鼠标事件失败: {:?}", e);
+        }
+    });
+
+    // 等待框选完成(30秒超时)
+    let result = rx.recv_timeout(Duration::from_secs(30));
+    stop_flag.store(true, Ordering::Relaxed);
+
+    match result {
+        Ok(((x1, y1), (x2, y2))) => {
+            let rect = uni_window::Rect {
+                x: x1.min(x2),
+                y: y1.min(y2),
+                width: (x1 - x2).unsigned_abs(),
+                height: (y1 - y2).unsigned_abs(),
+            };
+            if rect.width == 0 || rect.height == 0 {
+                return Err(AppError::invalid("框选区域为空"));
+            }
+            Ok(rect)
         }
+        Err(_) => Err(AppError::new(ErrorCode::Timeout, "等待框选区域超时(30秒)")),
     }
 }
@@ -0,0 +1,299 @@
+use crate::error::AppError;
+use crate::input_hook;
+use crate::json_store;
+use rdev::EventType;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uni_input::TracePoint;
+
+// 真人鼠标轨迹库：鼠标回放时把录制的轨迹旋转缩放到起点和目标之间，
+// 代替固定的二次贝塞尔曲线。录制时用户正常移动并点击，每段"移动后点击"保存为一条轨迹。
+
+const TRACE_FILE_NAME: &str = "mouse_traces.json";
+
+// 移动中停顿超过这么久，之前的移动不算入下一次点击的轨迹
+const PAUSE_SPLIT: Duration = Duration::from_millis(250);
+// 太短的移动没有可用的形状
+const MIN_POINTS: usize = 8;
+const MIN_DISTANCE: f64 = 50.0;
+
+// 轨迹上的点 (x, y, 毫秒)
+type Point = (f64, f64, f64);
+
+// 内置的示例轨迹：按最小加加速度模型生成，带弯曲和末端的过冲修正。
+// 录制自己的轨迹后只使用录制的轨迹
+const BUILTIN_TRACES: [(&str, &[Point]); 3] = [
+    (
+        "builtin-arc",
+        &[
+            (0.0, 0.0, 0.0),
+            (0.9, 6.2, 26.0),
+            (6.7, 12.2, 52.0),
+            (20.3, 17.8, 79.0),
+            (43.1, 22.6, 105.0),
+            (74.9, 26.6, 131.0),
+            (114.5, 29.6, 158.0),
+            (159.8, 31.4, 184.0),
+            (208.0, 32.0, 210.0),
+            (256.2, 31.4, 236.0),
+            (301.5, 29.6, 262.0),
+            (341.1, 26.6, 289.0),
+            (372.9, 22.6, 315.0),
+            (395.7, 17.8, 341.0),
+            (409.3, 12.2, 368.0),
+            (415.1, 6.2, 394.0),
+            (416.0, 0.0, 420.0),
+            (410.7, 0.0, 445.0),
+            (405.3, 0.0, 470.0),
+            (400.0, 0.0, 495.0),
+        ],
+    ),
+    (
+        "builtin-flat",
+        &[
+            (0.0, 0.0, 0.0),
+            (0.7, -2.9, 22.0),
+            (5.0, -5.7, 45.0),
+            (15.1, -8.3, 68.0),
+            (32.0, -10.6, 90.0),
+            (55.6, -12.5, 112.0),
+            (85.0, -13.9, 135.0),
+            (118.7, -14.7, 158.0),
+            (154.5, -15.0, 180.0),
+            (190.3, -14.7, 202.0),
+            (224.0, -13.9, 225.0),
+            (253.4, -12.5, 248.0),
+            (277.0, -10.6, 270.0),
+            (293.9, -8.3, 292.0),
+            (304.0, -5.7, 315.0),
+            (308.3, -2.9, 338.0),
+            (309.0, 0.0, 360.0),
+            (306.0, 0.0, 385.0),
+            (303.0, 0.0, 410.0),
+            (300.0, 0.0, 435.0),
+        ],
+    ),
+    (
+        "builtin-wide",
+        &[
+            (0.0, 0.0, 0.0),
+            (1.1, 11.7, 30.0),
+            (8.2, 23.0, 60.0),
+            (24.9, 33.3, 90.0),
+            (52.8, 42.4, 120.0),
+            (91.8, 49.9, 150.0),
+            (140.4, 55.4, 180.0),
+            (195.9, 58.8, 210.0),
+            (255.0, 60.0, 240.0),
+            (314.1, 58.8, 270.0),
+            (369.6, 55.4, 300.0),
+            (418.2, 49.9, 330.0),
+            (457.2, 42.4, 360.0),
+            (485.1, 33.3, 390.0),
+            (501.8, 23.0, 420.0),
+            (508.9, 11.7, 450.0),
+            (510.0, 0.0, 480.0),
+            (506.7, 0.0, 505.0),
+            (503.3, 0.0, 530.0),
+            (500.0, 0.0, 555.0),
+        ],
+    ),
+];
+
+/// 一条轨迹，points 为 (x, y, 毫秒)，坐标和时间都相对第一个点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseTrace {
+    pub name: String,
+    pub points: Vec<Point>,
+}
+
+impl MouseTrace {
+    fn summary(&self, builtin: bool) -> TraceSummary {
+        let (x, y, t_ms) = self.points.last().copied().unwrap_or_default();
+        TraceSummary {
+            name: self.name.clone(),
+            builtin,
+            points: self.points.len(),
+            distance: x.hypot(y),
+            duration_ms: t_ms,
+        }
+    }
+
+    fn to_points(&self) -> Arc<[TracePoint]> {
+        self.points
+            .iter()
+            .map(|&(x, y, t_ms)| TracePoint { x, y, t_ms })
+            .collect()
+    }
+}
+
+/// 轨迹概要，供前端列表显示
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceSummary {
+    pub name: String,
+    pub builtin: bool,
+    pub points: usize,
+    pub distance: f64, // 起点到终点的像素距离
+    pub duration_ms: f64,
+}
+
+/// 轨迹库，录制的轨迹保存在数据目录的 mouse_traces.json 中
+pub struct MouseTraceLibrary {
+    builtin: Vec<MouseTrace>,
+    recorded: RwLock<Vec<MouseTrace>>,
+    recording: AtomicBool,
+    path: PathBuf,
+}
+
+impl MouseTraceLibrary {
+    pub fn load(data_dir: PathBuf) -> Self {
+        let path = data_dir.join(TRACE_FILE_NAME);
+        let recorded = match json_store::load::<Vec<MouseTrace>>(&path) {
+            Ok(traces) => traces.unwrap_or_default(),
+            Err(e) => {
+                log::error!("Failed to load mouse traces: {}", e);
+                Vec::new()
+            }
+        };
+        let builtin = BUILTIN_TRACES
+            .iter()
+            .map(|(name, points)| MouseTrace {
+                name: name.to_string(),
+                points: points.to_vec(),
+            })
+            .collect();
+        Self {
+            builtin,
+            recorded: RwLock::new(recorded),
+            recording: AtomicBool::new(false),
+            path,
+        }
+    }
+
+    pub fn list(&self) -> Vec<TraceSummary> {
+        let recorded = self.recorded.read().unwrap();
+        self.builtin
+            .iter()
+            .map(|t| t.summary(true))
+            .chain(recorded.iter().map(|t| t.summary(false)))
+            .collect()
+    }
+
+    /// 回放时可选的轨迹：有录制的轨迹时只用录制的
+    pub fn playback_set(&self) -> Vec<Arc<[TracePoint]>> {
+        let recorded = self.recorded.read().unwrap();
+        let traces = if recorded.is_empty() {
+            &self.builtin
+        } else {
+            &*recorded
+        };
+        traces.iter().map(MouseTrace::to_points).collect()
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        if self.builtin.iter().any(|t| t.name == name) {
+            return Err(
+                AppError::invalid("Built-in mouse traces cannot be deleted").with_context(name)
+            );
+        }
+        let mut recorded = self.recorded.write().unwrap();
+        let before = recorded.len();
+        recorded.retain(|t| t.name != name);
+        if recorded.len() == before {
+            return Err(AppError::not_found("Mouse trace not found").with_context(name));
+        }
+        json_store::save(&self.path, &*recorded)?;
+        Ok(())
+    }
+
+    /// 开始录制：之后用户的每段移动并点击都会成为一条轨迹
+    pub fn start_recording(&self) -> Result<(), AppError> {
+        if self.recording.swap(true, Ordering::SeqCst) {
+            return Err(AppError::invalid(
+                "Mouse trace recording already in progress",
+            ));
+        }
+        input_hook::ensure_started();
+        input_hook::start_capture();
+        Ok(())
+    }
+
+    /// 结束录制，保存切分出的轨迹并返回它们的概要
+    pub fn stop_recording(&self, name_prefix: &str) -> Result<Vec<TraceSummary>, AppError> {
+        if !self.recording.swap(false, Ordering::SeqCst) {
+            return Err(AppError::invalid("Mouse trace recording is not running"));
+        }
+        let segments = split_segments(&input_hook::stop_capture());
+
+        let mut recorded = self.recorded.write().unwrap();
+        let mut added = Vec::new();
+        let mut number = recorded.len() + 1;
+        for points in segments {
+            let name = loop {
+                let name = format!("{} {}", name_prefix, number);
+                number += 1;
+                if !recorded.iter().any(|t| t.name == name) {
+                    break name;
+                }
+            };
+            let trace = MouseTrace { name, points };
+            added.push(trace.summary(false));
+            recorded.push(trace);
+        }
+        if !added.is_empty() {
+            json_store::save(&self.path, &*recorded)?;
+        }
+        log::info!("Recorded {} mouse traces", added.len());
+        Ok(added)
+    }
+}
+
+// 按点击切分捕获的事件：点击前一段连续的移动为一条轨迹
+fn split_segments(events: &[(Instant, EventType)]) -> Vec<Vec<Point>> {
+    let mut segments = Vec::new();
+    let mut current: Vec<(Instant, f64, f64)> = Vec::new();
+    for (at, event) in events {
+        match *event {
+            EventType::MouseMove { x, y } => {
+                if current
+                    .last()
+                    .is_some_and(|(last, _, _)| at.duration_since(*last) > PAUSE_SPLIT)
+                {
+                    current.clear();
+                }
+                current.push((*at, x, y));
+            }
+            EventType::ButtonPress(_) => {
+                if let Some(points) = normalize(&current) {
+                    segments.push(points);
+                }
+                current.clear();
+            }
+            _ => {}
+        }
+    }
+    segments
+}
+
+fn normalize(points: &[(Instant, f64, f64)]) -> Option<Vec<Point>> {
+    let &(start, x0, y0) = points.first()?;
+    let &(_, x1, y1) = points.last()?;
+    if points.len() < MIN_POINTS || (x1 - x0).hypot(y1 - y0) < MIN_DISTANCE {
+        return None;
+    }
+    Some(
+        points
+            .iter()
+            .map(|&(at, x, y)| {
+                (
+                    x - x0,
+                    y - y0,
+                    at.duration_since(start).as_secs_f64() * 1000.0,
+                )
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,216 @@
+use crate::guitar_pro::extend_tied;
+use crate::score_import::{ImportedNote, ImportedSong, ImportedTrack, TICKS_PER_BEAT};
+use crate::xml_tree::{parse_xml, Node};
+use std::collections::BTreeSet;
+
+// 支持 score-partwise 格式：音高、时值、和弦、连音线、多声部（backup/forward）、
+// 拍号和速度标记。装饰音和无音高的打击乐音符忽略。
+
+const DEFAULT_VELOCITY: u8 = 90;
+// C D E F G A B 的自然音半音数
+const STEP_SEMITONES: [(&str, i32); 7] = [
+    ("C", 0),
+    ("D", 2),
+    ("E", 4),
+    ("F", 5),
+    ("G", 7),
+    ("A", 9),
+    ("B", 11),
+];
+
+/// 解析结果，附带小节时值检查，用于评估识别质量
+pub struct ParsedScore {
+    pub song: ImportedSong,
+    pub measures: usize,
+    // 实际时值与拍号不符的小节（从 1 开始）
+    pub suspect_measures: Vec<usize>,
+}
+
+fn number<T: std::str::FromStr>(node: Option<&Node>) -> Option<T> {
+    node.and_then(|n| n.text().parse().ok())
+}
+
+fn note_key(pitch: &Node) -> Option<u8> {
+    let step = pitch.child("step")?.text();
+    let (_, semitone) = STEP_SEMITONES.iter().find(|(s, _)| *s == step)?;
+    let alter = number::<f64>(pitch.child("alter")).unwrap_or(0.0).round() as i32;
+    let octave: i32 = number(pitch.child("octave"))?;
+    let key = (octave + 1) * 12 + semitone + alter;
+    (0..=127).contains(&key).then_some(key as u8)
+}
+
+// 拍号分子可能是 "3+2" 这样的复合形式
+fn time_signature(time: &Node) -> Option<(u32, u32)> {
+    let beats = time
+        .child("beats")?
+        .text()
+        .split('+')
+        .map(|b| b.trim().parse::<u32>().ok())
+        .sum::<Option<u32>>()?;
+    let beat_type = number(time.child("beat-type"))?;
+    (beats > 0 && beat_type > 0).then_some((beats, beat_type))
+}
+
+// 小节内的位置（以 divisions 为单位）换算为 tick
+fn to_tick(measure_tick: u32, pos: i64, divisions: u32) -> u32 {
+    measure_tick + (pos.max(0) as u64 * TICKS_PER_BEAT as u64 / divisions as u64) as u32
+}
+
+fn tempo(node: &Node) -> Option<f64> {
+    node.attr("tempo")
+        .and_then(|t| t.parse::<f64>().ok())
+        .filter(|t| *t > 0.0)
+}
+
+pub fn parse(text: &str) -> Result<ParsedScore, String> {
+    let root = parse_xml(text)?;
+    if root.name != "score-partwise" {
+        return Err(format!("Unsupported MusicXML document: <{}>", root.name));
+    }
+
+    let mut song = ImportedSong {
+        title: root
+            .path(&["work", "work-title"])
+            .or_else(|| root.child("movement-title"))
+            .map(|n| n.text().to_string())
+            .filter(|t| !t.is_empty()),
+        composer: root
+            .child("identification")
+            .and_then(|i| {
+                i.children_named("creator")
+                    .find(|c| c.attr("type") == Some("composer"))
+            })
+            .map(|n| n.text().to_string())
+            .filter(|t| !t.is_empty()),
+        ..Default::default()
+    };
+
+    let part_names: Vec<(&str, &str)> = root
+        .child("part-list")
+        .map(|list| {
+            list.children_named("score-part")
+                .map(|p| {
+                    let name = p.child("part-name").map_or("", |n| n.text());
+                    (p.attr("id").unwrap_or(""), name)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut measure_count = 0;
+    let mut suspect = BTreeSet::new();
+    for (part_index, part) in root.children_named("part").enumerate() {
+        // 速度和拍号只取第一个声部
+        let conductor = part_index == 0;
+        let mut divisions = 1u32;
+        let mut signature = (4u32, 4u32);
+        let mut measure_tick = 0u32;
+        let mut notes: Vec<ImportedNote> = Vec::new();
+
+        let measures: Vec<&Node> = part.children_named("measure").collect();
+        measure_count = measure_count.max(measures.len());
+        for (index, measure) in measures.into_iter().enumerate() {
+            let mut pos = 0i64;
+            let mut max_pos = 0i64;
+            let mut chord_start = 0i64;
+
+            for child in &measure.children {
+                match child.name.as_str() {
+                    "attributes" => {
+                        if let Some(d) = number::<u32>(child.child("divisions")).filter(|d| *d > 0)
+                        {
+                            divisions = d;
+                        }
+                        if let Some(sig) = child.child("time").and_then(time_signature) {
+                            signature = sig;
+                            if conductor {
+                                song.time_signatures.push((
+                                    to_tick(measure_tick, pos, divisions),
+                                    sig.0.min(255) as u8,
+                                    sig.1.min(255) as u8,
+                                ));
+                            }
+                        }
+                    }
+                    "backup" => pos -= number::<i64>(child.child("duration")).unwrap_or(0),
+                    "forward" => pos += number::<i64>(child.child("duration")).unwrap_or(0),
+                    "sound" | "direction" if conductor => {
+                        let sound = if child.name == "sound" {
+                            Some(child)
+                        } else {
+                            child.child("sound")
+                        };
+                        if let Some(bpm) = sound.and_then(tempo) {
+                            song.tempo_changes
+                                .push((to_tick(measure_tick, pos, divisions), bpm));
+                        }
+                    }
+                    "note" => {
+                        if child.child("grace").is_some() || child.child("cue").is_some() {
+                            continue;
+                        }
+                        let duration = number::<i64>(child.child("duration")).unwrap_or(0);
+                        let start = if child.child("chord").is_some() {
+                            chord_start
+                        } else {
+                            chord_start = pos;
+                            pos += duration;
+                            chord_start
+                        };
+                        max_pos = max_pos.max(pos);
+
+                        let Some(key) = child.child("pitch").and_then(note_key) else {
+                            continue;
+                        };
+                        let tick = to_tick(measure_tick, start, divisions);
+                        let length = to_tick(measure_tick, start + duration, divisions) - tick;
+                        if length == 0 {
+                            continue;
+                        }
+                        let tied = child
+                            .children_named("tie")
+                            .any(|t| t.attr("type") == Some("stop"));
+                        if tied && extend_tied(&mut notes, key, tick, length) {
+                            continue;
+                        }
+                        notes.push(ImportedNote {
+                            tick,
+                            length,
+                            key,
+                            velocity: DEFAULT_VELOCITY,
+                        });
+                    }
+                    _ => {}
+                }
+                max_pos = max_pos.max(pos);
+            }
+
+            // 第一小节和标记为不完整的小节允许是弱起
+            let expected = signature.0 as i64 * 4 * divisions as i64 / signature.1 as i64;
+            let pickup = index == 0 || measure.attr("implicit") == Some("yes");
+            if max_pos > expected || (max_pos < expected && !pickup) {
+                suspect.insert(index + 1);
+            }
+            measure_tick = to_tick(measure_tick, max_pos, divisions);
+        }
+
+        let name = part
+            .attr("id")
+            .and_then(|id| part_names.iter().find(|(pid, _)| *pid == id))
+            .map_or("", |(_, name)| *name);
+        song.tracks.push(ImportedTrack {
+            name: name.to_string(),
+            channel: (song.tracks.len() % 9) as u8,
+            notes,
+        });
+    }
+
+    if song.tracks.is_empty() {
+        return Err("No parts found in MusicXML".to_string());
+    }
+    Ok(ParsedScore {
+        song,
+        measures: measure_count,
+        suspect_measures: suspect.into_iter().collect(),
+    })
+}
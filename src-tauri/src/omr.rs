@@ -0,0 +1,171 @@
+use crate::musicxml;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// 通过 Audiveris 命令行做光学乐谱识别（实验性）：
+// 识别结果导出为未压缩的 MusicXML，再转换为 MIDI 文件交给分析流程。
+
+const DEFAULT_AUDIVERIS: &str = "audiveris";
+const SHEETS_DIR: &str = "sheets";
+// 关闭导出压缩，直接得到 .xml 而不是 .mxl
+const UNCOMPRESSED_OPTION: &str = "org.audiveris.omr.sheet.BookManager.useCompression=false";
+// 报告中最多保留的识别警告行数
+const MAX_WARNINGS: usize = 50;
+
+/// 识别质量报告。可信度按时值与拍号相符的小节比例估算，只作参考
+#[derive(Debug, Clone, Serialize)]
+pub struct SheetImportReport {
+    pub measures: usize,
+    pub suspect_measures: Vec<usize>,
+    pub notes: usize,
+    // 乐谱被拆分成多个乐章时只导入第一个
+    pub movements: usize,
+    pub recognizer_warnings: Vec<String>,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SheetImport {
+    // 生成的 MIDI 文件，可直接交给 parse_midi 分析
+    pub midi_path: String,
+    pub report: SheetImportReport,
+}
+
+/// 识别乐谱图片或 PDF，结果保存在数据目录下
+pub fn import_sheet_image(
+    data_dir: &Path,
+    file_path: &str,
+    audiveris_path: Option<&str>,
+) -> Result<SheetImport, String> {
+    let input = Path::new(file_path);
+    if !input.is_file() {
+        return Err(format!("File not found: {}", file_path));
+    }
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sheet".to_string());
+
+    let sheets_dir = data_dir.join(SHEETS_DIR);
+    let output_dir = sheets_dir.join(&stem);
+    // 清掉上次的结果，避免读到旧文件
+    if output_dir.exists() {
+        std::fs::remove_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to clear output directory: {}", e))?;
+    }
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let warnings = run_audiveris(
+        audiveris_path.unwrap_or(DEFAULT_AUDIVERIS),
+        input,
+        &output_dir,
+    )?;
+
+    let mut exported = Vec::new();
+    collect_exports(&output_dir, &mut exported);
+    exported.sort();
+    let Some(first) = exported.first() else {
+        return Err("Audiveris did not recognize any music in the file".to_string());
+    };
+    let text =
+        std::fs::read_to_string(first).map_err(|e| format!("Failed to read MusicXML: {}", e))?;
+    let parsed = musicxml::parse(&text)?;
+
+    let notes = parsed.song.tracks.iter().map(|t| t.notes.len()).sum();
+    let confidence = if parsed.measures == 0 {
+        0.0
+    } else {
+        1.0 - parsed.suspect_measures.len() as f64 / parsed.measures as f64
+    };
+    let report = SheetImportReport {
+        measures: parsed.measures,
+        suspect_measures: parsed.suspect_measures,
+        notes,
+        movements: exported.len(),
+        recognizer_warnings: warnings,
+        confidence,
+    };
+
+    let midi_path = sheets_dir.join(format!("{}.mid", stem));
+    std::fs::write(&midi_path, parsed.song.to_smf_bytes()?)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    log::info!(
+        "Recognized {} notes in {} measures from {}, confidence {:.2}",
+        report.notes,
+        report.measures,
+        file_path,
+        report.confidence
+    );
+
+    Ok(SheetImport {
+        midi_path: midi_path.display().to_string(),
+        report,
+    })
+}
+
+// 以批处理模式运行 Audiveris，返回输出中的警告行
+fn run_audiveris(program: &str, input: &Path, output_dir: &Path) -> Result<Vec<String>, String> {
+    let mut command = Command::new(program);
+    command
+        .arg("-batch")
+        .arg("-export")
+        .arg("-option")
+        .arg(UNCOMPRESSED_OPTION)
+        .arg("-output")
+        .arg(output_dir)
+        .arg("--")
+        .arg(input);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW，不弹出控制台窗口
+        command.creation_flags(0x0800_0000);
+    }
+
+    let output = command.output().map_err(|e| {
+        format!(
+            "Failed to run Audiveris ({}): {}. Install Audiveris or set its path",
+            program, e
+        )
+    })?;
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !output.status.success() {
+        let tail: Vec<&str> = log.lines().rev().take(5).collect();
+        return Err(format!(
+            "Audiveris failed ({}): {}",
+            output.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ));
+    }
+
+    Ok(log
+        .lines()
+        .filter(|line| line.contains("WARN") || line.contains("ERROR"))
+        .take(MAX_WARNINGS)
+        .map(|line| line.trim().to_string())
+        .collect())
+}
+
+// Audiveris 会在输出目录下再建以书名命名的子目录
+fn collect_exports(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_exports(&path, found);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "xml" || ext == "musicxml")
+        {
+            found.push(path);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::warning::Warning;
+use serde::Serialize;
+use std::time::Instant;
+
+// 平均偏差超过该值时提示计时不准
+const HIGH_JITTER_MS: f64 = 20.0;
+// 偏差直方图：每格 0.1ms，最后一格收集 50ms 以上的偏差，内存占用与事件数无关
+const HISTOGRAM_STEP_MS: f64 = 0.1;
+const HISTOGRAM_BUCKETS: usize = 501;
+
+/// 一次播放结束后的统计报告
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackReport {
+    pub kind: String, // "keyboard" | "mouse" | "combined"
+    pub total_events: usize,
+    pub played: usize,
+    pub failed: usize,
+    pub skipped: usize,  // 停止时尚未播放的事件
+    pub completed: bool, // 是否完整播放（未被停止）
+    pub duration_secs: f64,
+    pub avg_jitter_ms: f64, // 实际发送时间与计划时间的平均偏差
+    pub max_jitter_ms: f64,
+    pub p95_jitter_ms: f64,
+    pub avg_offset_ms: f64, // 带符号的平均偏差，正数表示整体偏晚
+    pub warnings: Vec<Warning>,
+}
+
+/// 播放线程中逐个事件累计统计
+pub struct ReportBuilder {
+    kind: &'static str,
+    total_events: usize,
+    played: usize,
+    failed: usize,
+    jitter_sum_ms: f64,
+    offset_sum_ms: f64,
+    max_jitter_ms: f64,
+    histogram: Vec<usize>,
+    started_at: Instant,
+}
+
+impl ReportBuilder {
+    pub fn new(kind: &'static str, total_events: usize) -> Self {
+        Self {
+            kind,
+            total_events,
+            played: 0,
+            failed: 0,
+            jitter_sum_ms: 0.0,
+            offset_sum_ms: 0.0,
+            max_jitter_ms: 0.0,
+            histogram: vec![0; HISTOGRAM_BUCKETS],
+            started_at: Instant::now(),
+        }
+    }
+
+    /// 循环或往回跳转后重新排队的事件，计入总数（同一事件会被播放多次）
+    pub fn requeue(&mut self, count: usize) {
+        self.total_events += count;
+    }
+
+    /// 记录一个事件的计划时间和实际发送时间（秒）
+    pub fn record(&mut self, scheduled: f64, actual: f64, ok: bool) {
+        if !ok {
+            self.failed += 1;
+            return;
+        }
+        let offset_ms = (actual - scheduled) * 1000.0;
+        let jitter_ms = offset_ms.abs();
+        self.played += 1;
+        self.jitter_sum_ms += jitter_ms;
+        self.offset_sum_ms += offset_ms;
+        self.max_jitter_ms = self.max_jitter_ms.max(jitter_ms);
+        let bucket = ((jitter_ms / HISTOGRAM_STEP_MS) as usize).min(HISTOGRAM_BUCKETS - 1);
+        self.histogram[bucket] += 1;
+    }
+
+    // 从直方图估算百分位，结果取所在格的上界
+    fn percentile(&self, ratio: f64) -> f64 {
+        let target = (self.played as f64 * ratio).ceil() as usize;
+        let mut count = 0;
+        for (bucket, n) in self.histogram.iter().enumerate() {
+            count += n;
+            if count >= target {
+                return ((bucket + 1) as f64 * HISTOGRAM_STEP_MS).min(self.max_jitter_ms);
+            }
+        }
+        self.max_jitter_ms
+    }
+
+    pub fn finish(self, completed: bool) -> PlaybackReport {
+        let (avg_jitter_ms, avg_offset_ms) = if self.played > 0 {
+            (
+                self.jitter_sum_ms / self.played as f64,
+                self.offset_sum_ms / self.played as f64,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        let mut warnings = Vec::new();
+        if self.failed > 0 {
+            warnings.push(Warning::EventsFailed { count: self.failed });
+        }
+        if avg_jitter_ms > HIGH_JITTER_MS {
+            warnings.push(Warning::HighJitter {
+                avg_ms: avg_jitter_ms,
+            });
+        }
+
+        PlaybackReport {
+            kind: self.kind.to_string(),
+            total_events: self.total_events,
+            played: self.played,
+            failed: self.failed,
+            skipped: self.total_events.saturating_sub(self.played + self.failed),
+            completed,
+            duration_secs: self.started_at.elapsed().as_secs_f64(),
+            avg_jitter_ms,
+            max_jitter_ms: self.max_jitter_ms,
+            p95_jitter_ms: self.percentile(0.95),
+            avg_offset_ms,
+            warnings,
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::after_playback;
+use crate::keypress_simulator::{KeyEvent, KeyPlaybackMode, MAX_EVENT_SECS};
+use crate::playback_report::PlaybackReport;
+use crate::session_stats::NowPlaying;
+use crate::sleep_inhibit::SleepInhibitGuard;
+use crate::state::AppState;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+// 连续播放多首曲子：曲间留出间隔，每首可以单独设置倒计时，
+// 播放中可以继续添加、打乱、跳过，每次切换都通过 "queue://transition" 事件通知前端。
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 队列中的一首曲子
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueSong {
+    pub title: Option<String>,
+    pub file_path: Option<String>,
+    pub events: Vec<KeyEvent>,
+    #[serde(default)]
+    pub mode: KeyPlaybackMode,
+    // 覆盖队列的默认倒计时
+    pub lead_in_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueSettings {
+    pub gap_secs: f64, // 上一首结束到下一首开始的间隔
+    pub lead_in_secs: Option<f64>,
+    // 关闭后每首播完都等待 skip 再开始下一首
+    pub auto_advance: bool,
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        Self {
+            gap_secs: 0.0,
+            lead_in_secs: None,
+            auto_advance: true,
+        }
+    }
+}
+
+/// 队列内容，供前端显示
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSnapshot {
+    pub titles: Vec<Option<String>>,
+    pub current_index: usize, // 正在播放或下一首要播放的位置
+    pub running: bool,
+    pub settings: QueueSettings,
+}
+
+/// 队列切换事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueueTransition {
+    SongStarted { index: usize, title: Option<String> },
+    SongFinished { index: usize, completed: bool },
+    Gap { next_index: usize, seconds: f64 },
+    // 未开启自动切换，等待 skip
+    AwaitingNext { next_index: usize },
+    Skipped { index: usize },
+    Failed { index: usize, message: String },
+    // 全部播完，或设置了"播完当前曲目后停止"
+    Finished { stopped_after_current: bool },
+    Stopped,
+}
+
+/// 开始播放一首曲子，播放结束时调用传入的回调
+pub type PlaySong =
+    fn(&AppHandle, &QueueSong, Box<dyn FnOnce(PlaybackReport) + Send>) -> Result<(), String>;
+
+#[derive(Default)]
+struct Queue {
+    songs: Vec<QueueSong>,
+    current: usize,
+    playing: bool, // current 指向的曲子正在播放
+    settings: QueueSettings,
+}
+
+/// 队列内容和运行状态，由 AppState 持有
+#[derive(Default)]
+pub struct QueueRunner {
+    queue: Mutex<Queue>,
+    running: AtomicBool,
+    cancelled: AtomicBool,
+    stop_after_current: AtomicBool,
+    skip: AtomicBool,
+}
+
+impl QueueRunner {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        let queue = self.queue.lock().unwrap();
+        QueueSnapshot {
+            titles: queue.songs.iter().map(|s| s.title.clone()).collect(),
+            current_index: queue.current,
+            running: self.is_running(),
+            settings: queue.settings.clone(),
+        }
+    }
+
+    /// 追加到队尾，播放中也可以添加，返回队列长度
+    pub fn enqueue(&self, songs: Vec<QueueSong>) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        queue.songs.extend(songs);
+        queue.songs.len()
+    }
+
+    /// 清除还没播放的曲子，正在播放的不受影响
+    pub fn clear(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        if self.is_running() {
+            let keep = queue.current + queue.playing as usize;
+            queue.songs.truncate(keep);
+        } else {
+            queue.songs.clear();
+            queue.current = 0;
+        }
+    }
+
+    /// 打乱还没播放的曲子
+    pub fn shuffle(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        let first = queue.current + queue.playing as usize;
+        if first < queue.songs.len() {
+            queue.songs[first..].shuffle(&mut rand::thread_rng());
+        }
+    }
+
+    pub fn set_settings(&self, settings: QueueSettings) -> Result<(), String> {
+        validate_settings(&settings)?;
+        self.queue.lock().unwrap().settings = settings;
+        Ok(())
+    }
+
+    /// 跳到下一首：正在播放的曲子由调用方停止，处于间隔中时立即开始下一首
+    pub fn skip(&self) {
+        if self.is_running() {
+            self.skip.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// 结束队列；正在播放的曲子由调用方停止
+    pub fn stop(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// 当前曲子播完后停止，不再开始下一首
+    pub fn set_stop_after_current(&self, enabled: bool) {
+        self.stop_after_current.store(enabled, Ordering::SeqCst);
+    }
+}
+
+// 曲间间隔的上限（一天），再长就没有意义了
+const MAX_GAP_SECS: f64 = 86_400.0;
+
+fn validate_settings(settings: &QueueSettings) -> Result<(), String> {
+    if !(0.0..=MAX_GAP_SECS).contains(&settings.gap_secs) {
+        return Err(format!("Invalid gap: {}", settings.gap_secs));
+    }
+    if let Some(secs) = settings
+        .lead_in_secs
+        .filter(|s| !(0.0..=MAX_EVENT_SECS).contains(s))
+    {
+        return Err(format!("Invalid lead-in: {}", secs));
+    }
+    Ok(())
+}
+
+/// 在后台线程中依次播放队列。传入曲子时替换整个队列，否则从上次停下的位置继续播放已添加的曲子
+pub fn start(
+    app: &AppHandle,
+    songs: Option<Vec<QueueSong>>,
+    settings: Option<QueueSettings>,
+    play: PlaySong,
+) -> Result<(), String> {
+    if let Some(settings) = &settings {
+        validate_settings(settings)?;
+    }
+    let state = app.state::<AppState>();
+    let runner = &state.queue;
+    {
+        let mut queue = runner.queue.lock().unwrap();
+        // 先检查是否在播放，失败时不能改动正在进行的队列
+        if state.is_any_playing() || runner.running.swap(true, Ordering::SeqCst) {
+            return Err("Playback already in progress".to_string());
+        }
+        if let Some(songs) = songs.filter(|s| !s.is_empty()) {
+            queue.songs = songs;
+            queue.current = 0;
+        }
+        if queue.current >= queue.songs.len() {
+            runner.running.store(false, Ordering::SeqCst);
+            return Err("Queue is empty".to_string());
+        }
+        if let Some(settings) = settings {
+            queue.settings = settings;
+        }
+    }
+    runner.cancelled.store(false, Ordering::SeqCst);
+    runner.stop_after_current.store(false, Ordering::SeqCst);
+    runner.skip.store(false, Ordering::SeqCst);
+
+    let app = app.clone();
+    thread::spawn(move || {
+        // 曲间间隔也不能休眠
+        let _awake = SleepInhibitGuard::acquire();
+        let transition = run(&app, play);
+        app.state::<AppState>()
+            .queue
+            .running
+            .store(false, Ordering::SeqCst);
+        let completed = matches!(transition, QueueTransition::Finished { .. });
+        let _ = app.emit("queue://transition", transition);
+        after_playback::on_finished(&app, completed);
+    });
+    Ok(())
+}
+
+// 返回队列结束时的事件
+fn run(app: &AppHandle, play: PlaySong) -> QueueTransition {
+    let state = app.state::<AppState>();
+    let runner = &state.queue;
+    let emit = |transition: QueueTransition| {
+        let _ = app.emit("queue://transition", transition);
+    };
+
+    let mut first = true;
+    let mut finished_songs = 0;
+    loop {
+        let (index, settings) = {
+            let queue = runner.queue.lock().unwrap();
+            if queue.current >= queue.songs.len() {
+                return QueueTransition::Finished {
+                    stopped_after_current: false,
+                };
+            }
+            (queue.current, queue.settings.clone())
+        };
+
+        if !first {
+            let song_limit = state.after_playback.lock().unwrap().stop_after_songs;
+            if runner.stop_after_current.load(Ordering::SeqCst)
+                || song_limit.is_some_and(|limit| finished_songs >= limit)
+            {
+                return QueueTransition::Finished {
+                    stopped_after_current: true,
+                };
+            }
+            let seconds = if settings.auto_advance {
+                emit(QueueTransition::Gap {
+                    next_index: index,
+                    seconds: settings.gap_secs,
+                });
+                Some(settings.gap_secs)
+            } else {
+                emit(QueueTransition::AwaitingNext { next_index: index });
+                None
+            };
+            if !wait_gap(&state, seconds) {
+                return QueueTransition::Stopped;
+            }
+        }
+        first = false;
+
+        // 间隔中队列可能被打乱或清空，重新取当前曲子
+        let (mut song, length, next_title) = {
+            let mut queue = runner.queue.lock().unwrap();
+            let Some(song) = queue.songs.get(index).cloned() else {
+                return QueueTransition::Finished {
+                    stopped_after_current: false,
+                };
+            };
+            queue.playing = true;
+            let next_title = queue.songs.get(index + 1).and_then(|s| s.title.clone());
+            (song, queue.songs.len(), next_title)
+        };
+
+        song.lead_in_secs = song.lead_in_secs.or(settings.lead_in_secs);
+        runner.skip.store(false, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        let on_finish = Box::new(move |report: PlaybackReport| {
+            let _ = sender.send(report.completed);
+        });
+        if let Err(message) = play(app, &song, on_finish) {
+            runner.queue.lock().unwrap().playing = false;
+            log::warn!("Failed to start queued song {}: {}", index, message);
+            emit(QueueTransition::Failed { index, message });
+            return QueueTransition::Stopped;
+        }
+        *state.now_playing.lock().unwrap() = NowPlaying {
+            title: song.title.clone(),
+            file_path: song.file_path.clone(),
+            queue_index: Some(index),
+            queue_length: Some(length),
+            next_title,
+        };
+        emit(QueueTransition::SongStarted {
+            index,
+            title: song.title.clone(),
+        });
+
+        // 发送端随播放线程一起释放，线程异常退出时视为未完成
+        let completed = receiver.recv().unwrap_or(false);
+        {
+            let mut queue = runner.queue.lock().unwrap();
+            queue.current = index + 1;
+            queue.playing = false;
+        }
+        if runner.cancelled.load(Ordering::SeqCst) {
+            emit(QueueTransition::SongFinished { index, completed });
+            return QueueTransition::Stopped;
+        }
+        if !completed && runner.skip.swap(false, Ordering::SeqCst) {
+            emit(QueueTransition::Skipped { index });
+            continue;
+        }
+        emit(QueueTransition::SongFinished { index, completed });
+        if !completed {
+            return QueueTransition::Stopped;
+        }
+        finished_songs += 1;
+    }
+}
+
+// 等待上一首的播放线程退出并度过间隔（None 表示一直等到 skip），
+// skip 时立即结束间隔，队列被停止时返回 false
+fn wait_gap(state: &AppState, seconds: Option<f64>) -> bool {
+    let deadline = seconds.map(|s| Instant::now() + Duration::from_secs_f64(s));
+    let mut skipped = false;
+    loop {
+        if state.queue.cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        skipped |= state.queue.skip.swap(false, Ordering::SeqCst);
+        let elapsed = skipped || deadline.is_some_and(|d| Instant::now() >= d);
+        if elapsed && !state.keyboard.is_playing() {
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
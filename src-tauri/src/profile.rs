@@ -0,0 +1,346 @@
+use crate::json_store;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+const DEFAULT_PROFILE_NAME: &str = "default";
+const PROFILE_FILE_NAME: &str = "profiles.json";
+// 停止时等待播放线程的最短时限，太短会把正常结束的线程也当作卡住
+pub const MIN_STOP_TIMEOUT_MS: u64 = 100;
+// 输出延迟补偿的上限（毫秒）
+pub const MAX_OUTPUT_LATENCY_MS: f64 = 1000.0;
+// 空闲自动恢复的最长等待（秒）
+const MAX_IDLE_RESUME_SECS: f64 = 86_400.0;
+
+/// 播放前激活锁定窗口的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationMode {
+    Always,      // 每次都激活（失焦会暂停的游戏）
+    IfUnfocused, // 仅当窗口不在前台时激活
+    Never,       // 从不激活（有切屏检测的游戏）
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivationSettings {
+    pub mode: ActivationMode,
+    pub wait_ms: u64, // 激活后等待窗口获得焦点的时间（毫秒）
+    pub verify: bool, // 等待后确认窗口确实位于前台
+}
+
+impl Default for ActivationSettings {
+    fn default() -> Self {
+        Self {
+            mode: ActivationMode::Always,
+            wait_ms: 500,
+            verify: false,
+        }
+    }
+}
+
+/// 播放期间锁定窗口失去焦点时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusGuardMode {
+    Off,        // 不检测
+    Pause,      // 暂停播放
+    Reactivate, // 重新激活锁定窗口
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusGuardSettings {
+    pub mode: FocusGuardMode,
+    pub poll_ms: u64,      // 前台窗口检测间隔（毫秒）
+    pub auto_resume: bool, // Pause 模式下窗口回到前台后自动继续
+    // 每隔多少秒重新激活一次锁定窗口，有些游戏长时间后会丢失键盘焦点
+    pub reassert_secs: Option<u64>,
+}
+
+impl Default for FocusGuardSettings {
+    fn default() -> Self {
+        Self {
+            mode: FocusGuardMode::Off,
+            poll_ms: 250,
+            auto_resume: true,
+            reassert_secs: None,
+        }
+    }
+}
+
+/// 用户操作键鼠时的紧急暂停设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputInterruptSettings {
+    pub enabled: bool,
+    pub idle_resume_secs: Option<f64>, // 用户空闲多少秒后自动恢复，None 表示保持暂停
+}
+
+/// 键盘矩阵限制，同时按下过多或特定组合的键时会串键（丢键）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardMatrixSettings {
+    pub max_simultaneous: Option<usize>, // 最多同时按下的键数
+    pub ghost_combos: Vec<Vec<String>>,  // 已知会串键的组合，如 ["a", "s", "w"]
+    pub revoice: bool,                   // 播放前去掉冲突的按键，否则只提示
+}
+
+/// 等待事件时间的调度方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulerMode {
+    #[default]
+    Precise, // 临近事件时忙等，时间最准
+    Economy,  // 离事件较远时睡得更久、忙等窗口更短，占用更低
+    Adaptive, // 先按 Precise，CPU 占用持续偏高时切换到 Economy
+}
+
+/// 鼠标点击的随机化：落点按正态分布偏离目标中心，标准差按目标大小缩放，
+/// 移动到位后的停顿和按住时长按对数正态分布，sigma 为其形状参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseHumanizeSettings {
+    pub target_size_px: f64, // 点击目标（按钮）的直径，落点不会超出目标
+    pub offset_spread: f64,  // 落点偏移的标准差占目标直径的比例
+    pub settle_median_ms: f64,
+    pub settle_sigma: f64,
+    pub dwell_median_ms: f64, // 按住时长的中位数，0 表示按下立即松开
+    pub dwell_sigma: f64,
+}
+
+impl Default for MouseHumanizeSettings {
+    fn default() -> Self {
+        Self {
+            target_size_px: 12.0,
+            offset_spread: 0.25,
+            settle_median_ms: 20.0,
+            settle_sigma: 0.3,
+            dwell_median_ms: 50.0,
+            dwell_sigma: 0.25,
+        }
+    }
+}
+
+impl MouseHumanizeSettings {
+    pub fn timing(&self) -> uni_input::ClickTiming {
+        let size = self.target_size_px.max(0.0);
+        uni_input::ClickTiming {
+            offset_sigma_px: size * self.offset_spread.max(0.0),
+            offset_limit_px: size / 2.0,
+            settle_median_ms: self.settle_median_ms,
+            settle_sigma: self.settle_sigma,
+            dwell_median_ms: self.dwell_median_ms,
+            dwell_sigma: self.dwell_sigma,
+        }
+    }
+}
+
+/// 游戏配置档案，不同游戏对输入和焦点的要求不同
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameProfile {
+    pub name: String,
+    pub activation: ActivationSettings,
+    pub focus_guard: FocusGuardSettings,
+    pub input_interrupt: InputInterruptSettings,
+    pub keyboard_matrix: KeyboardMatrixSettings,
+    pub output_latency_ms: f64, // 游戏处理输入的延迟（毫秒），播放时所有事件提前这么多发送
+    pub frame_sync_ms: Option<f64>, // 按固定帧读取输入的游戏每帧时长（毫秒），设置后事件对齐到帧边界
+    pub high_resolution_timer: bool, // 播放期间把系统计时器精度提高到 1ms（Windows）
+    pub realtime_priority: bool,    // 提高播放线程的优先级，减少后台负载造成的抖动
+    pub scheduler: SchedulerMode,   // 等待事件的方式，长时间播放时可降低 CPU 占用
+    pub stop_timeout_ms: u64,       // 停止时等待播放线程结束的时限，超时后放弃该线程
+    pub keymap: Option<String>,     // 播放原始 MIDI 事件时默认使用的映射表
+    pub human_mouse_traces: bool,   // 鼠标点击前沿录制的真人轨迹移动，而不是合成的贝塞尔曲线
+    pub mouse_humanize: MouseHumanizeSettings,
+}
+
+impl GameProfile {
+    // 检查保存或导入的档案
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Profile name cannot be empty".to_string());
+        }
+        if self.stop_timeout_ms < MIN_STOP_TIMEOUT_MS {
+            return Err(format!(
+                "Stop timeout must be at least {}ms",
+                MIN_STOP_TIMEOUT_MS
+            ));
+        }
+        if !(0.0..=MAX_OUTPUT_LATENCY_MS).contains(&self.output_latency_ms) {
+            return Err(format!(
+                "Invalid output latency: {}ms",
+                self.output_latency_ms
+            ));
+        }
+        if let Some(secs) = self
+            .input_interrupt
+            .idle_resume_secs
+            .filter(|s| !(0.0..=MAX_IDLE_RESUME_SECS).contains(s))
+        {
+            return Err(format!("Invalid idle resume delay: {}s", secs));
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameProfile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROFILE_NAME.to_string(),
+            activation: ActivationSettings::default(),
+            focus_guard: FocusGuardSettings::default(),
+            input_interrupt: InputInterruptSettings::default(),
+            keyboard_matrix: KeyboardMatrixSettings::default(),
+            output_latency_ms: 0.0,
+            frame_sync_ms: None,
+            high_resolution_timer: true,
+            realtime_priority: false,
+            scheduler: SchedulerMode::default(),
+            stop_timeout_ms: 2000,
+            keymap: None,
+            human_mouse_traces: false,
+            mouse_humanize: MouseHumanizeSettings::default(),
+        }
+    }
+}
+
+/// 持久化到 profiles.json 的结构，也用于配置导入导出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStore {
+    pub active: String,
+    pub profiles: Vec<GameProfile>,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE_NAME.to_string(),
+            profiles: vec![GameProfile::default()],
+        }
+    }
+}
+
+/// 档案管理，保存在配置目录的 profiles.json 中
+pub struct ProfileManager {
+    store: RwLock<ProfileStore>,
+    path: PathBuf,
+}
+
+impl ProfileManager {
+    /// 从配置目录加载档案，文件不存在时使用默认档案
+    pub fn load(config_dir: PathBuf) -> Self {
+        let path = config_dir.join(PROFILE_FILE_NAME);
+
+        let store = match json_store::load::<ProfileStore>(&path) {
+            Ok(Some(mut store)) => {
+                if store.profiles.is_empty() {
+                    store.profiles.push(GameProfile::default());
+                }
+                store
+            }
+            Ok(None) => ProfileStore::default(),
+            Err(e) => {
+                log::error!("Failed to load profiles: {}", e);
+                ProfileStore::default()
+            }
+        };
+
+        Self {
+            store: RwLock::new(store),
+            path,
+        }
+    }
+
+    fn persist(&self, store: &ProfileStore) -> Result<(), String> {
+        json_store::save(&self.path, store)
+    }
+
+    pub fn list_profiles(&self) -> Vec<GameProfile> {
+        self.store.read().unwrap().profiles.clone()
+    }
+
+    /// 新增或覆盖同名档案
+    pub fn save_profile(&self, profile: GameProfile) -> Result<(), String> {
+        profile.validate()?;
+
+        let mut store = self.store.write().unwrap();
+        match store.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => store.profiles.push(profile),
+        }
+        self.persist(&store)
+    }
+
+    pub fn delete_profile(&self, name: &str) -> Result<(), String> {
+        let mut store = self.store.write().unwrap();
+        if store.profiles.len() <= 1 {
+            return Err("Cannot delete the last profile".to_string());
+        }
+
+        let before = store.profiles.len();
+        store.profiles.retain(|p| p.name != name);
+        if store.profiles.len() == before {
+            return Err(format!("Profile not found: {}", name));
+        }
+
+        // 删除的是当前档案时回退到第一个
+        if store.active == name {
+            store.active = store.profiles[0].name.clone();
+        }
+        self.persist(&store)
+    }
+
+    pub fn set_active_profile(&self, name: &str) -> Result<(), String> {
+        let mut store = self.store.write().unwrap();
+        if !store.profiles.iter().any(|p| p.name == name) {
+            return Err(format!("Profile not found: {}", name));
+        }
+        store.active = name.to_string();
+        self.persist(&store)
+    }
+
+    pub fn snapshot(&self) -> ProfileStore {
+        self.store.read().unwrap().clone()
+    }
+
+    /// 导入档案：replace 时整体替换，否则同名覆盖、其余追加并保留当前档案
+    pub fn import(&self, imported: ProfileStore, replace: bool) -> Result<usize, String> {
+        for profile in &imported.profiles {
+            profile.validate()?;
+        }
+        let count = imported.profiles.len();
+
+        let mut store = self.store.write().unwrap();
+        if replace {
+            if imported.profiles.is_empty() {
+                return Err("No profiles to import".to_string());
+            }
+            *store = imported;
+            if !store.profiles.iter().any(|p| p.name == store.active) {
+                store.active = store.profiles[0].name.clone();
+            }
+        } else {
+            for profile in imported.profiles {
+                match store.profiles.iter_mut().find(|p| p.name == profile.name) {
+                    Some(existing) => *existing = profile,
+                    None => store.profiles.push(profile),
+                }
+            }
+        }
+        self.persist(&store)?;
+        Ok(count)
+    }
+
+    /// 获取当前档案，找不到时返回默认档案
+    pub fn active_profile(&self) -> GameProfile {
+        let store = self.store.read().unwrap();
+        store
+            .profiles
+            .iter()
+            .find(|p| p.name == store.active)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,213 @@
+use crate::keypress_simulator::KeyEvent;
+use crate::midi_analyzer::{self, AnalyzeOptions, MidiAnalysis};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 可分享的 .autoscore 乐谱文件：按键序列或 MIDI 数据、目标档案、转换选项和作者信息，
+// 附带校验和。分享到社区后对方打开即可得到相同的设置。
+//
+// 文件结构：{ "format", "version", "checksum", "score": {...} }，校验和只覆盖 score 部分。
+
+const FORMAT_NAME: &str = "autoscore";
+const FORMAT_VERSION: u32 = 1;
+const CHECKSUM_PREFIX: &str = "fnv1a64:";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreMetadata {
+    pub title: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub created_at: Option<u64>, // Unix 时间戳（秒），保存时自动填写
+}
+
+/// 乐谱内容：已转换好的按键序列，或原始 MIDI 数据（十六进制）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScoreContent {
+    Events { events: Vec<KeyEvent> },
+    Midi { data: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub metadata: ScoreMetadata,
+    pub profile: Option<String>, // 目标档案名
+    pub keymap: Option<String>,  // 目标键位表名
+    pub options: Option<AnalyzeOptions>,
+    pub content: ScoreContent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScoreFile {
+    format: String,
+    version: u32,
+    checksum: String,
+    score: Value,
+}
+
+/// 打开后的乐谱，MIDI 内容按保存的转换选项分析后放在 analysis 中
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedScore {
+    pub metadata: ScoreMetadata,
+    pub profile: Option<String>,
+    pub keymap: Option<String>,
+    pub options: Option<AnalyzeOptions>,
+    pub events: Option<Vec<KeyEvent>>,
+    pub analysis: Option<MidiAnalysis>,
+}
+
+/// 保存的内容来源：直接给出按键序列，或嵌入一个乐曲文件
+pub enum ScoreSource {
+    Events(Vec<KeyEvent>),
+    MidiFile(String),
+}
+
+pub fn save(
+    path: &Path,
+    mut metadata: ScoreMetadata,
+    profile: Option<String>,
+    keymap: Option<String>,
+    options: Option<AnalyzeOptions>,
+    source: ScoreSource,
+    state: &AppState,
+) -> Result<(), String> {
+    validate_references(profile.as_deref(), keymap.as_deref(), state)?;
+    let content = match source {
+        ScoreSource::Events(events) => {
+            if events.is_empty() {
+                return Err("Score has no events".to_string());
+            }
+            ScoreContent::Events { events }
+        }
+        ScoreSource::MidiFile(file_path) => {
+            let import_track = options.as_ref().and_then(|o| o.import_track);
+            let bytes = midi_analyzer::load_midi_bytes(Path::new(&file_path), import_track)?;
+            ScoreContent::Midi {
+                data: to_hex(&bytes),
+            }
+        }
+    };
+    if metadata.created_at.is_none() {
+        metadata.created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+    }
+
+    let score = serde_json::to_value(Score {
+        metadata,
+        profile,
+        keymap,
+        options,
+        content,
+    })
+    .map_err(|e| e.to_string())?;
+    let file = ScoreFile {
+        format: FORMAT_NAME.to_string(),
+        version: FORMAT_VERSION,
+        checksum: checksum(&score)?,
+        score,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn open(path: &Path, state: &AppState) -> Result<OpenedScore, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file: ScoreFile =
+        serde_json::from_str(&content).map_err(|e| format!("Not a valid score file: {}", e))?;
+    if file.format != FORMAT_NAME {
+        return Err(format!("Not a score file (format: {})", file.format));
+    }
+    if file.version > FORMAT_VERSION {
+        return Err(format!(
+            "Score file version {} is newer than supported version {}",
+            file.version, FORMAT_VERSION
+        ));
+    }
+    if checksum(&file.score)? != file.checksum {
+        return Err(
+            "Score file checksum mismatch, the file is corrupted or was modified".to_string(),
+        );
+    }
+    let score: Score =
+        serde_json::from_value(file.score).map_err(|e| format!("Invalid score content: {}", e))?;
+    validate_references(score.profile.as_deref(), score.keymap.as_deref(), state)?;
+
+    let (events, analysis) = match score.content {
+        ScoreContent::Events { events } => (Some(events), None),
+        ScoreContent::Midi { data } => {
+            let bytes = from_hex(&data)?;
+            let options = score.options.clone().unwrap_or_default();
+            let analysis =
+                midi_analyzer::analyze_midi_bytes(&bytes, &score.metadata.title, &options)?;
+            (None, Some(analysis))
+        }
+    };
+    Ok(OpenedScore {
+        metadata: score.metadata,
+        profile: score.profile,
+        keymap: score.keymap,
+        options: score.options,
+        events,
+        analysis,
+    })
+}
+
+// 乐谱引用的档案和键位表必须在本机存在
+fn validate_references(
+    profile: Option<&str>,
+    keymap: Option<&str>,
+    state: &AppState,
+) -> Result<(), String> {
+    if let Some(name) = profile {
+        if !state
+            .profiles
+            .list_profiles()
+            .iter()
+            .any(|p| p.name == name)
+        {
+            return Err(format!("Profile not found: {}", name));
+        }
+    }
+    if let Some(name) = keymap {
+        if !state.keymaps.contains(name) {
+            return Err(format!("Keymap not found: {}", name));
+        }
+    }
+    Ok(())
+}
+
+// 对 score 部分的 JSON 文本计算 FNV-1a 64 位校验和
+fn checksum(score: &Value) -> Result<String, String> {
+    let bytes = serde_json::to_vec(score).map_err(|e| e.to_string())?;
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(format!("{}{:016x}", CHECKSUM_PREFIX, hash))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| "Invalid MIDI data in score".to_string())
+        })
+        .collect()
+}
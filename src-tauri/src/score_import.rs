@@ -0,0 +1,146 @@
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+// 导入时使用的时间精度
+pub const TICKS_PER_BEAT: u16 = 480;
+const DEFAULT_BPM: f64 = 120.0;
+
+/// 从其他乐谱格式导入的音符，以 tick 计时
+#[derive(Debug, Clone)]
+pub struct ImportedNote {
+    pub tick: u32,
+    pub length: u32,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportedTrack {
+    pub name: String,
+    pub channel: u8,
+    pub notes: Vec<ImportedNote>,
+}
+
+/// 导入的乐曲，转换成标准 MIDI 后与 .mid 文件走同样的分析流程
+#[derive(Debug, Clone, Default)]
+pub struct ImportedSong {
+    pub title: Option<String>,
+    pub composer: Option<String>,
+    pub tempo_changes: Vec<(u32, f64)>, // (tick, 每分钟四分音符数)
+    pub time_signatures: Vec<(u32, u8, u8)>, // (tick, 分子, 分母)
+    pub tracks: Vec<ImportedTrack>,
+}
+
+// 同一 tick 上先松开再按下，保证同音重复时能重新触发
+fn to_track(mut events: Vec<(u32, u8, TrackEventKind)>) -> Vec<TrackEvent> {
+    events.sort_by_key(|(tick, order, _)| (*tick, *order));
+    let mut last_tick = 0;
+    let mut track: Vec<TrackEvent> = events
+        .into_iter()
+        .map(|(tick, _, kind)| {
+            let delta = tick - last_tick;
+            last_tick = tick;
+            TrackEvent {
+                delta: u28::new(delta),
+                kind,
+            }
+        })
+        .collect();
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+impl ImportedSong {
+    /// 编码为标准 MIDI 文件：第 0 轨保存曲名、速度和拍号，之后每个导入音轨一轨
+    pub fn to_smf_bytes(&self) -> Result<Vec<u8>, String> {
+        if self.tracks.iter().all(|t| t.notes.is_empty()) {
+            return Err("No notes found in imported file".to_string());
+        }
+
+        let composer_text = self.composer.as_ref().map(|c| format!("Composer: {}", c));
+        let mut conductor = Vec::new();
+        if let Some(title) = &self.title {
+            conductor.push((
+                0,
+                0,
+                TrackEventKind::Meta(MetaMessage::TrackName(title.as_bytes())),
+            ));
+        }
+        if let Some(text) = &composer_text {
+            conductor.push((
+                0,
+                0,
+                TrackEventKind::Meta(MetaMessage::Text(text.as_bytes())),
+            ));
+        }
+        let tempo_changes = if self.tempo_changes.is_empty() {
+            vec![(0, DEFAULT_BPM)]
+        } else {
+            self.tempo_changes.clone()
+        };
+        for (tick, bpm) in tempo_changes {
+            let micros = (60_000_000.0 / bpm.max(1.0)).round() as u32;
+            conductor.push((
+                tick,
+                0,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros))),
+            ));
+        }
+        for &(tick, numerator, denominator) in &self.time_signatures {
+            let pow = denominator.max(1).trailing_zeros() as u8;
+            conductor.push((
+                tick,
+                0,
+                TrackEventKind::Meta(MetaMessage::TimeSignature(numerator, pow, 24, 8)),
+            ));
+        }
+
+        let mut tracks = vec![to_track(conductor)];
+        for track in self.tracks.iter().filter(|t| !t.notes.is_empty()) {
+            let channel = u4::new(track.channel.min(15));
+            let mut events = vec![(
+                0,
+                0,
+                TrackEventKind::Meta(MetaMessage::TrackName(track.name.as_bytes())),
+            )];
+            for note in &track.notes {
+                let key = u7::new(note.key.min(127));
+                events.push((
+                    note.tick,
+                    1,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn {
+                            key,
+                            vel: u7::new(note.velocity.clamp(1, 127)),
+                        },
+                    },
+                ));
+                events.push((
+                    note.tick + note.length.max(1),
+                    0,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOff {
+                            key,
+                            vel: u7::new(0),
+                        },
+                    },
+                ));
+            }
+            tracks.push(to_track(events));
+        }
+
+        let smf = Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(TICKS_PER_BEAT))),
+            tracks,
+        };
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes)
+            .map_err(|e| format!("Failed to encode MIDI: {}", e))?;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::input_hook;
+use crate::input_service;
+use crate::state::AppState;
+use enigo::{Direction, Key};
+use rdev::EventType;
+use serde::Serialize;
+use std::thread;
+use std::time::{Duration, Instant};
+use uni_input::NativeKey;
+
+// 测试用按键：F20 在各平台都存在且几乎不会被游戏或系统占用
+const TEST_KEY: Key = Key::F20;
+const TEST_KEY_NAME: &str = "f20";
+const TEST_EVENT_COUNT: usize = 20;
+const TEST_INTERVAL: Duration = Duration::from_millis(50);
+const HOOK_WARMUP: Duration = Duration::from_millis(300);
+const CAPTURE_TAIL: Duration = Duration::from_millis(300);
+// 平均时间误差达到该值时时间分扣满
+const MAX_TIMING_ERROR_MS: f64 = 50.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub hook_active: bool,
+    pub scheduled: usize,
+    pub received: usize,
+    pub missing: usize,
+    pub avg_latency_ms: f64, // 发送到被系统钩子收到的延迟
+    pub max_latency_ms: f64,
+    pub avg_timing_error_ms: f64, // 收到时间与计划时间的偏差
+    pub max_timing_error_ms: f64,
+    pub fidelity_score: f64, // 0-100，收到率占一半，时间精度占一半
+}
+
+/// 播放一段内部测试序列，用系统钩子测量实际送达情况
+pub fn run(state: &AppState) -> Result<SelfTestReport, String> {
+    if state.is_any_playing() {
+        return Err("Cannot run self test while playback is in progress".to_string());
+    }
+
+    input_hook::ensure_started();
+    thread::sleep(HOOK_WARMUP);
+    let hook_active = input_hook::is_running();
+
+    let input = input_service::handle().map_err(|e| e.to_string())?;
+
+    input_hook::start_capture();
+    let start = Instant::now();
+    let mut sent_at = Vec::with_capacity(TEST_EVENT_COUNT);
+
+    for i in 0..TEST_EVENT_COUNT {
+        let target = TEST_INTERVAL * i as u32;
+        let elapsed = start.elapsed();
+        if target > elapsed {
+            thread::sleep(target - elapsed);
+        }
+
+        input_hook::begin_injection();
+        sent_at.push(Instant::now());
+        let result = input
+            .native_key(NativeKey::Key(TEST_KEY), TEST_KEY_NAME, Direction::Press)
+            .and_then(|_| {
+                input.native_key(NativeKey::Key(TEST_KEY), TEST_KEY_NAME, Direction::Release)
+            });
+        input_hook::end_injection();
+
+        if let Err(e) = result {
+            input_hook::stop_capture();
+            return Err(format!("Failed to send test key: {}", e));
+        }
+    }
+
+    thread::sleep(CAPTURE_TAIL);
+    let captured = input_hook::stop_capture();
+
+    // rdev 不认识 F20，会报告为 Unknown 键码
+    let presses: Vec<Instant> = captured
+        .into_iter()
+        .filter(|(_, e)| matches!(e, EventType::KeyPress(rdev::Key::Unknown(_))))
+        .map(|(t, _)| t)
+        .collect();
+
+    let received = presses.len().min(TEST_EVENT_COUNT);
+    let mut latency_sum = 0.0;
+    let mut max_latency = 0.0_f64;
+    let mut error_sum = 0.0;
+    let mut max_error = 0.0_f64;
+
+    for (i, received_at) in presses.iter().take(received).enumerate() {
+        let latency = received_at
+            .saturating_duration_since(sent_at[i])
+            .as_secs_f64()
+            * 1000.0;
+        let scheduled = start + TEST_INTERVAL * i as u32;
+        let error = received_at
+            .saturating_duration_since(scheduled)
+            .as_secs_f64()
+            * 1000.0;
+
+        latency_sum += latency;
+        max_latency = max_latency.max(latency);
+        error_sum += error;
+        max_error = max_error.max(error);
+    }
+
+    let avg = |sum: f64| {
+        if received > 0 {
+            sum / received as f64
+        } else {
+            0.0
+        }
+    };
+    let avg_timing_error_ms = avg(error_sum);
+    let delivery_ratio = received as f64 / TEST_EVENT_COUNT as f64;
+    let timing_ratio = if received > 0 {
+        1.0 - (avg_timing_error_ms / MAX_TIMING_ERROR_MS).min(1.0)
+    } else {
+        0.0
+    };
+
+    Ok(SelfTestReport {
+        hook_active,
+        scheduled: TEST_EVENT_COUNT,
+        received,
+        missing: TEST_EVENT_COUNT - received,
+        avg_latency_ms: avg(latency_sum),
+        max_latency_ms: max_latency,
+        avg_timing_error_ms,
+        max_timing_error_ms: max_error,
+        fidelity_score: (delivery_ratio * 50.0 + timing_ratio * 50.0).round(),
+    })
+}
@@ -0,0 +1,103 @@
+use crate::cpu_usage::CpuMonitor;
+use crate::media_controls;
+use crate::state::{AppState, PlaybackStatus};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+// 播放期间定时发送 "session://stats" 事件（当前曲目、进度、下一首、每秒音符数、CPU 占用），
+// 供直播叠加层等外部页面显示"正在演奏"，同时同步到系统媒体控制。
+
+const EMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+static EMITTER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// 正在播放的曲目，队列播放时带有队列位置和下一首
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NowPlaying {
+    pub title: Option<String>,
+    pub file_path: Option<String>,
+    pub queue_index: Option<usize>,
+    pub queue_length: Option<usize>,
+    pub next_title: Option<String>,
+}
+
+impl NowPlaying {
+    /// 单曲播放，标题取文件名
+    pub fn from_file(file_path: Option<&str>) -> Self {
+        Self {
+            title: file_path.and_then(|p| {
+                Path::new(p)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+            }),
+            file_path: file_path.map(str::to_string),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStats {
+    pub now_playing: NowPlaying,
+    pub keyboard: PlaybackStatus,
+    pub mouse: PlaybackStatus,
+    pub notes_per_second: f64,    // 最近一个统计周期内实际发送的事件数
+    pub cpu_percent: Option<f64>, // 本进程最近一个统计周期的 CPU 占用，100 为占满一个核
+}
+
+/// 汇总当前的播放状态，notes_per_second 为 0
+pub fn snapshot(state: &AppState) -> SessionStats {
+    let status = state.playback_status();
+    SessionStats {
+        now_playing: state.now_playing.lock().unwrap().clone(),
+        keyboard: status.keyboard,
+        mouse: status.mouse,
+        notes_per_second: 0.0,
+        cpu_percent: None,
+    }
+}
+
+/// 开始定时发送统计事件，播放和队列都结束后自动停止（重复调用无副作用）
+pub fn start(app: AppHandle) {
+    if EMITTER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let mut last_remaining = None;
+        let mut last_tick = Instant::now();
+        let mut cpu = CpuMonitor::default();
+        cpu.sample();
+
+        while state.is_any_playing() || state.queue.is_running() {
+            thread::sleep(EMIT_INTERVAL);
+
+            let mut stats = snapshot(&state);
+            let remaining = stats.keyboard.remaining_events + stats.mouse.remaining_events;
+            // 跳转或换曲时剩余数会变大，这一周期不计速度
+            if let Some(last) = last_remaining {
+                let played: usize = last - remaining.min(last);
+                stats.notes_per_second = played as f64 / last_tick.elapsed().as_secs_f64();
+            }
+            last_remaining = Some(remaining);
+            last_tick = Instant::now();
+
+            stats.cpu_percent = cpu.sample();
+            if cpu.is_sustained_high() {
+                state.keyboard.note_high_cpu();
+                state.mouse.note_high_cpu();
+            }
+
+            media_controls::update(&stats);
+            let _ = app.emit("session://stats", stats);
+        }
+
+        media_controls::clear();
+        EMITTER_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
@@ -0,0 +1,160 @@
+// 播放期间阻止系统休眠和关闭显示器，长时间的后台演奏不会因为空闲休眠而中断。
+// 持有守卫的线程退出或守卫被丢弃后恢复。失败时只记录日志。
+
+/// 持有期间系统不会因空闲而休眠或关闭显示器。Windows 上按线程生效，
+/// 应在播放线程内获取并在同一线程丢弃
+pub struct SleepInhibitGuard {
+    #[cfg(windows)]
+    active: bool,
+    #[cfg(target_os = "macos")]
+    assertion: Option<u32>,
+    #[cfg(target_os = "linux")]
+    child: Option<std::process::Child>,
+}
+
+impl SleepInhibitGuard {
+    #[cfg(windows)]
+    pub fn acquire() -> Self {
+        use windows::Win32::System::Power::{
+            SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+        };
+        let previous = unsafe {
+            SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED)
+        };
+        let active = previous.0 != 0;
+        if !active {
+            log::warn!("Failed to prevent system sleep");
+        }
+        Self { active }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn acquire() -> Self {
+        let assertion = macos::create_assertion();
+        if assertion.is_none() {
+            log::warn!("Failed to prevent system sleep");
+        }
+        Self { assertion }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn acquire() -> Self {
+        use std::process::{Command, Stdio};
+        // 由 systemd-inhibit 持有锁，运行 cat 等待标准输入，关闭管道后两者都退出并释放锁
+        let child = Command::new("systemd-inhibit")
+            .args([
+                "--what=idle:sleep",
+                "--who=OpenGamesAutoPlay",
+                "--why=Playback in progress",
+                "--mode=block",
+                "cat",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| log::warn!("Failed to prevent system sleep: {}", e))
+            .ok();
+        Self { child }
+    }
+
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    pub fn acquire() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for SleepInhibitGuard {
+    #[cfg(windows)]
+    fn drop(&mut self) {
+        use windows::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS};
+        if self.active {
+            unsafe {
+                SetThreadExecutionState(ES_CONTINUOUS);
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn drop(&mut self) {
+        if let Some(id) = self.assertion {
+            macos::release_assertion(id);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    fn drop(&mut self) {}
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void};
+
+    const UTF8_ENCODING: u32 = 0x0800_0100;
+    const ASSERTION_LEVEL_ON: u32 = 255;
+    // 阻止显示器休眠，同时也阻止系统空闲休眠
+    const ASSERTION_TYPE: &[u8] = b"PreventUserIdleDisplaySleep\0";
+    const ASSERTION_NAME: &[u8] = b"OpenGamesAutoPlay playback\0";
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            allocator: *const c_void,
+            text: *const c_char,
+            encoding: u32,
+        ) -> *const c_void;
+        fn CFRelease(object: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: *const c_void,
+            level: u32,
+            name: *const c_void,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    pub fn create_assertion() -> Option<u32> {
+        unsafe {
+            let kind = CFStringCreateWithCString(
+                std::ptr::null(),
+                ASSERTION_TYPE.as_ptr().cast(),
+                UTF8_ENCODING,
+            );
+            let name = CFStringCreateWithCString(
+                std::ptr::null(),
+                ASSERTION_NAME.as_ptr().cast(),
+                UTF8_ENCODING,
+            );
+            let mut id = 0;
+            let result = if kind.is_null() || name.is_null() {
+                -1
+            } else {
+                IOPMAssertionCreateWithName(kind, ASSERTION_LEVEL_ON, name, &mut id)
+            };
+            for object in [kind, name] {
+                if !object.is_null() {
+                    CFRelease(object);
+                }
+            }
+            (result == 0).then_some(id)
+        }
+    }
+
+    pub fn release_assertion(id: u32) {
+        unsafe {
+            IOPMAssertionRelease(id);
+        }
+    }
+}
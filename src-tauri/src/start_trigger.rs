@@ -0,0 +1,144 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uni_window::WindowInfo;
+
+// 条件开始：等游戏画面出现指定的像素（如演奏模式界面的按钮）时才开始播放。
+// 检查在播放线程中进行，满足后接着走定时开始和倒计时，见 StartWait::with_gate。
+
+const DEFAULT_POLL_MS: u64 = 100;
+const MIN_POLL_MS: u64 = 20;
+const MAX_POLL_MS: u64 = 1000;
+
+fn default_tolerance() -> u8 {
+    16
+}
+
+/// 像素探针：坐标处颜色的每个分量与 color 相差不超过 tolerance 时视为匹配
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PixelProbe {
+    pub x: i32,
+    pub y: i32,
+    pub color: String, // "#rrggbb"
+    #[serde(default = "default_tolerance")]
+    pub tolerance: u8,
+}
+
+/// 开始条件：所有探针同时匹配时开始播放。
+/// relative_to_window 为 true 时坐标相对锁定窗口的左上角（开始等待时的位置）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartCondition {
+    pub probes: Vec<PixelProbe>,
+    #[serde(default)]
+    pub relative_to_window: bool,
+    #[serde(default)]
+    pub poll_ms: Option<u64>,
+    #[serde(default)]
+    pub timeout_secs: Option<f64>,
+}
+
+/// 某个探针当前的读数
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeReading {
+    pub x: i32, // 屏幕坐标
+    pub y: i32,
+    pub color: String,
+    pub matched: bool,
+}
+
+/// 解析好的开始条件
+pub struct Trigger {
+    points: Vec<(i32, i32)>,
+    colors: Vec<([u8; 3], u8)>,
+    pub poll_interval: Duration,
+    pub timeout: Option<Duration>,
+}
+
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn format_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+impl Trigger {
+    pub fn compile(
+        condition: StartCondition,
+        window: Option<&WindowInfo>,
+    ) -> Result<Self, AppError> {
+        if condition.probes.is_empty() {
+            return Err(AppError::invalid("Start condition has no pixel probes"));
+        }
+        let (left, top) = match (condition.relative_to_window, window) {
+            (false, _) => (0, 0),
+            (true, Some(window)) => (window.x, window.y),
+            (true, None) => {
+                return Err(AppError::invalid(
+                    "Start condition is relative to the window but no window is locked",
+                ))
+            }
+        };
+        let timeout = match condition.timeout_secs {
+            Some(secs) if !secs.is_finite() || secs <= 0.0 => {
+                return Err(AppError::invalid(format!(
+                    "Invalid start condition timeout: {}",
+                    secs
+                )))
+            }
+            Some(secs) => Some(Duration::from_secs_f64(secs)),
+            None => None,
+        };
+
+        let mut points = Vec::with_capacity(condition.probes.len());
+        let mut colors = Vec::with_capacity(condition.probes.len());
+        for probe in &condition.probes {
+            let color = parse_color(&probe.color).ok_or_else(|| {
+                AppError::invalid("Invalid probe color").with_context(&probe.color)
+            })?;
+            points.push((left + probe.x, top + probe.y));
+            colors.push((color, probe.tolerance));
+        }
+
+        let poll_ms = condition
+            .poll_ms
+            .unwrap_or(DEFAULT_POLL_MS)
+            .clamp(MIN_POLL_MS, MAX_POLL_MS);
+        Ok(Self {
+            points,
+            colors,
+            poll_interval: Duration::from_millis(poll_ms),
+            timeout,
+        })
+    }
+
+    /// 截屏读取所有探针
+    pub fn read(&self) -> Result<Vec<ProbeReading>, AppError> {
+        let pixels = uni_window::pixel_colors(&self.points).map_err(AppError::window)?;
+        Ok(self
+            .points
+            .iter()
+            .zip(&self.colors)
+            .zip(pixels)
+            .map(|((&(x, y), &(expected, tolerance)), actual)| ProbeReading {
+                x,
+                y,
+                color: format_color(actual),
+                matched: expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(e, a)| e.abs_diff(a) <= tolerance),
+            })
+            .collect())
+    }
+
+    /// 所有探针是否同时匹配
+    pub fn is_met(&self) -> Result<bool, AppError> {
+        Ok(self.read()?.iter().all(|r| r.matched))
+    }
+}
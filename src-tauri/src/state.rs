@@ -0,0 +1,716 @@
+use crate::accompaniment::Accompaniment;
+use crate::after_playback::AfterPlayback;
+use crate::emergency_stop::EmergencyStop;
+use crate::error::AppError;
+use crate::input_service;
+use crate::keymap::KeymapManager;
+use crate::keypress_simulator::MAX_EVENT_SECS;
+use crate::library::Library;
+use crate::link_sync::TempoLink;
+use crate::midi_clock::MidiClock;
+use crate::mouse_traces::MouseTraceLibrary;
+use crate::playback_report::PlaybackReport;
+use crate::playlist::QueueRunner;
+use crate::profile::{self, GameProfile, ProfileManager, SchedulerMode};
+use crate::session_stats::NowPlaying;
+use crate::sleep_inhibit::SleepInhibitGuard;
+use crate::thread_priority;
+use crate::timeline_store::TimelineStore;
+use crate::timer_resolution::TimerResolutionGuard;
+use serde::Serialize;
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use uni_window::{ChildWindowInfo, Rect, WindowInfo};
+
+// 等待时的轮询间隔，保证暂停和停止能及时响应
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// 距离目标时间不足该值时改为忙等，避开 sleep 的调度粒度
+const SPIN_WINDOW: Duration = Duration::from_micros(1500);
+// 省电等待：离目标较远时最多睡这么久，忙等窗口也更短
+const ECONOMY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const ECONOMY_SPIN_WINDOW: Duration = Duration::from_micros(300);
+// 停止时最多等待播放线程这么久，档案没有设置时使用
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// 每次播放的编号，放弃卡住的线程后，它恢复时据此发现自己已过期
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // 播放线程所属的播放编号，其他线程为 0
+    static SESSION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// 锁定的目标：窗口、窗口内的子区域和子窗口，三者总是一起读写
+#[derive(Debug, Clone, Default)]
+pub struct LockState {
+    pub window: Option<WindowInfo>,
+    // 锁定窗口内的子区域（相对窗口左上角），如网页游戏的画布
+    pub region: Option<Rect>,
+    // 锁定的子窗口，作为基于窗口消息发送输入时的目标
+    pub child: Option<ChildWindowInfo>,
+}
+
+/// 一路播放的当前状态
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlaybackStatus {
+    pub running: bool,
+    pub paused: bool,
+    pub position_secs: f64,
+    pub remaining_events: usize,
+    pub total_events: usize,
+    pub economy: bool, // 正在使用省电等待
+    // 有停止时未能结束而被放弃的线程仍卡着（多半卡在系统输入调用中），之后的输入可能受影响
+    pub poisoned: bool,
+}
+
+/// 键盘和鼠标两路播放的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackStatusReport {
+    pub keyboard: PlaybackStatus,
+    pub mouse: PlaybackStatus,
+}
+
+/// 等待事件时间的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitOutcome {
+    Ready,
+    Stop,
+    Seek(f64), // 跳转到指定位置（秒），计时起点已重新对齐
+}
+
+// 播放线程更新的进度
+struct Progress {
+    clock: Option<Instant>, // 计时起点，暂停后会顺延
+    paused_at: Option<Instant>,
+    remaining: usize,
+    total: usize,
+    rate: f64, // 计时起点使用的播放速率，播放位置 = 经过的时间 × rate
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            clock: None,
+            paused_at: None,
+            remaining: 0,
+            total: 0,
+            rate: 1.0,
+        }
+    }
+}
+
+// 倒计时回调，参数为剩余整秒数，0 表示开始
+type CountdownTick = Box<dyn Fn(u32) + Send>;
+// 定时开始的回调，参数为实际开始时刻比计划晚了多少
+type ScheduledStart = Box<dyn FnOnce(Duration) + Send>;
+// 开始条件：按间隔反复检查，满足后才进入定时开始和倒计时
+type StartGate = (Duration, Box<dyn FnMut() -> GateCheck + Send>);
+
+/// 开始条件的检查结果
+pub enum GateCheck {
+    Open,    // 条件满足，开始播放
+    Wait,    // 继续等待
+    Abandon, // 放弃这次播放（如等待超时）
+}
+
+/// 播放开始前的等待：开始条件、定时开始和倒计时。随 start 一起传入，只对这一次播放有效
+#[derive(Default)]
+pub struct StartWait {
+    lead_in: Option<(f64, CountdownTick)>,
+    scheduled: Option<(Instant, ScheduledStart)>,
+    gate: Option<StartGate>,
+}
+
+impl StartWait {
+    /// 开始前先倒计时，每过一秒调用一次 on_tick
+    pub fn with_lead_in(mut self, seconds: f64, on_tick: impl Fn(u32) + Send + 'static) -> Self {
+        self.lead_in = Some((seconds, Box::new(on_tick)));
+        self
+    }
+
+    /// 等到指定时刻才开始（倒计时也提前到此时刻结束），开始时调用 on_start
+    pub fn with_scheduled_start(
+        mut self,
+        at: Instant,
+        on_start: impl FnOnce(Duration) + Send + 'static,
+    ) -> Self {
+        self.scheduled = Some((at, Box::new(on_start)));
+        self
+    }
+
+    /// 等到 check 返回 Open 才开始，每隔 interval 检查一次
+    pub fn with_gate(
+        mut self,
+        interval: Duration,
+        check: impl FnMut() -> GateCheck + Send + 'static,
+    ) -> Self {
+        self.gate = Some((interval, Box::new(check)));
+        self
+    }
+}
+
+/// 一路播放（键盘或鼠标）的线程句柄和控制标志
+#[derive(Default)]
+pub struct PlaybackControl {
+    handle: Mutex<Option<thread::JoinHandle<()>>>,
+    should_stop: AtomicBool,
+    is_paused: AtomicBool,
+    output_latency: Mutex<Duration>,
+    progress: Mutex<Progress>,
+    seek_request: Mutex<Option<f64>>,
+    high_resolution_timer: AtomicBool,
+    realtime_priority: AtomicBool,
+    waiting_for_start: AtomicBool,
+    rate: Mutex<Option<f64>>, // 外部时钟（如 Ableton Link）要求的播放速率，None 为原速
+    scheduler: Mutex<SchedulerMode>,
+    economy: AtomicBool, // 本次播放是否使用省电等待
+    session: AtomicU64,  // 当前播放的编号，0 表示没有
+    stop_timeout: Mutex<Option<Duration>>,
+    abandoned: Mutex<Vec<(u64, thread::JoinHandle<()>)>>, // 停止时被放弃的线程和它们的播放编号
+}
+
+impl PlaybackControl {
+    /// 在新线程中执行播放（先完成 wait 中的等待），结束后自动清理句柄
+    pub fn start<F>(self: &Arc<Self>, wait: StartWait, body: F) -> Result<(), AppError>
+    where
+        F: FnOnce(&PlaybackControl) + Send + 'static,
+    {
+        let StartWait {
+            lead_in,
+            scheduled,
+            gate,
+        } = wait;
+        let mut handle = self.handle.lock().unwrap();
+        if handle.is_some() {
+            return Err(AppError::busy());
+        }
+
+        self.should_stop.store(false, Ordering::SeqCst);
+        self.waiting_for_start
+            .store(scheduled.is_some() || gate.is_some(), Ordering::SeqCst);
+        self.is_paused.store(false, Ordering::SeqCst);
+        self.economy.store(
+            *self.scheduler.lock().unwrap() == SchedulerMode::Economy,
+            Ordering::SeqCst,
+        );
+        *self.seek_request.lock().unwrap() = None;
+        // 倒计时期间还没有计时起点，不沿用上一次播放的进度
+        *self.progress.lock().unwrap() = Progress::default();
+
+        let session = NEXT_SESSION.fetch_add(1, Ordering::SeqCst);
+        self.session.store(session, Ordering::SeqCst);
+
+        let control = Arc::clone(self);
+        let high_resolution = self.high_resolution_timer.load(Ordering::SeqCst);
+        let realtime = self.realtime_priority.load(Ordering::SeqCst);
+        *handle = Some(thread::spawn(move || {
+            SESSION.with(|s| s.set(session));
+            let _timer = high_resolution.then(TimerResolutionGuard::acquire);
+            let _awake = SleepInhibitGuard::acquire();
+            if realtime {
+                thread_priority::raise_current_thread();
+            }
+            // 放弃等待时按停止处理，照常经过播放体，结束回调（报告、播放后操作）同样会执行
+            if let Some((interval, check)) = gate {
+                if !control.wait_for_gate(interval, check) && control.is_current() {
+                    control.should_stop.store(true, Ordering::SeqCst);
+                }
+            }
+            // 倒计时在定时开始的时刻结束
+            if let Some((at, _)) = &scheduled {
+                let lead_in = lead_in.as_ref().map_or(0.0, |(seconds, _)| *seconds);
+                let countdown_at = at
+                    .checked_sub(Duration::from_secs_f64(lead_in))
+                    .unwrap_or(*at);
+                control.wait_for_start(countdown_at);
+            }
+            if let Some((seconds, on_tick)) = lead_in {
+                control.count_down(seconds, &on_tick);
+            }
+            if control.is_current() {
+                control.waiting_for_start.store(false, Ordering::SeqCst);
+            }
+            if let Some((at, on_start)) = scheduled {
+                if !control.stopping() {
+                    on_start(at.elapsed());
+                }
+            }
+            body(&control);
+            control.finish(session);
+        }));
+        Ok(())
+    }
+
+    // 播放线程结束时清理句柄；已过期的线程不碰新的播放。
+    // 在句柄的锁内比较编号，start 同样在锁内更新编号，不会清掉新播放的句柄
+    fn finish(&self, session: u64) {
+        let mut handle = self.handle.lock().unwrap();
+        if self.session.load(Ordering::SeqCst) == session {
+            *handle = None;
+        } else if self
+            .abandoned
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(id, _)| *id == session)
+        {
+            log::warn!("Abandoned playback thread finished");
+        }
+    }
+
+    // 当前线程是否为仍在进行的播放的线程
+    fn is_current(&self) -> bool {
+        SESSION.with(Cell::get) == self.session.load(Ordering::SeqCst)
+    }
+
+    // 播放线程应当结束：收到停止，或自己已被放弃
+    fn stopping(&self) -> bool {
+        self.should_stop.load(Ordering::SeqCst) || !self.is_current()
+    }
+
+    /// 停止播放并等待线程结束。超过档案设置的时限仍未结束（如卡在系统输入调用中）时放弃该线程：
+    /// 重置播放状态和输入线程，返回 false。被放弃的线程恢复后在下一次等待时结束，不再影响新的播放
+    pub fn stop(&self) -> bool {
+        self.should_stop.store(true, Ordering::SeqCst);
+
+        let (handle, session) = {
+            let mut handle = self.handle.lock().unwrap();
+            let Some(handle) = handle.take() else {
+                return true;
+            };
+            (handle, self.session.load(Ordering::SeqCst))
+        };
+        let timeout = self
+            .stop_timeout
+            .lock()
+            .unwrap()
+            .unwrap_or(DEFAULT_STOP_TIMEOUT);
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                self.abandon(session, handle, timeout);
+                return false;
+            }
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+        let _ = handle.join();
+        true
+    }
+
+    // 放弃卡住的线程，保留句柄以便得知它何时结束。
+    // 等待期间已经开始了新的播放时只记录，不重置新播放的状态
+    fn abandon(&self, session: u64, handle: thread::JoinHandle<()>, timeout: Duration) {
+        self.abandoned.lock().unwrap().push((session, handle));
+        log::error!(
+            "Playback thread did not stop within {}ms and was abandoned",
+            timeout.as_millis()
+        );
+        if self
+            .session
+            .compare_exchange(session, 0, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        self.is_paused.store(false, Ordering::SeqCst);
+        self.waiting_for_start.store(false, Ordering::SeqCst);
+        *self.seek_request.lock().unwrap() = None;
+        *self.progress.lock().unwrap() = Progress::default();
+        // 卡住的多半是输入线程，下次播放换一个新的
+        input_service::reset();
+    }
+
+    /// 是否有停止时被放弃、仍未结束的播放线程
+    pub fn is_poisoned(&self) -> bool {
+        let mut abandoned = self.abandoned.lock().unwrap();
+        abandoned.retain(|(_, handle)| !handle.is_finished());
+        !abandoned.is_empty()
+    }
+
+    /// 应用档案中的调度设置（输出延迟补偿、计时器精度、线程优先级），对之后开始的播放生效
+    pub fn apply_profile(&self, profile: &GameProfile) {
+        // 旧版本保存的档案没有经过检查，超出范围的延迟按上限处理
+        let latency_ms = profile
+            .output_latency_ms
+            .clamp(0.0, profile::MAX_OUTPUT_LATENCY_MS);
+        let latency = Duration::try_from_secs_f64(latency_ms / 1000.0).unwrap_or_default();
+        *self.output_latency.lock().unwrap() = latency;
+        self.high_resolution_timer
+            .store(profile.high_resolution_timer, Ordering::SeqCst);
+        self.realtime_priority
+            .store(profile.realtime_priority, Ordering::SeqCst);
+        *self.scheduler.lock().unwrap() = profile.scheduler;
+        let stop_timeout = profile.stop_timeout_ms.max(profile::MIN_STOP_TIMEOUT_MS);
+        *self.stop_timeout.lock().unwrap() = Some(Duration::from_millis(stop_timeout));
+    }
+
+    /// CPU 占用持续偏高时调用：自适应调度在本次播放剩下的时间里改用省电等待
+    pub fn note_high_cpu(&self) {
+        if *self.scheduler.lock().unwrap() == SchedulerMode::Adaptive
+            && self.is_playing()
+            && !self.economy.swap(true, Ordering::SeqCst)
+        {
+            log::info!("CPU usage stays high, switching playback to economy waiting");
+        }
+    }
+
+    // 忙等窗口和最长的单次睡眠
+    fn wait_granularity(&self) -> (Duration, Duration) {
+        if self.economy.load(Ordering::SeqCst) {
+            (ECONOMY_SPIN_WINDOW, ECONOMY_POLL_INTERVAL)
+        } else {
+            (SPIN_WINDOW, POLL_INTERVAL)
+        }
+    }
+
+    /// 设置播放速率（1.0 为原速），播放中由播放线程在下次等待时平滑切换，不影响倒计时
+    pub fn set_rate(&self, rate: Option<f64>) -> Result<(), AppError> {
+        if let Some(rate) = rate.filter(|r| !r.is_finite() || *r <= 0.0) {
+            return Err(AppError::invalid(format!(
+                "Invalid playback rate: {}",
+                rate
+            )));
+        }
+        *self.rate.lock().unwrap() = rate;
+        Ok(())
+    }
+
+    fn target_rate(&self) -> f64 {
+        self.rate.lock().unwrap().unwrap_or(1.0)
+    }
+
+    /// 从计时起点到现在对应的播放位置（秒）
+    pub fn song_time(&self, start_time: Instant) -> f64 {
+        start_time.elapsed().as_secs_f64() * self.progress.lock().unwrap().rate
+    }
+
+    /// 是否在等待开始条件或定时开始（包括之后的倒计时）
+    pub fn is_waiting_for_start(&self) -> bool {
+        self.waiting_for_start.load(Ordering::SeqCst)
+    }
+
+    // 等待定时开始只响应停止，暂停不会推迟开始时刻
+    fn wait_for_start(&self, at: Instant) {
+        while !self.stopping() {
+            let now = Instant::now();
+            if now >= at {
+                return;
+            }
+            let remaining = at - now;
+            if remaining <= SPIN_WINDOW {
+                while Instant::now() < at {
+                    std::hint::spin_loop();
+                }
+                return;
+            }
+            thread::sleep((remaining - SPIN_WINDOW).min(POLL_INTERVAL));
+        }
+    }
+
+    // 等待开始条件，放弃时返回 false；停止时与定时开始一样交给播放线程结束
+    fn wait_for_gate(
+        &self,
+        interval: Duration,
+        mut check: Box<dyn FnMut() -> GateCheck + Send>,
+    ) -> bool {
+        while !self.stopping() {
+            match check() {
+                GateCheck::Open => return true,
+                GateCheck::Abandon => return false,
+                GateCheck::Wait => thread::sleep(interval),
+            }
+        }
+        true
+    }
+
+    // 倒计时期间同样响应暂停和停止；跳转时直接结束倒计时，交给播放处理
+    fn count_down(&self, seconds: f64, on_tick: &dyn Fn(u32)) {
+        let mut start = Instant::now();
+        let whole = seconds.ceil() as u32;
+        for remaining in (0..=whole).rev() {
+            let at = (seconds - remaining as f64).max(0.0);
+            match self.wait_until(Duration::from_secs_f64(at), &mut start) {
+                WaitOutcome::Ready => on_tick(remaining),
+                WaitOutcome::Seek(position) => {
+                    *self.seek_request.lock().unwrap() = Some(position);
+                    return;
+                }
+                WaitOutcome::Stop => return,
+            }
+        }
+    }
+
+    /// 播放计时的起点，按输出延迟往前挪，事件因此提前发送；同时重置进度
+    pub fn clock_start(&self, total_events: usize) -> Instant {
+        let now = Instant::now();
+        let start = now
+            .checked_sub(*self.output_latency.lock().unwrap())
+            .unwrap_or(now);
+        *self.progress.lock().unwrap() = Progress {
+            clock: Some(start),
+            paused_at: None,
+            remaining: total_events,
+            total: total_events,
+            rate: self.target_rate(),
+        };
+        start
+    }
+
+    /// 重新对齐计时起点，使当前时刻对应播放位置 position（秒）
+    pub fn reanchor(&self, start_time: &mut Instant, position: f64) {
+        let mut progress = self.progress.lock().unwrap();
+        // 速率极低时换算结果可能超出 Duration 的范围，此时计时起点取现在
+        let offset = Duration::try_from_secs_f64(position / progress.rate)
+            .unwrap_or(Duration::MAX)
+            .saturating_add(*self.output_latency.lock().unwrap());
+        let now = Instant::now();
+        *start_time = now.checked_sub(offset).unwrap_or(now);
+        progress.clock = Some(*start_time);
+        progress.paused_at = None;
+    }
+
+    /// 记录已发送的事件数
+    pub fn advance(&self, count: usize) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.remaining = progress.remaining.saturating_sub(count);
+    }
+
+    pub fn set_remaining(&self, remaining: usize) {
+        self.progress.lock().unwrap().remaining = remaining;
+    }
+
+    /// 请求跳转到指定位置，由播放线程在下次等待时处理；超出事件时间上限的位置视为无效
+    pub fn seek(&self, seconds: f64) -> Result<(), AppError> {
+        if !(0.0..=MAX_EVENT_SECS).contains(&seconds) {
+            return Err(AppError::invalid(format!(
+                "Invalid seek position: {}",
+                seconds
+            )));
+        }
+        if !self.is_playing() {
+            return Err(AppError::not_playing());
+        }
+        *self.seek_request.lock().unwrap() = Some(seconds);
+        Ok(())
+    }
+
+    /// 当前播放位置（秒），还没开始计时（倒计时、等待定时开始）或没有播放时为 None
+    pub fn position(&self) -> Option<f64> {
+        if !self.is_playing() {
+            return None;
+        }
+        let progress = self.progress.lock().unwrap();
+        progress.clock.map(|clock| {
+            progress
+                .paused_at
+                .unwrap_or_else(Instant::now)
+                .saturating_duration_since(clock)
+                .as_secs_f64()
+                * progress.rate
+        })
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        let running = self.is_playing();
+        if !running {
+            return PlaybackStatus {
+                poisoned: self.is_poisoned(),
+                ..Default::default()
+            };
+        }
+        let position = self.position().unwrap_or(0.0);
+        let progress = self.progress.lock().unwrap();
+        PlaybackStatus {
+            running,
+            paused: self.is_paused(),
+            position_secs: position,
+            remaining_events: progress.remaining,
+            total_events: progress.total,
+            economy: self.economy.load(Ordering::SeqCst),
+            poisoned: self.is_poisoned(),
+        }
+    }
+
+    pub fn pause(&self) -> Result<(), AppError> {
+        if !self.is_playing() {
+            return Err(AppError::not_playing());
+        }
+        self.is_paused.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn resume(&self) {
+        self.is_paused.store(false, Ordering::SeqCst);
+    }
+
+    /// 是否有播放在进行（包括暂停中）
+    pub fn is_playing(&self) -> bool {
+        self.handle.lock().unwrap().is_some()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::SeqCst)
+    }
+
+    /// 等待到目标时间，期间响应暂停、停止和跳转
+    /// 暂停的时长会顺延到 start_time 上；跳转时 start_time 重新对齐到跳转位置
+    pub fn wait_until(&self, target_time: Duration, start_time: &mut Instant) -> WaitOutcome {
+        loop {
+            if self.stopping() {
+                return WaitOutcome::Stop;
+            }
+
+            if let Some(position) = self.seek_request.lock().unwrap().take() {
+                self.reanchor(start_time, position);
+                return WaitOutcome::Seek(position);
+            }
+
+            if self.is_paused() {
+                let pause_start = Instant::now();
+                self.progress.lock().unwrap().paused_at = Some(pause_start);
+                while self.is_paused() && !self.stopping() {
+                    thread::sleep(POLL_INTERVAL);
+                }
+                // 被放弃的线程不能改动新播放的进度
+                if !self.is_current() {
+                    return WaitOutcome::Stop;
+                }
+                *start_time += pause_start.elapsed();
+                let mut progress = self.progress.lock().unwrap();
+                progress.clock = Some(*start_time);
+                progress.paused_at = None;
+                continue;
+            }
+
+            // 速率改变时保持当前播放位置不变，重新计算计时起点
+            let rate = {
+                let target = self.target_rate();
+                let mut progress = self.progress.lock().unwrap();
+                if progress.clock.is_some() && progress.rate != target {
+                    let elapsed = start_time.elapsed().as_secs_f64();
+                    let now = Instant::now();
+                    let rescaled = Duration::from_secs_f64(elapsed * progress.rate / target);
+                    *start_time = now.checked_sub(rescaled).unwrap_or(now);
+                    progress.clock = Some(*start_time);
+                    progress.rate = target;
+                }
+                progress.rate
+            };
+
+            // 目标时间是相对起点的绝对时刻，每次等待的误差不会累积
+            let target_time = target_time.div_f64(rate);
+            let elapsed = start_time.elapsed();
+            if target_time <= elapsed {
+                return WaitOutcome::Ready;
+            }
+            let remaining = target_time - elapsed;
+            let (spin_window, poll_interval) = self.wait_granularity();
+            if remaining <= spin_window {
+                while start_time.elapsed() < target_time {
+                    std::hint::spin_loop();
+                }
+                continue;
+            }
+            thread::sleep((remaining - spin_window).min(poll_interval));
+        }
+    }
+}
+
+/// 应用全局状态，由 Tauri 管理，命令通过 State<AppState> 访问
+pub struct AppState {
+    pub lock: RwLock<LockState>,
+    pub keyboard: Arc<PlaybackControl>,
+    pub accompaniment: Accompaniment, // 跟随键盘播放循环的伴奏
+    pub midi_clock: MidiClock,        // 跟随键盘播放输出 MIDI 时钟
+    pub tempo_link: TempoLink,        // 通过 Ableton Link 与其他设备同步速度
+    pub mouse: Arc<PlaybackControl>,
+    pub profiles: ProfileManager,
+    pub keymaps: KeymapManager,
+    pub emergency_stop: EmergencyStop,
+    pub library: Library,
+    pub timelines: TimelineStore,
+    pub queue: QueueRunner,
+    pub now_playing: Mutex<NowPlaying>,
+    pub last_report: Mutex<Option<PlaybackReport>>,
+    pub after_playback: Mutex<AfterPlayback>,
+    pub mouse_traces: MouseTraceLibrary,
+}
+
+impl AppState {
+    /// 从配置目录和数据目录加载档案、映射表、设置与曲库
+    pub fn new(config_dir: PathBuf, data_dir: PathBuf) -> Self {
+        Self {
+            lock: RwLock::new(LockState::default()),
+            keyboard: Arc::new(PlaybackControl::default()),
+            accompaniment: Accompaniment::default(),
+            midi_clock: MidiClock::default(),
+            tempo_link: TempoLink::default(),
+            mouse: Arc::new(PlaybackControl::default()),
+            profiles: ProfileManager::load(config_dir.clone()),
+            keymaps: KeymapManager::load(config_dir.clone()),
+            emergency_stop: EmergencyStop::load(config_dir),
+            library: Library::load(data_dir.clone()),
+            mouse_traces: MouseTraceLibrary::load(data_dir.clone()),
+            timelines: TimelineStore::new(data_dir),
+            queue: QueueRunner::default(),
+            now_playing: Mutex::new(NowPlaying::default()),
+            last_report: Mutex::new(None),
+            after_playback: Mutex::new(AfterPlayback::default()),
+        }
+    }
+
+    pub fn locked_window(&self) -> Option<WindowInfo> {
+        self.lock.read().unwrap().window.clone()
+    }
+
+    pub fn clear_lock(&self) {
+        *self.lock.write().unwrap() = LockState::default();
+    }
+
+    pub fn is_any_playing(&self) -> bool {
+        self.keyboard.is_playing() || self.mouse.is_playing()
+    }
+
+    pub fn is_any_paused(&self) -> bool {
+        self.keyboard.is_paused() || self.mouse.is_paused()
+    }
+
+    pub fn pause_all(&self) {
+        let _ = self.keyboard.pause();
+        let _ = self.mouse.pause();
+    }
+
+    pub fn resume_all(&self) {
+        self.keyboard.resume();
+        self.mouse.resume();
+    }
+
+    pub fn playback_status(&self) -> PlaybackStatusReport {
+        PlaybackStatusReport {
+            keyboard: self.keyboard.status(),
+            mouse: self.mouse.status(),
+        }
+    }
+
+    pub fn stop_all(&self) {
+        self.queue.stop();
+        self.accompaniment.stop();
+        self.midi_clock.stop();
+        self.tempo_link.stop();
+        self.keyboard.stop();
+        self.mouse.stop();
+    }
+
+    /// 队列跳到下一首，不在队列播放时什么也不做
+    pub fn skip_queued_song(&self) {
+        if self.queue.is_running() {
+            self.queue.skip();
+            self.keyboard.stop();
+        }
+    }
+}
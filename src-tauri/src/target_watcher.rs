@@ -0,0 +1,44 @@
+use crate::state::AppState;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use uni_window::WindowInfo;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+// 每次锁定新窗口时递增，旧的监视线程发现不一致后退出
+static WATCH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 监视锁定窗口所属进程，进程退出时停止播放、解除锁定并发出 "target://closed"
+pub fn start(app: AppHandle, window: WindowInfo) {
+    let generation = WATCH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    if window.pid == 0 {
+        return;
+    }
+
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+
+        while WATCH_GENERATION.load(Ordering::SeqCst) == generation {
+            thread::sleep(POLL_INTERVAL);
+
+            if WATCH_GENERATION.load(Ordering::SeqCst) != generation {
+                break;
+            }
+            if uni_window::is_process_alive(window.pid) {
+                continue;
+            }
+
+            state.stop_all();
+            state.clear_lock();
+            let _ = app.emit("target://closed", window.clone());
+            break;
+        }
+    });
+}
+
+/// 停止监视（解除锁定时调用）
+pub fn stop() {
+    WATCH_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
@@ -0,0 +1,49 @@
+// 提高播放线程的调度优先级，减少录屏、直播软件等后台负载造成的发送抖动。
+// 失败时只记录日志，播放照常进行。
+
+/// 把当前线程设为高优先级，只应在播放线程中调用
+pub fn raise_current_thread() {
+    if let Err(e) = raise() {
+        log::warn!("Failed to raise playback thread priority: {}", e);
+    }
+}
+
+#[cfg(windows)]
+fn raise() -> Result<(), String> {
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_TIME_CRITICAL) }
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn raise() -> Result<(), String> {
+    const QOS_CLASS_USER_INTERACTIVE: u32 = 0x21;
+    extern "C" {
+        fn pthread_set_qos_class_self_np(qos_class: u32, relative_priority: i32) -> i32;
+    }
+    match unsafe { pthread_set_qos_class_self_np(QOS_CLASS_USER_INTERACTIVE, 0) } {
+        0 => Ok(()),
+        code => Err(format!("pthread_set_qos_class_self_np returned {}", code)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn raise() -> Result<(), String> {
+    // Linux 上 nice 值按线程生效；调低 nice 需要 CAP_SYS_NICE 权限
+    const PRIO_PROCESS: i32 = 0;
+    const NICE: i32 = -10;
+    extern "C" {
+        fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+    }
+    match unsafe { setpriority(PRIO_PROCESS, 0, NICE) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error().to_string()),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn raise() -> Result<(), String> {
+    Err("Not supported on this platform".to_string())
+}
@@ -0,0 +1,243 @@
+use crate::keypress_simulator::{EventSource, KeyEvent, SourceEvent, MAX_EVENT_SECS};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// 超长时间线（几小时的串烧）不经内存一次性传入，而是由前端分块追加到磁盘文件，
+// 播放时按顺序流式读取，内存占用只和按键种类数有关，与事件数量无关。
+
+const TIMELINES_DIR: &str = "timelines";
+// 每条记录：时间 f64 + 持续时间 f64 + 按键编号 u32，小端
+const RECORD_SIZE: u64 = 20;
+// 读取缓冲区能容纳的记录数
+const READ_CHUNK_RECORDS: usize = 4096;
+
+/// 磁盘上的一条时间线。按键字符串去重后放在内存里，事件只记录编号
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    path: PathBuf,
+    pub keys: Vec<String>,
+    pub events: usize,
+    last_time: f64,
+}
+
+/// 按句柄管理的时间线存储，文件放在数据目录下，启动时清理上次遗留的文件
+pub struct TimelineStore {
+    dir: PathBuf,
+    next_handle: Mutex<u64>,
+    timelines: Mutex<HashMap<u64, Timeline>>,
+}
+
+impl TimelineStore {
+    pub fn new(data_dir: PathBuf) -> Self {
+        let dir = data_dir.join(TIMELINES_DIR);
+        if dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                log::warn!("Failed to clear old timelines: {}", e);
+            }
+        }
+        Self {
+            dir,
+            next_handle: Mutex::new(1),
+            timelines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 新建一条空时间线，返回句柄
+    pub fn create(&self) -> Result<u64, String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+        let handle = {
+            let mut next = self.next_handle.lock().unwrap();
+            *next += 1;
+            *next - 1
+        };
+        let path = self.dir.join(format!("{}.bin", handle));
+        File::create(&path).map_err(|e| format!("Failed to create timeline: {}", e))?;
+        self.timelines.lock().unwrap().insert(
+            handle,
+            Timeline {
+                path,
+                keys: Vec::new(),
+                events: 0,
+                last_time: 0.0,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// 追加一块事件，事件必须按时间排序且不早于已追加的事件，返回总事件数
+    pub fn append(&self, handle: u64, events: &[KeyEvent]) -> Result<usize, String> {
+        let mut timelines = self.timelines.lock().unwrap();
+        let timeline = timelines
+            .get_mut(&handle)
+            .ok_or_else(|| format!("Timeline not found: {}", handle))?;
+
+        let mut last_time = timeline.last_time;
+        for event in events {
+            if !event.time.is_finite() || event.time < last_time {
+                return Err(format!(
+                    "Timeline events must be in time order (got {} after {})",
+                    event.time, last_time
+                ));
+            }
+            // 与播放前检查事件的范围一致，流式播放时不再修正负的时长
+            if event.time > MAX_EVENT_SECS {
+                return Err(format!("Invalid event time: {}", event.time));
+            }
+            if !(0.0..=MAX_EVENT_SECS).contains(&event.duration) {
+                return Err(format!("Invalid event duration: {}", event.duration));
+            }
+            last_time = event.time;
+        }
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&timeline.path)
+            .map_err(|e| format!("Failed to open timeline: {}", e))?;
+        let mut writer = BufWriter::new(file);
+        let mut keys = timeline.keys.clone();
+        for event in events {
+            let key = match keys.iter().position(|k| *k == event.key) {
+                Some(index) => index,
+                None => {
+                    keys.push(event.key.clone());
+                    keys.len() - 1
+                }
+            };
+            let mut record = [0u8; RECORD_SIZE as usize];
+            record[..8].copy_from_slice(&event.time.to_le_bytes());
+            record[8..16].copy_from_slice(&event.duration.to_le_bytes());
+            record[16..].copy_from_slice(&(key as u32).to_le_bytes());
+            writer
+                .write_all(&record)
+                .map_err(|e| format!("Failed to write timeline: {}", e))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write timeline: {}", e))?;
+
+        // 写入成功后才更新元数据
+        timeline.keys = keys;
+        timeline.events += events.len();
+        timeline.last_time = last_time;
+        Ok(timeline.events)
+    }
+
+    pub fn get(&self, handle: u64) -> Result<Timeline, String> {
+        self.timelines
+            .lock()
+            .unwrap()
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| format!("Timeline not found: {}", handle))
+    }
+
+    pub fn remove(&self, handle: u64) -> Result<(), String> {
+        let timeline = self
+            .timelines
+            .lock()
+            .unwrap()
+            .remove(&handle)
+            .ok_or_else(|| format!("Timeline not found: {}", handle))?;
+        std::fs::remove_file(&timeline.path)
+            .map_err(|e| format!("Failed to delete timeline: {}", e))
+    }
+}
+
+/// 顺序读取时间线文件，跳转时按记录二分查找
+pub struct TimelineReader {
+    reader: BufReader<File>,
+    events: usize,
+    next: usize,
+    peeked: Option<SourceEvent>,
+}
+
+impl TimelineReader {
+    pub fn open(timeline: &Timeline) -> Result<Self, String> {
+        let file =
+            File::open(&timeline.path).map_err(|e| format!("Failed to open timeline: {}", e))?;
+        Ok(Self {
+            reader: BufReader::with_capacity(READ_CHUNK_RECORDS * RECORD_SIZE as usize, file),
+            events: timeline.events,
+            next: 0,
+            peeked: None,
+        })
+    }
+
+    fn read_record(&mut self) -> std::io::Result<SourceEvent> {
+        let mut record = [0u8; RECORD_SIZE as usize];
+        self.reader.read_exact(&mut record)?;
+        let f64_at = |at: usize| f64::from_le_bytes(record[at..at + 8].try_into().unwrap());
+        Ok(SourceEvent {
+            time: f64_at(0),
+            duration: f64_at(8),
+            key: u32::from_le_bytes(record[16..].try_into().unwrap()) as usize,
+        })
+    }
+
+    fn read_at(&mut self, index: usize) -> std::io::Result<SourceEvent> {
+        self.reader
+            .seek(SeekFrom::Start(index as u64 * RECORD_SIZE))?;
+        self.read_record()
+    }
+
+    // 第一个时间不早于 position 的事件
+    fn find(&mut self, position: f64) -> std::io::Result<usize> {
+        let (mut lo, mut hi) = (0, self.events);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.read_at(mid)?.time < position {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+}
+
+impl EventSource for TimelineReader {
+    fn peek(&mut self) -> Option<SourceEvent> {
+        if self.peeked.is_none() && self.next < self.events {
+            match self.read_record() {
+                Ok(event) => self.peeked = Some(event),
+                Err(e) => {
+                    // 读取失败时提前结束，剩余事件不再播放
+                    log::warn!("Failed to read timeline: {}", e);
+                    self.next = self.events;
+                }
+            }
+        }
+        self.peeked
+    }
+
+    fn advance(&mut self) {
+        if self.peek().is_some() {
+            self.peeked = None;
+            self.next += 1;
+        }
+    }
+
+    fn seek(&mut self, position: f64) {
+        self.peeked = None;
+        let result = self.find(position).and_then(|index| {
+            self.reader
+                .seek(SeekFrom::Start(index as u64 * RECORD_SIZE))?;
+            Ok(index)
+        });
+        self.next = match result {
+            Ok(index) => index,
+            Err(e) => {
+                log::warn!("Failed to seek timeline: {}", e);
+                self.events
+            }
+        };
+    }
+
+    fn remaining(&self) -> usize {
+        self.events - self.next
+    }
+}
@@ -0,0 +1,40 @@
+// Windows 默认的计时器精度约为 15.6ms，sleep 会被量化到这个粒度，快速段落明显不准。
+// 播放期间把系统计时器精度提高到 1ms，结束后恢复。其他平台无需处理。
+
+#[cfg(windows)]
+const PERIOD_MS: u32 = 1;
+
+/// 持有期间系统计时器保持 1ms 精度
+pub struct TimerResolutionGuard {
+    #[cfg(windows)]
+    active: bool,
+}
+
+impl TimerResolutionGuard {
+    #[cfg(windows)]
+    pub fn acquire() -> Self {
+        use windows::Win32::Media::{timeBeginPeriod, TIMERR_NOERROR};
+        let active = unsafe { timeBeginPeriod(PERIOD_MS) } == TIMERR_NOERROR;
+        if !active {
+            log::warn!("Failed to raise timer resolution");
+        }
+        Self { active }
+    }
+
+    #[cfg(not(windows))]
+    pub fn acquire() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(windows)]
+impl Drop for TimerResolutionGuard {
+    fn drop(&mut self) {
+        use windows::Win32::Media::timeEndPeriod;
+        if self.active {
+            unsafe {
+                timeEndPeriod(PERIOD_MS);
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::library;
+use crate::midi_analyzer::{self, AnalyzeOptions, MidiAnalysis};
+use crate::score_file::{self, OpenedScore};
+use crate::state::AppState;
+use serde::Serialize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+// 从网址下载乐曲（社区曲单大多是链接），保存到数据目录并直接分析。
+// 只接受 http(s) 和已知的乐曲格式，限制大小，可选校验 SHA-256。
+
+const DOWNLOADS_DIR: &str = "downloads";
+const MAX_DOWNLOAD_BYTES: u64 = 10 * 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: u32 = 5;
+// 每下载这么多字节发送一次进度
+const PROGRESS_STEP: u64 = 64 * 1024;
+const ALLOWED_EXTENSIONS: &[&str] = &["mid", "midi", "ly", "gp5", "gpx", "autoscore"];
+const MIDI_CONTENT_TYPES: &[&str] = &["audio/midi", "audio/x-midi", "audio/mid"];
+
+#[derive(Debug, Clone, Serialize)]
+struct DownloadProgress<'a> {
+    url: &'a str,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// 下载结果：乐曲文件按给定选项分析，.autoscore 文件按其中保存的设置打开
+#[derive(Debug, Clone, Serialize)]
+pub struct UrlImport {
+    pub file_path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub analysis: Option<MidiAnalysis>,
+    pub score: Option<OpenedScore>,
+}
+
+pub fn import_from_url(
+    app: &AppHandle,
+    data_dir: &Path,
+    url: &str,
+    expected_sha256: Option<&str>,
+    options: &AnalyzeOptions,
+) -> Result<UrlImport, String> {
+    let lower = url.to_ascii_lowercase();
+    if !lower.starts_with("http://") && !lower.starts_with("https://") {
+        return Err(format!("Unsupported URL: {}", url));
+    }
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .redirects(MAX_REDIRECTS)
+        .build();
+    let response = agent.get(url).call().map_err(|e| match e {
+        ureq::Error::Status(code, _) => format!("Download failed: HTTP {}", code),
+        e => format!("Download failed: {}", e),
+    })?;
+
+    let (stem, extension) = file_name(response.get_url(), response.content_type())?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    if total.is_some_and(|t| t > MAX_DOWNLOAD_BYTES) {
+        return Err(too_large());
+    }
+
+    // 多读一个字节用来判断是否超过上限
+    let mut reader = response.into_reader().take(MAX_DOWNLOAD_BYTES + 1);
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut buffer = [0u8; 16 * 1024];
+    let mut next_report = 0;
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|e| format!("Download failed: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&buffer[..read]);
+        if bytes.len() as u64 > MAX_DOWNLOAD_BYTES {
+            return Err(too_large());
+        }
+        if bytes.len() as u64 >= next_report {
+            emit_progress(app, url, bytes.len() as u64, total);
+            next_report = bytes.len() as u64 + PROGRESS_STEP;
+        }
+    }
+    emit_progress(app, url, bytes.len() as u64, total);
+
+    let sha256 = library::content_hash(&bytes);
+    if let Some(expected) = expected_sha256 {
+        if !expected.trim().eq_ignore_ascii_case(&sha256) {
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected.trim(),
+                sha256
+            ));
+        }
+    }
+    if matches!(extension.as_str(), "mid" | "midi") && !bytes.starts_with(b"MThd") {
+        return Err("Downloaded file is not a MIDI file".to_string());
+    }
+
+    // 文件名带上内容哈希，重复下载同一首不会产生多份
+    let dir = data_dir.join(DOWNLOADS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}-{}.{}", stem, &sha256[..8], extension));
+    fs::write(&path, &bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let file_path = path.display().to_string();
+    log::info!(
+        "Downloaded {} ({} bytes) to {}",
+        url,
+        bytes.len(),
+        file_path
+    );
+
+    let state = app.state::<AppState>();
+    let (analysis, score) = if extension == "autoscore" {
+        (None, Some(score_file::open(&path, &state)?))
+    } else {
+        let mut analysis = midi_analyzer::analyze_midi_file(&file_path, options)?;
+        state
+            .library
+            .apply_track_labels(&file_path, &mut analysis.tracks);
+        state
+            .library
+            .record_metadata(&file_path, &analysis.metadata);
+        (Some(analysis), None)
+    };
+    Ok(UrlImport {
+        file_path,
+        size: bytes.len() as u64,
+        sha256,
+        analysis,
+        score,
+    })
+}
+
+fn too_large() -> String {
+    format!(
+        "File is larger than the {} MB download limit",
+        MAX_DOWNLOAD_BYTES / 1024 / 1024
+    )
+}
+
+fn emit_progress(app: &AppHandle, url: &str, downloaded: u64, total: Option<u64>) {
+    let _ = app.emit(
+        "import://progress",
+        DownloadProgress {
+            url,
+            downloaded,
+            total,
+        },
+    );
+}
+
+// 从最终网址（跟随重定向后）取文件名，没有可识别的扩展名时按 Content-Type 判断是否为 MIDI
+fn file_name(url: &str, content_type: &str) -> Result<(String, String), String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let last = path.rsplit('/').next().unwrap_or("");
+    let (stem, extension) = match last.rsplit_once('.') {
+        Some((stem, ext)) => (stem, ext.to_ascii_lowercase()),
+        None => (last, String::new()),
+    };
+    let extension = if ALLOWED_EXTENSIONS.contains(&extension.as_str()) {
+        extension
+    } else if MIDI_CONTENT_TYPES.contains(&content_type.to_ascii_lowercase().as_str()) {
+        "mid".to_string()
+    } else {
+        return Err(format!("Unsupported file type: {}", last));
+    };
+
+    // 只保留安全的字符，网址中的编码和路径符号一律替换
+    let stem: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect();
+    let stem = if stem.is_empty() {
+        "download".to_string()
+    } else {
+        stem
+    };
+    Ok((stem, extension))
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use uni_window::Rect;
+
+/// 带参数的警告代码，由前端按 code 本地化显示
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", content = "params", rename_all = "snake_case")]
+pub enum Warning {
+    // 解析
+    NotesBelowRange {
+        count: usize,
+        min_note: u8,
+    },
+    NotesAboveRange {
+        count: usize,
+        max_note: u8,
+    },
+    NotesFolded {
+        count: usize,
+    },
+    NotesReduced {
+        count: usize,
+    },
+    NotesMerged {
+        count: usize,
+    },
+    NotesSimplified {
+        count: usize,
+    },
+    MelodyExtracted {
+        count: usize,
+    },
+    LongNotesTrimmed {
+        count: usize,
+    },
+    UnclosedNotes {
+        count: usize,
+    },
+    PercussionSkipped {
+        count: usize,
+    },
+    SectionsTrimmed {
+        count: usize,
+    },
+    SilenceCompressed {
+        gaps: usize,
+        removed_secs: f64,
+    },
+    // 播放
+    KeyGhosting {
+        count: usize,
+        revoiced: bool,
+    },
+    EventsFailed {
+        count: usize,
+    },
+    HighJitter {
+        avg_ms: f64,
+    },
+    // 锁定窗口
+    WindowResized {
+        from_width: u32,
+        from_height: u32,
+        to_width: u32,
+        to_height: u32,
+    },
+    RegionOutsideWindow {
+        region: Rect,
+    },
+    WindowMinimized,
+    ExclusiveFullscreen,
+}
@@ -0,0 +1,116 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+
+/// 简单的 XML 元素树
+#[derive(Debug, Default)]
+pub(crate) struct Node {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    pub fn path(&self, path: &[&str]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, name| node.child(name))
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.trim()
+    }
+
+    pub fn find(&self, predicate: &dyn Fn(&Node) -> bool) -> Option<&Node> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(predicate))
+    }
+
+    // 按 id 属性索引子元素，如 <Notes><Note id="0">...</Notes>
+    pub fn index_by_id(&self) -> HashMap<&str, &Node> {
+        self.children
+            .iter()
+            .filter_map(|c| c.attr("id").map(|id| (id, c)))
+            .collect()
+    }
+}
+
+pub(crate) fn parse_xml(text: &str) -> Result<Node, String> {
+    let mut reader = Reader::from_str(text);
+    let mut stack = vec![Node::default()];
+
+    let start_node = |e: &quick_xml::events::BytesStart| Node {
+        name: String::from_utf8_lossy(e.local_name().as_ref()).to_string(),
+        attrs: e
+            .attributes()
+            .flatten()
+            .map(|a| {
+                (
+                    String::from_utf8_lossy(a.key.as_ref()).to_string(),
+                    String::from_utf8_lossy(&a.value).to_string(),
+                )
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Failed to parse XML: {}", e))?;
+        match event {
+            Event::Start(e) => stack.push(start_node(&e)),
+            Event::Empty(e) => {
+                let node = start_node(&e);
+                stack.last_mut().unwrap().children.push(node);
+            }
+            Event::End(_) => {
+                let node = stack.pop().unwrap();
+                let parent = stack.last_mut().ok_or("Failed to parse XML")?;
+                parent.children.push(node);
+            }
+            Event::Text(e) => {
+                let text = String::from_utf8_lossy(&e.into_inner()).to_string();
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::CData(e) => {
+                let text = String::from_utf8_lossy(&e.into_inner()).to_string();
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::GeneralRef(e) => {
+                let text = match e.into_inner().as_ref() {
+                    b"amp" => "&",
+                    b"lt" => "<",
+                    b"gt" => ">",
+                    b"quot" => "\"",
+                    b"apos" => "'",
+                    _ => "",
+                };
+                stack.last_mut().unwrap().text.push_str(text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut root = stack.pop().ok_or("Failed to parse XML")?;
+    root.children
+        .pop()
+        .ok_or_else(|| "Empty XML document".to_string())
+}